@@ -25,10 +25,49 @@ pub mod templates {
 
 pub mod server {
     pub use suika_server::error::HttpError;
+    pub use suika_server::proxy::{Cidr, TrustedProxies};
+    pub use suika_server::proxy_protocol::ProxyProtocolMode;
     pub use suika_server::router::Router;
     pub use suika_server::server::Server;
 }
 
+pub mod auth {
+    pub use suika_server::auth::{BasicVerifier, BearerVerifier, Principal};
+}
+
+pub mod guard {
+    pub use suika_server::guard::{require_authenticated, require_permission, require_role, Guard};
+}
+
+pub mod jwt {
+    pub use suika_server::jwt::{
+        decode, encode, Algorithm, Jwk, JwkSet, JwtError, JwtKey, SigningKey, Validation,
+    };
+}
+
+pub mod access_log {
+    pub use suika_server::access_log::{
+        AccessLogEntry, ChannelSink, FileSink, LogFormat, LogSink, StdoutSink,
+    };
+}
+
+pub mod assets {
+    pub use suika_server::assets::AssetManifest;
+}
+
+pub mod embed {
+    pub use suika_server::embed::{EmbeddedAssets, EmbeddedFile, Embedder};
+    pub use suika_server::include_assets;
+}
+
+pub mod extensions {
+    pub use suika_server::extensions::Extensions;
+}
+
+pub mod state {
+    pub use suika_server::state::{AppState, StateKey};
+}
+
 pub mod middleware {
     pub use suika_server::middleware::*;
 }
@@ -7,7 +7,9 @@ use std::sync::Arc;
 
 use suika::{
     macros::json,
-    middleware::{CorsMiddleware, FaviconMiddleware, LoggerMiddleware, StaticFileMiddleware},
+    middleware::{
+        CorsMiddleware, CsrfMiddleware, FaviconMiddleware, LoggerMiddleware, StaticFileMiddleware,
+    },
     server::{Router, Server},
     templates::{Context, TemplateEngine},
 };
@@ -42,7 +44,8 @@ fn main() {
     );
 
     server.use_templates(template_engine);
-    server.use_module("todo_store", todo_store);
+    server.with_state(todo_store);
+    main_router.requires_state::<TodoStore>();
 
     main_router.get(r"/$", |_req, res| {
         Box::pin(async move {
@@ -55,34 +58,38 @@ fn main() {
 
     main_router.get("/todos", |req, res| {
         Box::pin(async move {
-            if let Some(store) = req.module::<TodoStore>("todo_store") {
-                let todos = store.to_json();
-                let mut context = Context::new();
-                context.insert("todos", todos);
-
-                res.set_status(200).await;
-                res.header("Content-Type", "text/html").await;
-                res.render_template("todos/list.html", &context).await?;
-            } else {
-                res.set_status(404).await;
-                res.body("No todos found".to_string()).await;
-            }
+            let todos = req.state::<TodoStore>().to_json();
+            let mut context = Context::new();
+            context.insert("todos", todos);
+
+            res.set_status(200).await;
+            res.header("Content-Type", "text/html").await;
+            res.render_template("todos/list.html", &context).await?;
+
+            Ok(())
+        })
+    });
+
+    main_router.post("/todos", |req, res| {
+        Box::pin(async move {
+            let form = req.form_data().cloned().unwrap_or_default();
+            let title = form.get("title").cloned().unwrap_or_default();
+            let content = form.get("content").cloned().unwrap_or_default();
+
+            req.state::<TodoStore>().add_todo(title, content);
 
+            res.set_status(303).await;
+            res.header("Location", "/todos").await;
             Ok(())
         })
     });
 
     main_router.get("/todo_json", |req, res| {
         Box::pin(async move {
-            if let Some(store) = req.module::<TodoStore>("todo_store") {
-                let todos = store.to_json();
-
-                res.set_status(200).await;
-                res.body_json(todos).await;
-            } else {
-                res.set_status(404).await;
-                res.body("No todos found".to_string()).await;
-            }
+            let todos = req.state::<TodoStore>().to_json();
+
+            res.set_status(200).await;
+            res.body_json(todos).await;
             Ok(())
         })
     });
@@ -190,8 +197,9 @@ fn main() {
         })
     });
 
-    server.use_middleware(Arc::new(CorsMiddleware));
-    server.use_middleware(Arc::new(LoggerMiddleware));
+    server.use_middleware(Arc::new(CorsMiddleware::new()));
+    server.use_middleware(Arc::new(LoggerMiddleware::new()));
+    server.use_middleware(Arc::new(CsrfMiddleware::new()));
 
     server.use_middleware(Arc::new(FaviconMiddleware::new(
         "crates/suika_example/public/favicon.ico",
@@ -74,12 +74,13 @@ impl JsonValue {
     /// ]);
     /// assert_eq!(json_object.to_string(), "{\"key\":\"value\"}");
     /// ```
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(&self) -> String {
         match self {
             JsonValue::Object(obj) => {
                 let entries: Vec<String> = obj
                     .iter()
-                    .map(|(k, v)| format!("\"{}\":{}", k, v.to_string()))
+                    .map(|(k, v)| format!("{}:{}", quote(k), v.to_string()))
                     .collect();
                 format!("{{{}}}", entries.join(","))
             }
@@ -87,7 +88,7 @@ impl JsonValue {
                 let entries: Vec<String> = arr.iter().map(|v| v.to_string()).collect();
                 format!("[{}]", entries.join(","))
             }
-            JsonValue::String(s) => format!("\"{}\"", s),
+            JsonValue::String(s) => quote(s),
             JsonValue::Number(n) => n.to_string(),
             JsonValue::Boolean(b) => b.to_string(),
             JsonValue::Null => "null".to_string(),
@@ -95,6 +96,102 @@ impl JsonValue {
     }
 }
 
+/// Quotes a string for JSON output, escaping quotes, backslashes and control characters.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\x08' => quoted.push_str("\\b"),
+            '\x0c' => quoted.push_str("\\f"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl JsonValue {
+    /// Returns the value of a field if this is an object containing `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    ///
+    /// let object = JsonValue::Object(vec![
+    ///     ("key".to_string(), JsonValue::String("value".to_string()))
+    /// ]);
+    /// assert_eq!(object.get("key"), Some(&JsonValue::String("value".to_string())));
+    /// assert_eq!(object.get("missing"), None);
+    /// assert_eq!(JsonValue::Null.get("key"), None);
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the string slice if this is a string value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    ///
+    /// assert_eq!(JsonValue::String("hello".to_string()).as_str(), Some("hello"));
+    /// assert_eq!(JsonValue::Number(1.0).as_str(), None);
+    /// ```
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if this is a number value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    ///
+    /// assert_eq!(JsonValue::Number(1.5).as_f64(), Some(1.5));
+    /// assert_eq!(JsonValue::Null.as_f64(), None);
+    /// ```
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements if this is an array value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    ///
+    /// let array = JsonValue::Array(vec![JsonValue::Boolean(true)]);
+    /// assert_eq!(array.as_array().map(|a| a.len()), Some(1));
+    /// assert_eq!(JsonValue::Null.as_array(), None);
+    /// ```
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for JsonValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -237,6 +334,22 @@ impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parse_json;
+
+    #[test]
+    fn test_json_value_escapes_strings() {
+        let value = JsonValue::Object(vec![(
+            "say \"hi\"".to_string(),
+            JsonValue::String("line\nbreak \\ tab\t bell\x07".to_string()),
+        )]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"say \"hi\"":"line\nbreak \\ tab\t bell\u0007"}"#
+        );
+
+        let value = JsonValue::String("quote \" and \r\n".to_string());
+        assert_eq!(parse_json(&value.to_string()).unwrap(), value);
+    }
 
     #[test]
     fn test_json_value_display() {
@@ -300,6 +413,30 @@ mod tests {
         assert_eq!(empty_array.to_string(), "[]");
     }
 
+    #[test]
+    fn test_accessors() {
+        let value = JsonValue::Object(vec![
+            ("name".to_string(), JsonValue::String("Alice".to_string())),
+            ("age".to_string(), JsonValue::Number(30.0)),
+            (
+                "roles".to_string(),
+                JsonValue::Array(vec![JsonValue::String("admin".to_string())]),
+            ),
+        ]);
+
+        assert_eq!(value.get("name").and_then(JsonValue::as_str), Some("Alice"));
+        assert_eq!(value.get("age").and_then(JsonValue::as_f64), Some(30.0));
+        assert_eq!(
+            value
+                .get("roles")
+                .and_then(JsonValue::as_array)
+                .map(|r| r.len()),
+            Some(1)
+        );
+        assert_eq!(value.get("name").and_then(JsonValue::as_f64), None);
+        assert_eq!(value.get("missing"), None);
+    }
+
     #[test]
     fn test_from_string() {
         let json = JsonValue::from("hello");
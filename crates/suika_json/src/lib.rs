@@ -38,19 +38,15 @@ pub const NULL: () = ();
 #[macro_export]
 macro_rules! json {
     ({ $($key:expr => $value:expr),* $(,)? }) => {{
-        let mut map = Vec::new();
-        $(
-            map.push(($key.to_string(), json!($value)));
-        )*
-        $crate::JsonValue::Object(map)
+        $crate::JsonValue::Object(vec![
+            $(($key.to_string(), json!($value)),)*
+        ])
     }};
 
     ([ $($value:expr),* $(,)? ]) => {{
-        let mut vec = Vec::new();
-        $(
-            vec.push(json!($value));
-        )*
-        $crate::JsonValue::Array(vec)
+        $crate::JsonValue::Array(vec![
+            $(json!($value),)*
+        ])
     }};
 
     (NULL) => { $crate::JsonValue::Null };
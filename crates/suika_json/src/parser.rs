@@ -187,7 +187,7 @@ impl<'a> JsonParser<'a> {
         }
 
         while let Some(c) = self.current_char {
-            if c.is_digit(10) || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-' {
+            if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-' {
                 number_str.push(c);
                 self.next_char();
             } else {
@@ -230,6 +230,56 @@ pub fn get_mime_type_from_path(path: &str) -> String {
         .map_or("application/octet-stream".to_string(), get_mime_type)
 }
 
+/// Returns `true` if content of the given MIME type benefits from HTTP compression.
+///
+/// Formats that are already compressed, such as most images, audio, video, archives
+/// and office documents, return `false`. Parameters like `; charset=utf-8` are ignored.
+///
+/// # Arguments
+///
+/// * `mime_type` - A string slice that holds the MIME type, e.g. a `Content-Type` value.
+///
+/// # Examples
+///
+/// ```
+/// use suika_mime::is_compressible;
+///
+/// assert!(is_compressible("text/html; charset=utf-8"));
+/// assert!(is_compressible("application/json"));
+/// assert!(is_compressible("image/svg+xml"));
+/// assert!(!is_compressible("image/png"));
+/// assert!(!is_compressible("application/gzip"));
+/// ```
+pub fn is_compressible(mime_type: &str) -> bool {
+    let essence = mime_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    match essence.as_str() {
+        "image/svg+xml" | "image/bmp" | "image/x-icon" | "audio/wav" => true,
+        "application/pdf"
+        | "application/zip"
+        | "application/gzip"
+        | "application/x-bzip2"
+        | "application/x-7z-compressed"
+        | "application/vnd.rar" => false,
+        _ if essence.starts_with("application/vnd.openxmlformats-officedocument.")
+            || essence.starts_with("application/vnd.oasis.opendocument.") =>
+        {
+            false
+        }
+        _ => {
+            !(essence.starts_with("image/")
+                || essence.starts_with("audio/")
+                || essence.starts_with("video/")
+                || essence.starts_with("font/woff"))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -365,4 +415,19 @@ mod tests {
             "application/wasm".to_string()
         ); // Test for WebAssembly MIME type from path
     }
+
+    #[test]
+    fn test_is_compressible() {
+        assert!(is_compressible("text/html"));
+        assert!(is_compressible("text/css; charset=utf-8"));
+        assert!(is_compressible("application/javascript"));
+        assert!(is_compressible("application/problem+json"));
+        assert!(is_compressible("image/svg+xml"));
+        assert!(!is_compressible("image/jpeg"));
+        assert!(!is_compressible("video/mp4"));
+        assert!(!is_compressible("audio/mpeg"));
+        assert!(!is_compressible("application/zip"));
+        assert!(!is_compressible("application/vnd.oasis.opendocument.text"));
+        assert!(!is_compressible("font/woff2"));
+    }
 }
@@ -0,0 +1,641 @@
+use crate::diagnostics::log_error;
+use crate::http_date::{format_common_log_date, format_rfc3339};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Result as IoResult, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use suika_json::JsonValue;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// A record of a handled request, written to the access log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    /// The time the request was received.
+    pub timestamp: SystemTime,
+    /// The IP address of the client.
+    pub client_ip: Option<IpAddr>,
+    /// The identifier of the authenticated principal.
+    pub user: Option<String>,
+    /// The request method.
+    pub method: String,
+    /// The request target, including the query string.
+    pub target: String,
+    /// The HTTP version of the request.
+    pub version: String,
+    /// The response status code.
+    pub status: u16,
+    /// The size of the response body in bytes, if known.
+    pub bytes: Option<u64>,
+    /// The time taken to handle the request.
+    pub latency: Duration,
+    /// The `User-Agent` request header.
+    pub user_agent: Option<String>,
+    /// The `Referer` request header.
+    pub referer: Option<String>,
+    /// The request ID, from the `X-Request-Id` header.
+    pub request_id: Option<String>,
+    /// The error returned by the handler, if any.
+    pub error: Option<String>,
+}
+
+/// The format of access log lines.
+///
+/// Custom formats are strings with placeholders in braces, which are replaced by the
+/// entry's values or `-` for missing values:
+///
+/// | Placeholder | Value |
+/// | --- | --- |
+/// | `{client_ip}` | The client IP address |
+/// | `{user}` | The authenticated principal |
+/// | `{time}` | The Common Log Format timestamp, `10/Oct/2000:13:55:36 +0000` |
+/// | `{time_iso}` | The RFC 3339 timestamp, `2000-10-10T13:55:36.000Z` |
+/// | `{method}`, `{target}`, `{version}` | The parts of the request line |
+/// | `{request_line}` | The request line, `GET /index.html HTTP/1.1` |
+/// | `{status}` | The response status code |
+/// | `{bytes}` | The response body size |
+/// | `{latency_ms}`, `{latency_us}` | The handling time in milli- or microseconds |
+/// | `{user_agent}`, `{referer}` | The request headers |
+/// | `{request_id}` | The request ID |
+/// | `{error}` | The handler error |
+///
+/// Unknown placeholders are written as is.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::access_log::LogFormat;
+///
+/// let format = LogFormat::Custom("{method} {target} {status} {latency_ms}ms".to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogFormat {
+    /// The Common Log Format: `127.0.0.1 - alice [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326`.
+    Common,
+    /// The Combined Log Format, which adds the quoted referer and user agent to the
+    /// Common Log Format.
+    Combined,
+    /// One JSON object per line.
+    Json,
+    /// A custom format string.
+    Custom(String),
+}
+
+impl LogFormat {
+    /// Formats an entry as a log line, without a trailing newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::access_log::{AccessLogEntry, LogFormat};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let entry = AccessLogEntry {
+    ///     timestamp: UNIX_EPOCH + Duration::from_secs(971_186_136),
+    ///     client_ip: Some("127.0.0.1".parse().unwrap()),
+    ///     user: None,
+    ///     method: "GET".to_string(),
+    ///     target: "/index.html".to_string(),
+    ///     version: "HTTP/1.1".to_string(),
+    ///     status: 200,
+    ///     bytes: Some(2326),
+    ///     latency: Duration::from_millis(3),
+    ///     user_agent: None,
+    ///     referer: None,
+    ///     request_id: None,
+    ///     error: None,
+    /// };
+    ///
+    /// assert_eq!(
+    ///     LogFormat::Common.format(&entry),
+    ///     "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /index.html HTTP/1.1\" 200 2326"
+    /// );
+    /// ```
+    pub fn format(&self, entry: &AccessLogEntry) -> String {
+        match self {
+            LogFormat::Common => common_log_line(entry),
+            LogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                common_log_line(entry),
+                entry.referer.as_deref().map_or("-".to_string(), escape),
+                entry.user_agent.as_deref().map_or("-".to_string(), escape)
+            ),
+            LogFormat::Json => json_log_line(entry),
+            LogFormat::Custom(format) => custom_log_line(format, entry),
+        }
+    }
+}
+
+/// Formats an entry in the Common Log Format.
+fn common_log_line(entry: &AccessLogEntry) -> String {
+    format!(
+        "{} - {} [{}] \"{}\" {} {}",
+        entry.client_ip.map_or("-".to_string(), |ip| ip.to_string()),
+        entry.user.as_deref().map_or("-".to_string(), escape),
+        format_common_log_date(entry.timestamp),
+        escape(&request_line(entry)),
+        entry.status,
+        entry
+            .bytes
+            .map_or("-".to_string(), |bytes| bytes.to_string())
+    )
+}
+
+/// Formats an entry as a JSON object.
+fn json_log_line(entry: &AccessLogEntry) -> String {
+    let optional = |value: &Option<String>| match value {
+        Some(value) => JsonValue::String(value.clone()),
+        None => JsonValue::Null,
+    };
+    JsonValue::Object(vec![
+        ("time".to_string(), format_rfc3339(entry.timestamp).into()),
+        (
+            "client_ip".to_string(),
+            optional(&entry.client_ip.map(|ip| ip.to_string())),
+        ),
+        ("user".to_string(), optional(&entry.user)),
+        ("method".to_string(), entry.method.clone().into()),
+        ("target".to_string(), entry.target.clone().into()),
+        ("version".to_string(), entry.version.clone().into()),
+        ("status".to_string(), (entry.status as u64).into()),
+        (
+            "bytes".to_string(),
+            entry.bytes.map_or(JsonValue::Null, JsonValue::from),
+        ),
+        (
+            "latency_ms".to_string(),
+            JsonValue::Number(entry.latency.as_secs_f64() * 1000.0),
+        ),
+        ("user_agent".to_string(), optional(&entry.user_agent)),
+        ("referer".to_string(), optional(&entry.referer)),
+        ("request_id".to_string(), optional(&entry.request_id)),
+        ("error".to_string(), optional(&entry.error)),
+    ])
+    .to_string()
+}
+
+/// Formats an entry with a custom format string.
+fn custom_log_line(format: &str, entry: &AccessLogEntry) -> String {
+    let mut line = String::with_capacity(format.len() + 64);
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(len) => start + len,
+            None => break,
+        };
+        line.push_str(&rest[..start]);
+        match placeholder_value(&rest[start + 1..end], entry) {
+            Some(value) => line.push_str(&value),
+            None => line.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    line.push_str(rest);
+    line
+}
+
+/// Returns the value for a custom format placeholder, or `None` if it is unknown.
+fn placeholder_value(name: &str, entry: &AccessLogEntry) -> Option<String> {
+    let optional = |value: &Option<String>| value.as_deref().map_or("-".to_string(), escape);
+    let value = match name {
+        "client_ip" => entry.client_ip.map_or("-".to_string(), |ip| ip.to_string()),
+        "user" => optional(&entry.user),
+        "time" => format_common_log_date(entry.timestamp),
+        "time_iso" => format_rfc3339(entry.timestamp),
+        "method" => escape(&entry.method),
+        "target" => escape(&entry.target),
+        "version" => escape(&entry.version),
+        "request_line" => escape(&request_line(entry)),
+        "status" => entry.status.to_string(),
+        "bytes" => entry
+            .bytes
+            .map_or("-".to_string(), |bytes| bytes.to_string()),
+        "latency_ms" => entry.latency.as_millis().to_string(),
+        "latency_us" => entry.latency.as_micros().to_string(),
+        "user_agent" => optional(&entry.user_agent),
+        "referer" => optional(&entry.referer),
+        "request_id" => optional(&entry.request_id),
+        "error" => optional(&entry.error),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Returns the request line of an entry.
+fn request_line(entry: &AccessLogEntry) -> String {
+    format!("{} {} {}", entry.method, entry.target, entry.version)
+}
+
+/// Escapes quotes, backslashes and control characters so that values taken from the
+/// request cannot break up or forge log lines.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A destination for access log lines.
+///
+/// Sinks are called from request handling, so `write` should not block for long.
+/// Failures are handled by the sink rather than failing the request.
+pub trait LogSink: Send + Sync {
+    /// Writes a formatted log line, which does not end with a newline.
+    fn write(&self, line: &str);
+}
+
+/// A sink writing log lines to standard output.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&self, line: &str) {
+        let _ = writeln!(io::stdout().lock(), "{}", line);
+    }
+}
+
+/// A sink sending log lines to a channel, for processing by another task.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::access_log::{ChannelSink, LogSink};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (sink, mut receiver) = ChannelSink::unbounded();
+///     let collector = tokio::spawn(async move {
+///         // Ship lines to a log collector.
+///         receiver.recv().await
+///     });
+///
+///     sink.write("127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET / HTTP/1.1\" 200 5");
+///     assert!(collector.await.unwrap().is_some());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: UnboundedSender<String>,
+}
+
+impl ChannelSink {
+    /// Creates a new `ChannelSink` sending log lines to `sender`.
+    pub fn new(sender: UnboundedSender<String>) -> Self {
+        Self { sender }
+    }
+
+    /// Creates a new `ChannelSink` and the receiver for its log lines.
+    pub fn unbounded() -> (Self, UnboundedReceiver<String>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self::new(sender), receiver)
+    }
+}
+
+impl LogSink for ChannelSink {
+    fn write(&self, line: &str) {
+        let _ = self.sender.send(line.to_string());
+    }
+}
+
+/// The state of an open log file.
+struct LogFile {
+    file: File,
+    size: u64,
+    opened_at: Instant,
+}
+
+/// Appends lines to a log file and rotates it, on the writer thread of a `FileSink`.
+struct FileWriter {
+    path: PathBuf,
+    max_size: Option<u64>,
+    rotation_interval: Option<Duration>,
+    max_files: usize,
+    file: LogFile,
+}
+
+impl FileWriter {
+    /// Returns `true` if the file should be rotated before writing `len` more bytes.
+    fn should_rotate(&self, len: u64) -> bool {
+        let too_large = self
+            .max_size
+            .is_some_and(|max_size| self.file.size > 0 && self.file.size + len > max_size);
+        let too_old = self
+            .rotation_interval
+            .is_some_and(|interval| self.file.opened_at.elapsed() >= interval);
+        too_large || too_old
+    }
+
+    /// Shifts the rotated files and starts a new log file.
+    fn rotate(&self) -> IoResult<LogFile> {
+        let rotated = |index: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        };
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(rotated(self.max_files)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            for index in (1..self.max_files).rev() {
+                match fs::rename(rotated(index), rotated(index + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        open_log_file(&self.path)
+    }
+
+    /// Appends a line, rotating the file first if needed.
+    fn write(&mut self, line: &str) {
+        let len = line.len() as u64 + 1;
+
+        if self.should_rotate(len) {
+            match self.rotate() {
+                Ok(rotated) => self.file = rotated,
+                Err(e) => {
+                    log_error!(path = %self.path.display(), error = %e, "Failed to rotate access log")
+                }
+            }
+        }
+
+        match writeln!(self.file.file, "{}", line) {
+            Ok(()) => self.file.size += len,
+            Err(e) => {
+                log_error!(path = %self.path.display(), error = %e, "Failed to write access log")
+            }
+        }
+    }
+}
+
+/// A sink appending log lines to a file, with rotation by size and age.
+///
+/// When the file would grow past the maximum size, or has been open for longer than
+/// the rotation interval, it is renamed to `<path>.1`, previous rotations are shifted
+/// to `<path>.2` and so on, and a new file is started. The oldest files beyond the
+/// configured number of rotated files are removed.
+///
+/// Lines are queued to a dedicated thread that does the file I/O, so a slow disk or a
+/// rotation never blocks the tasks handling requests. Dropping the sink waits for the
+/// queued lines to be written.
+///
+/// # Examples
+///
+/// ```no_run
+/// use suika_server::access_log::FileSink;
+/// use std::time::Duration;
+///
+/// let sink = FileSink::new("logs/access.log")
+///     .unwrap()
+///     .with_max_size(10 * 1024 * 1024)
+///     .with_rotation_interval(Duration::from_secs(24 * 60 * 60))
+///     .with_max_files(7);
+/// ```
+pub struct FileSink {
+    path: PathBuf,
+    writer: Mutex<Option<FileWriter>>,
+    sender: OnceLock<Sender<String>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FileSink {
+    /// Creates a new `FileSink` appending to the file at `path`, creating it if needed.
+    ///
+    /// Files are not rotated until a maximum size or rotation interval is set.
+    pub fn new<P: AsRef<Path>>(path: P) -> IoResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_log_file(&path)?;
+        Ok(Self {
+            writer: Mutex::new(Some(FileWriter {
+                path: path.clone(),
+                max_size: None,
+                rotation_interval: None,
+                max_files: 5,
+                file,
+            })),
+            path,
+            sender: OnceLock::new(),
+            thread: Mutex::new(None),
+        })
+    }
+
+    /// Sets the size in bytes after which the file is rotated.
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        self.configure(|writer| writer.max_size = Some(max_size))
+    }
+
+    /// Sets the time after which the file is rotated.
+    pub fn with_rotation_interval(self, interval: Duration) -> Self {
+        self.configure(|writer| writer.rotation_interval = Some(interval))
+    }
+
+    /// Sets the number of rotated files to keep. Defaults to 5.
+    pub fn with_max_files(self, max_files: usize) -> Self {
+        self.configure(|writer| writer.max_files = max_files)
+    }
+
+    /// Updates the writer's settings before the writer thread has started.
+    fn configure(mut self, update: impl FnOnce(&mut FileWriter)) -> Self {
+        let writer = self.writer.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(writer) = writer.as_mut() {
+            update(writer);
+        }
+        self
+    }
+
+    /// Returns the sender to the writer thread, starting the thread on first use.
+    fn sender(&self) -> &Sender<String> {
+        self.sender.get_or_init(|| {
+            let (sender, receiver) = channel::<String>();
+            let writer = self
+                .writer
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            if let Some(mut writer) = writer {
+                let spawned = thread::Builder::new()
+                    .name("suika-access-log".to_string())
+                    .spawn(move || {
+                        for line in receiver {
+                            writer.write(&line);
+                        }
+                    });
+                match spawned {
+                    Ok(handle) => {
+                        *self.thread.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle)
+                    }
+                    Err(e) => {
+                        log_error!(path = %self.path.display(), error = %e, "Failed to start access log writer")
+                    }
+                }
+            }
+            sender
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, line: &str) {
+        let _ = self.sender().send(line.to_string());
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer thread finish the queued lines and exit.
+        drop(self.sender.take());
+        let thread = self.thread.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(handle) = thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Opens a log file for appending.
+fn open_log_file(path: &Path) -> IoResult<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile {
+        file,
+        size,
+        opened_at: Instant::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            timestamp: UNIX_EPOCH + Duration::from_millis(971_186_136_250),
+            client_ip: Some("192.0.2.1".parse().unwrap()),
+            user: Some("alice".to_string()),
+            method: "GET".to_string(),
+            target: "/search?q=\"x\"".to_string(),
+            version: "HTTP/1.1".to_string(),
+            status: 200,
+            bytes: Some(2326),
+            latency: Duration::from_micros(1500),
+            user_agent: Some("curl/8.0".to_string()),
+            referer: None,
+            request_id: Some("abc".to_string()),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_common_and_combined_formats() {
+        assert_eq!(
+            LogFormat::Common.format(&entry()),
+            r#"192.0.2.1 - alice [10/Oct/2000:13:55:36 +0000] "GET /search?q=\"x\" HTTP/1.1" 200 2326"#
+        );
+
+        let mut entry = entry();
+        entry.bytes = None;
+        entry.user_agent = Some("evil\n127.0.0.1 - - [forged]".to_string());
+        assert_eq!(
+            LogFormat::Combined.format(&entry),
+            r#"192.0.2.1 - alice [10/Oct/2000:13:55:36 +0000] "GET /search?q=\"x\" HTTP/1.1" 200 - "-" "evil\x0a127.0.0.1 - - [forged]""#
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let line = LogFormat::Json.format(&entry());
+        let json = suika_json::parse_json(&line).unwrap();
+        assert_eq!(
+            json.get("time").and_then(|v| v.as_str()),
+            Some("2000-10-10T13:55:36.250Z")
+        );
+        assert_eq!(
+            json.get("client_ip").and_then(|v| v.as_str()),
+            Some("192.0.2.1")
+        );
+        assert_eq!(
+            json.get("target").and_then(|v| v.as_str()),
+            Some("/search?q=\"x\"")
+        );
+        assert_eq!(json.get("status").and_then(|v| v.as_f64()), Some(200.0));
+        assert_eq!(json.get("bytes").and_then(|v| v.as_f64()), Some(2326.0));
+        assert_eq!(json.get("latency_ms").and_then(|v| v.as_f64()), Some(1.5));
+        assert_eq!(json.get("referer"), Some(&JsonValue::Null));
+        assert_eq!(json.get("request_id").and_then(|v| v.as_str()), Some("abc"));
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let format = LogFormat::Custom(
+            "{request_id} {method} {target} {status} {latency_us}us {referer} {unknown} {"
+                .to_string(),
+        );
+        assert_eq!(
+            format.format(&entry()),
+            r#"abc GET /search?q=\"x\" 200 1500us - {unknown} {"#
+        );
+    }
+
+    #[test]
+    fn test_channel_sink() {
+        let (sink, mut receiver) = ChannelSink::unbounded();
+        sink.write("first");
+        sink.write("second");
+        assert_eq!(receiver.try_recv().unwrap(), "first");
+        assert_eq!(receiver.try_recv().unwrap(), "second");
+    }
+
+    #[test]
+    fn test_file_sink_rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let sink = FileSink::new(&path)
+            .unwrap()
+            .with_max_size(10)
+            .with_max_files(2);
+
+        for line in ["one", "two", "three", "four", "five"] {
+            sink.write(line);
+        }
+        drop(sink);
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("access.log"), "four\nfive\n");
+        assert_eq!(read("access.log.1"), "three\n");
+        assert_eq!(read("access.log.2"), "one\ntwo\n");
+        assert!(!dir.path().join("access.log.3").exists());
+    }
+
+    #[test]
+    fn test_file_sink_rotates_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        fs::write(&path, "existing\n").unwrap();
+
+        let sink = FileSink::new(&path)
+            .unwrap()
+            .with_rotation_interval(Duration::ZERO);
+        sink.write("new");
+        drop(sink);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("access.log.1")).unwrap(),
+            "existing\n"
+        );
+    }
+}
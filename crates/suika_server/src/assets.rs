@@ -0,0 +1,310 @@
+use crate::conditional::ETag;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use suika_json::JsonValue;
+use suika_templates::TemplateEngine;
+
+/// The number of hex digits of the content hash used in fingerprinted file names.
+const FINGERPRINT_LENGTH: usize = 8;
+
+/// A fingerprinted file, with the size and modification time it was hashed at.
+#[derive(Debug, Clone)]
+struct Asset {
+    path: PathBuf,
+    etag: ETag,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// A manifest mapping asset paths to fingerprinted paths that contain a hash of the
+/// file content, such as `css/style.css` to `css/style.3f9a1c2b.css`.
+///
+/// Since a fingerprinted URL changes whenever the content does, responses for it can
+/// be cached forever. Serve them with an `AssetMiddleware` and refer to assets from
+/// templates through the `asset` helper registered by `register_template_helpers`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use suika_server::assets::AssetManifest;
+/// use suika_server::middleware::AssetMiddleware;
+/// use suika_server::server::Server;
+/// use suika_templates::TemplateEngine;
+/// use std::sync::Arc;
+///
+/// let manifest = Arc::new(AssetManifest::build("/assets", "public").unwrap());
+///
+/// let mut engine = TemplateEngine::new();
+/// manifest.register_template_helpers(&mut engine);
+/// engine.add_template("layout.html", r#"<link rel="stylesheet" href="<%= asset("style.css") %>">"#);
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_templates(engine);
+/// server.use_middleware(Arc::new(AssetMiddleware::new(Arc::clone(&manifest))));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AssetManifest {
+    url_prefix: String,
+    fingerprints: BTreeMap<String, String>,
+    assets: HashMap<String, Asset>,
+}
+
+impl AssetManifest {
+    /// Builds a manifest by hashing every file below `directory`. Dotfiles are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `url_prefix` - The URL prefix fingerprinted assets are served under.
+    /// * `directory` - The directory containing the assets.
+    pub fn build<P: AsRef<Path>>(url_prefix: &str, directory: P) -> IoResult<Self> {
+        let root = fs::canonicalize(directory)?;
+        let mut files = Vec::new();
+        collect_files(&root, &mut files)?;
+
+        let mut manifest = Self {
+            url_prefix: url_prefix.trim_end_matches('/').to_string(),
+            fingerprints: BTreeMap::new(),
+            assets: HashMap::new(),
+        };
+        for file in files {
+            let logical = relative_path(&root, &file)?;
+            // Taken before reading, so a concurrent edit makes the entry look stale.
+            let metadata = fs::metadata(&file)?;
+            let content = fs::read(&file)?;
+            let hash = Sha256::digest(&content);
+            let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let fingerprinted = fingerprint_path(&logical, &hex[..FINGERPRINT_LENGTH]);
+
+            manifest.assets.insert(
+                fingerprinted.clone(),
+                Asset {
+                    path: file,
+                    etag: ETag::from_content(&content),
+                    len: metadata.len(),
+                    modified: metadata.modified().ok(),
+                },
+            );
+            manifest.fingerprints.insert(logical, fingerprinted);
+        }
+        Ok(manifest)
+    }
+
+    /// Returns the fingerprinted path of an asset, such as `style.3f9a1c2b.css` for
+    /// `style.css`.
+    pub fn fingerprint(&self, path: &str) -> Option<&str> {
+        self.fingerprints
+            .get(path.trim_start_matches('/'))
+            .map(String::as_str)
+    }
+
+    /// Returns the URL of an asset's fingerprinted path under the URL prefix.
+    ///
+    /// Assets missing from the manifest, such as files added after it was built, are
+    /// given their plain URL so they can still be served by a `StaticFileMiddleware`.
+    pub fn asset_url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        format!(
+            "{}/{}",
+            self.url_prefix,
+            self.fingerprint(path).unwrap_or(path)
+        )
+    }
+
+    /// Returns the URL prefix fingerprinted assets are served under.
+    pub fn url_prefix(&self) -> &str {
+        &self.url_prefix
+    }
+
+    /// Returns the file and entity tag for a fingerprinted path.
+    pub(crate) fn resolve(&self, fingerprinted: &str) -> Option<(&Path, &ETag)> {
+        self.assets
+            .get(fingerprinted)
+            .map(|asset| (asset.path.as_path(), &asset.etag))
+    }
+
+    /// Returns `true` if the file behind a fingerprinted path still has the size and
+    /// modification time it had when the manifest was built, so its content still
+    /// matches the fingerprint.
+    pub(crate) fn is_current(&self, fingerprinted: &str, metadata: &fs::Metadata) -> bool {
+        self.assets.get(fingerprinted).is_some_and(|asset| {
+            asset.len == metadata.len() && asset.modified == metadata.modified().ok()
+        })
+    }
+
+    /// Returns the manifest as a JSON object mapping asset paths to fingerprinted paths.
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(
+            self.fingerprints
+                .iter()
+                .map(|(path, fingerprinted)| (path.clone(), fingerprinted.clone().into()))
+                .collect(),
+        )
+    }
+
+    /// Writes the manifest as JSON to a file, for use by other tools.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> IoResult<()> {
+        fs::write(path, self.to_json().to_string())
+    }
+
+    /// Registers an `asset` function and filter with a template engine, resolving
+    /// asset paths to fingerprinted URLs: `<%= asset("style.css") %>` or
+    /// `<%= stylesheet|asset %>`.
+    pub fn register_template_helpers(self: &Arc<Self>, engine: &mut TemplateEngine) {
+        let manifest = Arc::clone(self);
+        engine.register_function("asset", move |args| match args.first() {
+            Some(JsonValue::String(path)) => Ok(JsonValue::String(manifest.asset_url(path))),
+            _ => Err("asset() expects a path".to_string()),
+        });
+        let manifest = Arc::clone(self);
+        engine.register_filter("asset", move |path: String| Ok(manifest.asset_url(&path)));
+    }
+}
+
+/// Inserts a fingerprint before the extension of the file name in `path`.
+fn fingerprint_path(path: &str, fingerprint: &str) -> String {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{}.{}{}", &path[..dot], fingerprint, &path[dot..])
+        }
+        _ => format!("{}.{}", path, fingerprint),
+    }
+}
+
+/// Collects the files below `dir`, skipping dotfiles and following symbolic links.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> IoResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let metadata = fs::metadata(&path)?;
+        if metadata.is_dir() {
+            collect_files(&path, files)?;
+        } else if metadata.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path of `file` relative to `root` with `/` separators.
+fn relative_path(root: &Path, file: &Path) -> IoResult<String> {
+    let relative = file
+        .strip_prefix(root)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let parts = relative
+        .components()
+        .map(|component| {
+            component.as_os_str().to_str().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("File name is not valid UTF-8: {}", file.display()),
+                )
+            })
+        })
+        .collect::<IoResult<Vec<_>>>()?;
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use suika_templates::context::Context;
+
+    fn site() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("js")).unwrap();
+        fs::write(dir.path().join("style.css"), "body { color: red }").unwrap();
+        fs::write(dir.path().join("js").join("app.min.js"), "run()").unwrap();
+        fs::write(dir.path().join("LICENSE"), "MIT").unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_fingerprint_path() {
+        assert_eq!(fingerprint_path("style.css", "abc"), "style.abc.css");
+        assert_eq!(
+            fingerprint_path("js/app.min.js", "abc"),
+            "js/app.min.abc.js"
+        );
+        assert_eq!(fingerprint_path("v1.0/LICENSE", "abc"), "v1.0/LICENSE.abc");
+        assert_eq!(fingerprint_path(".htaccess", "abc"), ".htaccess.abc");
+    }
+
+    #[test]
+    fn test_build_manifest() {
+        let dir = site();
+        let manifest = AssetManifest::build("/assets/", dir.path()).unwrap();
+
+        let style = manifest.fingerprint("style.css").unwrap();
+        assert!(style.starts_with("style.") && style.ends_with(".css"));
+        assert_eq!(style.len(), "style..css".len() + FINGERPRINT_LENGTH);
+        assert_eq!(
+            manifest.asset_url("/style.css"),
+            format!("/assets/{}", style)
+        );
+        assert!(manifest
+            .fingerprint("js/app.min.js")
+            .unwrap()
+            .starts_with("js/app.min."));
+        assert!(manifest.fingerprint(".env").is_none());
+        assert_eq!(manifest.asset_url("missing.png"), "/assets/missing.png");
+
+        let (path, etag) = manifest.resolve(style).unwrap();
+        assert_eq!(
+            path,
+            fs::canonicalize(dir.path().join("style.css")).unwrap()
+        );
+        assert_eq!(etag, &ETag::from_content(b"body { color: red }"));
+        assert!(manifest.resolve("style.css").is_none());
+
+        let json = manifest.to_json();
+        assert_eq!(json.get("style.css").and_then(|v| v.as_str()), Some(style));
+        assert!(matches!(json, JsonValue::Object(ref entries) if entries.len() == 3));
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_content() {
+        let dir = site();
+        let before = AssetManifest::build("/assets", dir.path()).unwrap();
+        fs::write(dir.path().join("style.css"), "body { color: blue }").unwrap();
+        let after = AssetManifest::build("/assets", dir.path()).unwrap();
+        assert_ne!(
+            before.fingerprint("style.css"),
+            after.fingerprint("style.css")
+        );
+        assert_eq!(before.fingerprint("LICENSE"), after.fingerprint("LICENSE"));
+    }
+
+    #[test]
+    fn test_template_helpers() {
+        let dir = site();
+        let manifest = Arc::new(AssetManifest::build("/assets", dir.path()).unwrap());
+        let mut engine = TemplateEngine::new();
+        manifest.register_template_helpers(&mut engine);
+        engine.add_template("function", r#"<%= asset("style.css") %>"#);
+        engine.add_template("filter", "<%= script|asset %>");
+
+        let expected = manifest.asset_url("style.css");
+        assert_eq!(
+            engine.render("function", &Context::new()).unwrap(),
+            expected
+        );
+
+        let mut context = Context::new();
+        context.insert("script", "js/app.min.js");
+        assert_eq!(
+            engine.render("filter", &context).unwrap(),
+            manifest.asset_url("js/app.min.js")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+/// An authenticated identity attached to a request.
+///
+/// A `Principal` is produced by an authentication verifier and carries the roles and
+/// permissions later used for authorization decisions.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::auth::Principal;
+///
+/// let principal = Principal::new("alice")
+///     .with_roles(vec!["admin"])
+///     .with_permissions(vec!["todos:write"]);
+///
+/// assert_eq!(principal.id(), "alice");
+/// assert!(principal.has_role("admin"));
+/// assert!(principal.has_permission("todos:write"));
+/// assert!(!principal.has_permission("todos:delete"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    id: String,
+    roles: Vec<String>,
+    permissions: Vec<String>,
+}
+
+impl Principal {
+    /// Creates a new `Principal` with the given identifier and no roles or permissions.
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        }
+    }
+
+    /// Sets the roles granted to the principal.
+    pub fn with_roles<T: Into<String>>(mut self, roles: Vec<T>) -> Self {
+        self.roles = roles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the permissions granted to the principal.
+    pub fn with_permissions<T: Into<String>>(mut self, permissions: Vec<T>) -> Self {
+        self.permissions = permissions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns the identifier of the principal, such as a username or subject.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the roles granted to the principal.
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+
+    /// Returns the permissions granted to the principal.
+    pub fn permissions(&self) -> &[String] {
+        &self.permissions
+    }
+
+    /// Returns `true` if the principal has the given role.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// Returns `true` if the principal has the given permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+/// Verifies credentials supplied with the HTTP Basic authentication scheme.
+///
+/// Closures taking a username and password are verifiers too.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::auth::{BasicVerifier, Principal};
+///
+/// let verifier = |username: &str, password: &str| {
+///     (username == "alice" && password == "secret").then(|| Principal::new(username))
+/// };
+///
+/// assert!(verifier.verify("alice", "secret").is_some());
+/// assert!(verifier.verify("alice", "wrong").is_none());
+/// ```
+pub trait BasicVerifier: Send + Sync {
+    /// Returns the authenticated principal, or `None` if the credentials are invalid.
+    fn verify(&self, username: &str, password: &str) -> Option<Principal>;
+}
+
+impl<F> BasicVerifier for F
+where
+    F: Fn(&str, &str) -> Option<Principal> + Send + Sync,
+{
+    fn verify(&self, username: &str, password: &str) -> Option<Principal> {
+        self(username, password)
+    }
+}
+
+/// Verifies tokens supplied with the HTTP Bearer authentication scheme.
+///
+/// Closures taking a token are verifiers too.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::auth::{BearerVerifier, Principal};
+///
+/// let verifier = |token: &str| (token == "let-me-in").then(|| Principal::new("service"));
+///
+/// assert!(verifier.verify("let-me-in").is_some());
+/// assert!(verifier.verify("nope").is_none());
+/// ```
+pub trait BearerVerifier: Send + Sync {
+    /// Returns the authenticated principal, or `None` if the token is invalid.
+    fn verify(&self, token: &str) -> Option<Principal>;
+}
+
+impl<F> BearerVerifier for F
+where
+    F: Fn(&str) -> Option<Principal> + Send + Sync,
+{
+    fn verify(&self, token: &str) -> Option<Principal> {
+        self(token)
+    }
+}
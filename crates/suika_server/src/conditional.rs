@@ -0,0 +1,557 @@
+use crate::error::HttpError;
+use crate::http_date::parse_http_date;
+use crate::request::Request;
+use crate::response::Response;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Result as IoResult;
+use std::time::{SystemTime, UNIX_EPOCH};
+use suika_utils::base64url_encode;
+use tokio::io::AsyncReadExt;
+
+/// An entity tag identifying a particular representation of a resource.
+///
+/// Strong tags promise byte-for-byte identical content; weak tags (`W/"..."`) only
+/// promise semantically equivalent content.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::conditional::ETag;
+///
+/// let etag = ETag::from_content(b"hello");
+/// assert!(!etag.is_weak());
+/// assert_eq!(ETag::parse(&etag.to_string()), Some(etag));
+///
+/// let weak = ETag::parse("W/\"v1\"").unwrap();
+/// assert_eq!(weak, ETag::weak("v1"));
+/// assert!(weak.weak_eq(&ETag::strong("v1")));
+/// assert!(!weak.strong_eq(&ETag::strong("v1")));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ETag {
+    tag: String,
+    weak: bool,
+}
+
+impl ETag {
+    /// Creates a strong entity tag from an opaque tag without quotes.
+    pub fn strong(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            weak: false,
+        }
+    }
+
+    /// Creates a weak entity tag from an opaque tag without quotes.
+    pub fn weak(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            weak: true,
+        }
+    }
+
+    /// Creates a strong entity tag from a SHA-256 hash of the content.
+    pub fn from_content(data: &[u8]) -> Self {
+        let digest = Sha256::digest(data);
+        Self::strong(&base64url_encode(&digest[..16]))
+    }
+
+    /// Creates a strong entity tag from a SHA-256 hash of a file's content, reading
+    /// the file in chunks.
+    pub async fn from_file(path: &str) -> IoResult<Self> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let size = file.read(&mut buffer).await?;
+            if size == 0 {
+                break;
+            }
+            hasher.update(&buffer[..size]);
+        }
+        Ok(Self::strong(&base64url_encode(&hasher.finalize()[..16])))
+    }
+
+    /// Creates a weak entity tag from a file's size and modification time.
+    pub fn from_metadata(len: u64, modified: SystemTime) -> Self {
+        let modified = modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self::weak(&format!("{:x}-{:x}", len, modified))
+    }
+
+    /// Parses an entity tag header value such as `"abc"` or `W/"abc"`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (weak, quoted) = match value.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        if tag.contains('"') {
+            return None;
+        }
+        Some(Self {
+            tag: tag.to_string(),
+            weak,
+        })
+    }
+
+    /// Returns the opaque tag without quotes.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns `true` if this is a weak entity tag.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Strong comparison: both tags are strong and identical.
+    pub fn strong_eq(&self, other: &ETag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: the opaque tags are identical, ignoring weakness.
+    pub fn weak_eq(&self, other: &ETag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/\"{}\"", self.tag)
+        } else {
+            write!(f, "\"{}\"", self.tag)
+        }
+    }
+}
+
+/// The outcome of evaluating a request's conditional headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// The request should be processed normally.
+    Proceed,
+    /// The client's cached representation is current (`304 Not Modified`).
+    NotModified,
+    /// A precondition failed (`412 Precondition Failed`).
+    Failed,
+}
+
+/// Evaluates `If-Match`, `If-Unmodified-Since`, `If-None-Match` and
+/// `If-Modified-Since` against the current representation, in the order given by
+/// RFC 9110.
+///
+/// Handlers of state-changing requests such as `PUT` should call this before applying
+/// changes, so that a stale `If-Match` is rejected with `412`.
+///
+/// The representation is assumed to exist, so `*` matches it even when it has no
+/// entity tag. Use `evaluate_missing` for resources that do not exist yet.
+///
+/// # Arguments
+///
+/// * `req` - The request carrying the conditional headers.
+/// * `etag` - The entity tag of the current representation, if any.
+/// * `last_modified` - The modification time of the current representation, if any.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::conditional::{evaluate, ETag, Precondition};
+/// use suika_server::request::Request;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let req = Request::new(
+///     "GET / HTTP/1.1\r\nIf-None-Match: \"v1\", \"v2\"\r\n\r\n",
+///     Arc::new(Mutex::new(HashMap::new())),
+/// ).unwrap();
+///
+/// assert_eq!(evaluate(&req, Some(&ETag::strong("v2")), None), Precondition::NotModified);
+/// assert_eq!(evaluate(&req, Some(&ETag::strong("v3")), None), Precondition::Proceed);
+/// ```
+pub fn evaluate(
+    req: &Request,
+    etag: Option<&ETag>,
+    last_modified: Option<SystemTime>,
+) -> Precondition {
+    let last_modified = last_modified.map(truncate_to_seconds);
+    let is_get_or_head = matches!(req.method(), "GET" | "HEAD");
+
+    if let Some(if_match) = req.header("If-Match") {
+        if !matches_any(if_match, etag, true) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = req.header("If-Unmodified-Since").and_then(parse_http_date) {
+        if last_modified.is_some_and(|modified| modified > since) {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = req.header("If-None-Match") {
+        if matches_any(if_none_match, etag, false) {
+            return if is_get_or_head {
+                Precondition::NotModified
+            } else {
+                Precondition::Failed
+            };
+        }
+    } else if is_get_or_head {
+        if let Some(since) = req.header("If-Modified-Since").and_then(parse_http_date) {
+            if last_modified.is_some_and(|modified| modified <= since) {
+                return Precondition::NotModified;
+            }
+        }
+    }
+
+    Precondition::Proceed
+}
+
+/// Evaluates the conditional headers of a request for a resource that does not exist.
+///
+/// Any `If-Match` fails, as there is no representation to match, while
+/// `If-None-Match`, including `*`, and the date conditions pass. This lets a `PUT`
+/// with `If-None-Match: *` create a resource only if it is not there yet.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::conditional::{evaluate_missing, Precondition};
+/// use suika_server::request::Request;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let req = Request::new(
+///     "PUT /items/1 HTTP/1.1\r\nIf-None-Match: *\r\n\r\n",
+///     Arc::new(Mutex::new(HashMap::new())),
+/// ).unwrap();
+///
+/// assert_eq!(evaluate_missing(&req), Precondition::Proceed);
+/// ```
+pub fn evaluate_missing(req: &Request) -> Precondition {
+    if req.header("If-Match").is_some() {
+        Precondition::Failed
+    } else {
+        Precondition::Proceed
+    }
+}
+
+/// Returns `true` if a `Range` request should be honoured according to `If-Range`.
+///
+/// Without `If-Range` ranges are always honoured. An entity tag must match the current
+/// one using strong comparison, and a date must equal the current modification time.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::conditional::{if_range_matches, ETag};
+/// use suika_server::request::Request;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let req = Request::new(
+///     "GET / HTTP/1.1\r\nRange: bytes=0-9\r\nIf-Range: \"v1\"\r\n\r\n",
+///     Arc::new(Mutex::new(HashMap::new())),
+/// ).unwrap();
+///
+/// assert!(if_range_matches(&req, Some(&ETag::strong("v1")), None));
+/// assert!(!if_range_matches(&req, Some(&ETag::weak("v1")), None));
+/// ```
+pub fn if_range_matches(
+    req: &Request,
+    etag: Option<&ETag>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let value = match req.header("If-Range") {
+        Some(value) => value.trim(),
+        None => return true,
+    };
+    if value.starts_with('"') || value.starts_with("W/") {
+        return match (ETag::parse(value), etag) {
+            (Some(candidate), Some(etag)) => candidate.strong_eq(etag),
+            _ => false,
+        };
+    }
+    match (parse_http_date(value), last_modified) {
+        (Some(date), Some(modified)) => truncate_to_seconds(modified) == date,
+        _ => false,
+    }
+}
+
+/// Applies the request's conditional headers to a successful response.
+///
+/// The response's `ETag` and `Last-Modified` headers describe the current
+/// representation. If the client's copy is current the response becomes a bodiless
+/// `304 Not Modified`; if a precondition fails, the body and the headers describing it
+/// are removed and `HttpError::PreconditionFailed` is returned. Responses with a
+/// non-2xx status are left untouched.
+pub async fn respond(req: &Request, res: &Response) -> Result<(), HttpError> {
+    if res
+        .status()
+        .await
+        .is_some_and(|status| !(200..300).contains(&status))
+    {
+        return Ok(());
+    }
+
+    let etag = res.get_header("ETag").await.and_then(|v| ETag::parse(&v));
+    let last_modified = res
+        .get_header("Last-Modified")
+        .await
+        .and_then(|v| parse_http_date(&v));
+
+    match evaluate(req, etag.as_ref(), last_modified) {
+        Precondition::Proceed => Ok(()),
+        Precondition::NotModified => {
+            res.set_status(304).await;
+            res.set_body(None).await;
+            res.remove_header("Content-Length").await;
+            Ok(())
+        }
+        Precondition::Failed => {
+            res.set_body(None).await;
+            for header in [
+                "Content-Length",
+                "Content-Range",
+                "Content-Encoding",
+                "Content-Type",
+            ] {
+                res.remove_header(header).await;
+            }
+            Err(HttpError::PreconditionFailed(
+                "Precondition failed".to_string(),
+            ))
+        }
+    }
+}
+
+/// Returns `true` if an `If-Match` or `If-None-Match` list matches the entity tag.
+///
+/// `*` matches any existing representation, with or without an entity tag.
+fn matches_any(header: &str, etag: Option<&ETag>, strong: bool) -> bool {
+    if header.trim() == "*" {
+        return true;
+    }
+    let etag = match etag {
+        Some(etag) => etag,
+        None => return false,
+    };
+    split_etags(header)
+        .filter_map(ETag::parse)
+        .any(|candidate| {
+            if strong {
+                candidate.strong_eq(etag)
+            } else {
+                candidate.weak_eq(etag)
+            }
+        })
+}
+
+/// Splits a comma-separated list of entity tags, respecting quotes.
+fn split_etags(header: &str) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    header
+        .split(move |c| {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            }
+            c == ',' && !in_quotes
+        })
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// Truncates a time to whole seconds, the precision of HTTP dates.
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => UNIX_EPOCH + std::time::Duration::from_secs(duration.as_secs()),
+        Err(_) => time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_date::format_http_date;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn request(method: &str, headers: &[(&str, &str)]) -> Request {
+        let mut raw = format!("{} / HTTP/1.1\r\n", method);
+        for (name, value) in headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str("\r\n");
+        Request::new(&raw, Arc::new(Mutex::new(HashMap::new()))).unwrap()
+    }
+
+    #[test]
+    fn test_etag_parse_and_display() {
+        assert_eq!(ETag::parse("\"abc\""), Some(ETag::strong("abc")));
+        assert_eq!(ETag::parse(" W/\"abc\" "), Some(ETag::weak("abc")));
+        assert_eq!(ETag::parse("abc"), None);
+        assert_eq!(ETag::parse("\"a\"b\""), None);
+        assert_eq!(ETag::weak("abc").to_string(), "W/\"abc\"");
+        assert_eq!(
+            ETag::from_metadata(255, UNIX_EPOCH + Duration::from_secs(16)),
+            ETag::weak("ff-10")
+        );
+        assert_eq!(ETag::from_content(b"a"), ETag::from_content(b"a"));
+        assert_ne!(ETag::from_content(b"a"), ETag::from_content(b"b"));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = ETag::strong("v1");
+        let req = request("GET", &[("If-None-Match", "W/\"v1\"")]);
+        assert_eq!(evaluate(&req, Some(&etag), None), Precondition::NotModified);
+
+        let req = request("GET", &[("If-None-Match", "*")]);
+        assert_eq!(evaluate(&req, Some(&etag), None), Precondition::NotModified);
+        assert_eq!(evaluate(&req, None, None), Precondition::NotModified);
+        assert_eq!(evaluate_missing(&req), Precondition::Proceed);
+
+        let req = request("PUT", &[("If-None-Match", "*")]);
+        assert_eq!(evaluate(&req, Some(&etag), None), Precondition::Failed);
+        assert_eq!(evaluate_missing(&req), Precondition::Proceed);
+    }
+
+    #[test]
+    fn test_if_match() {
+        let req = request("PUT", &[("If-Match", "\"v1\", \"v2\"")]);
+        assert_eq!(
+            evaluate(&req, Some(&ETag::strong("v2")), None),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            evaluate(&req, Some(&ETag::strong("v3")), None),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(&req, Some(&ETag::weak("v2")), None),
+            Precondition::Failed
+        );
+        assert_eq!(evaluate(&req, None, None), Precondition::Failed);
+        assert_eq!(evaluate_missing(&req), Precondition::Failed);
+
+        let req = request("PUT", &[("If-Match", "*")]);
+        assert_eq!(evaluate(&req, None, None), Precondition::Proceed);
+        assert_eq!(evaluate_missing(&req), Precondition::Failed);
+    }
+
+    #[test]
+    fn test_dates() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_000_000_500);
+        let at = format_http_date(modified);
+        let before = format_http_date(modified - Duration::from_secs(60));
+
+        let req = request("GET", &[("If-Modified-Since", &at)]);
+        assert_eq!(
+            evaluate(&req, None, Some(modified)),
+            Precondition::NotModified
+        );
+        let req = request("GET", &[("If-Modified-Since", &before)]);
+        assert_eq!(evaluate(&req, None, Some(modified)), Precondition::Proceed);
+        let req = request("POST", &[("If-Modified-Since", &at)]);
+        assert_eq!(evaluate(&req, None, Some(modified)), Precondition::Proceed);
+
+        let req = request("PUT", &[("If-Unmodified-Since", &before)]);
+        assert_eq!(evaluate(&req, None, Some(modified)), Precondition::Failed);
+        let req = request("PUT", &[("If-Unmodified-Since", &at)]);
+        assert_eq!(evaluate(&req, None, Some(modified)), Precondition::Proceed);
+    }
+
+    #[test]
+    fn test_if_range() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let etag = ETag::strong("v1");
+
+        let req = request("GET", &[]);
+        assert!(if_range_matches(&req, None, None));
+
+        let req = request("GET", &[("If-Range", "\"v1\"")]);
+        assert!(if_range_matches(&req, Some(&etag), None));
+        assert!(!if_range_matches(&req, Some(&ETag::strong("v2")), None));
+        assert!(!if_range_matches(&req, None, Some(modified)));
+
+        let req = request("GET", &[("If-Range", &format_http_date(modified))]);
+        assert!(if_range_matches(&req, Some(&etag), Some(modified)));
+        assert!(!if_range_matches(
+            &req,
+            Some(&etag),
+            Some(modified + Duration::from_secs(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_etag_from_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"file content").unwrap();
+        assert_eq!(
+            ETag::from_file(file.path().to_str().unwrap())
+                .await
+                .unwrap(),
+            ETag::from_content(b"file content")
+        );
+    }
+
+    #[test]
+    fn test_etag_takes_precedence_over_dates() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let req = request(
+            "GET",
+            &[
+                ("If-None-Match", "\"old\""),
+                ("If-Modified-Since", &format_http_date(modified)),
+            ],
+        );
+        assert_eq!(
+            evaluate(&req, Some(&ETag::strong("new")), Some(modified)),
+            Precondition::Proceed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let req = request("GET", &[("If-None-Match", "\"v1\"")]);
+        let res = Response::new(None);
+        res.set_status(200).await;
+        res.etag(&ETag::strong("v1")).await;
+        res.header("Content-Length", "5").await;
+        res.body("hello".to_string()).await;
+
+        respond(&req, &res).await.unwrap();
+        let inner = res.get_inner().await;
+        assert_eq!(inner.status_code(), Some(304));
+        assert_eq!(inner.body(), &None);
+        assert_eq!(inner.headers().get("Content-Length"), None);
+        assert_eq!(inner.headers().get("ETag"), Some(&"\"v1\"".to_string()));
+
+        let req = request("GET", &[("If-Match", "\"v2\"")]);
+        let res = Response::new(None);
+        res.etag(&ETag::strong("v1")).await;
+        res.header("Content-Type", "text/plain").await;
+        res.header("Content-Length", "5").await;
+        res.header("Content-Range", "bytes 0-4/10").await;
+        res.header("Content-Encoding", "br").await;
+        res.body("hello".to_string()).await;
+        assert!(matches!(
+            respond(&req, &res).await,
+            Err(HttpError::PreconditionFailed(_))
+        ));
+        let inner = res.get_inner().await;
+        assert_eq!(inner.body(), &None);
+        for header in [
+            "Content-Type",
+            "Content-Length",
+            "Content-Range",
+            "Content-Encoding",
+        ] {
+            assert_eq!(inner.headers().get(header), None);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Internal diagnostics.
+//!
+//! With the `tracing` feature, diagnostics are emitted as `tracing` events with
+//! structured fields, and each request is handled inside a span carrying its method,
+//! path and request ID. The `log` feature additionally forwards them to the `log`
+//! crate when no `tracing` subscriber is installed. Without either feature, errors and
+//! warnings are written to standard error, informational messages to standard output,
+//! and debug messages are discarded.
+//!
+//! The macros take optional `name = %value` fields followed by a format string:
+//!
+//! ```ignore
+//! log_warn!(peer = %peer_addr, error = %e, "Rejected connection");
+//! ```
+
+/// Emits a diagnostic event at the given level.
+macro_rules! event {
+    ($level:ident, $($key:ident = %$value:expr,)* $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        {
+            tracing::$level!($($key = %$value,)* $fmt $(, $arg)*);
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            $crate::diagnostics::fallback!($level, $($key = $value,)* $fmt $(, $arg)*);
+        }
+    }};
+}
+
+/// Writes a diagnostic event without a `tracing` subscriber.
+#[cfg(not(feature = "tracing"))]
+macro_rules! fallback {
+    (error, $($rest:tt)+) => {
+        eprintln!("{}", $crate::diagnostics::message!($($rest)+))
+    };
+    (warn, $($rest:tt)+) => {
+        eprintln!("{}", $crate::diagnostics::message!($($rest)+))
+    };
+    (info, $($rest:tt)+) => {
+        println!("{}", $crate::diagnostics::message!($($rest)+))
+    };
+    (debug, $($key:ident = $value:expr,)* $fmt:literal $(, $arg:expr)*) => {{
+        $(let _ = &$value;)*
+        $(let _ = &$arg;)*
+    }};
+}
+
+/// Formats a message followed by its fields as `key=value` pairs.
+#[cfg(not(feature = "tracing"))]
+macro_rules! message {
+    ($($key:ident = $value:expr,)* $fmt:literal $(, $arg:expr)*) => {{
+        #[allow(unused_mut)]
+        let mut message = format!($fmt $(, $arg)*);
+        $(message.push_str(&format!(" {}={}", stringify!($key), $value));)*
+        message
+    }};
+}
+
+/// Emits an error event.
+macro_rules! log_error {
+    ($($arg:tt)+) => { $crate::diagnostics::event!(error, $($arg)+) };
+}
+
+/// Emits a warning event.
+macro_rules! log_warn {
+    ($($arg:tt)+) => { $crate::diagnostics::event!(warn, $($arg)+) };
+}
+
+/// Emits an informational event.
+macro_rules! log_info {
+    ($($arg:tt)+) => { $crate::diagnostics::event!(info, $($arg)+) };
+}
+
+/// Emits a debug event.
+macro_rules! log_debug {
+    ($($arg:tt)+) => { $crate::diagnostics::event!(debug, $($arg)+) };
+}
+
+pub(crate) use {event, log_debug, log_error, log_info, log_warn};
+#[cfg(not(feature = "tracing"))]
+pub(crate) use {fallback, message};
+
+/// Creates the span a request is handled in.
+///
+/// The `request_id` field is left empty: the raw `X-Request-Id` header is client input,
+/// so the field is only recorded by a `RequestIdMiddleware` once it has validated or
+/// generated the ID.
+#[cfg(feature = "tracing")]
+pub(crate) fn request_span(req: &crate::request::Request) -> tracing::Span {
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.path(),
+        request_id = tracing::field::Empty,
+    )
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::request::Request;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // Subscriber recording the fields of spans and events
+    #[derive(Clone, Default)]
+    struct RecordingSubscriber {
+        fields: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Visit for RecordingSubscriber {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut self.clone());
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_request_span_and_events() {
+        let subscriber = RecordingSubscriber::default();
+        let fields = Arc::clone(&subscriber.fields);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let req = Request::new(
+                "GET /items HTTP/1.1\r\nX-Request-Id: a b\"c\r\n\r\n",
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+            .unwrap();
+            let span = request_span(&req);
+            assert!(fields
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|(field, _)| field != "request_id"));
+            span.record("request_id", "abc");
+            log_warn!(peer = %"192.0.2.1:50000", "Rejected connection");
+        });
+
+        let fields = fields.lock().unwrap();
+        let value = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("method"), Some("GET"));
+        assert_eq!(value("path"), Some("/items"));
+        assert_eq!(value("request_id"), Some("abc"));
+        assert_eq!(value("peer"), Some("192.0.2.1:50000"));
+        assert_eq!(value("message"), Some("Rejected connection"));
+    }
+}
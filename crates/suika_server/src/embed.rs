@@ -0,0 +1,413 @@
+use crate::conditional::ETag;
+use crate::encoding::ContentEncoding;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use suika_mime::{get_mime_type_from_path, is_compressible};
+
+/// A precompressed variant as emitted by `Embedder`: encoding, content and entity tag.
+#[doc(hidden)]
+pub type EmbeddedVariantEntry = (&'static str, &'static [u8], &'static str);
+
+/// A file as emitted by `Embedder`: path, content, entity tag, modification time in
+/// seconds since the Unix epoch and precompressed variants.
+#[doc(hidden)]
+pub type EmbeddedEntry = (
+    &'static str,
+    &'static [u8],
+    &'static str,
+    Option<u64>,
+    &'static [EmbeddedVariantEntry],
+);
+
+/// A directory tree compiled into the binary.
+///
+/// Created with the `include_assets!` macro from a file generated by an `Embedder` in
+/// a build script.
+///
+/// # Examples
+///
+/// ```ignore
+/// use suika_server::embed::EmbeddedAssets;
+///
+/// static ASSETS: EmbeddedAssets = suika_server::include_assets!("assets.rs");
+///
+/// let index = ASSETS.get("/index.html").unwrap();
+/// assert_eq!(index.mime_type(), "text/html");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedAssets {
+    source_dir: &'static str,
+    entries: &'static [EmbeddedEntry],
+}
+
+impl EmbeddedAssets {
+    /// Creates the assets from the code generated by `Embedder`. Use `include_assets!`
+    /// instead of calling this directly.
+    #[doc(hidden)]
+    pub const fn from_entries(generated: (&'static str, &'static [EmbeddedEntry])) -> Self {
+        Self {
+            source_dir: generated.0,
+            entries: generated.1,
+        }
+    }
+
+    /// Returns the directory the assets were embedded from.
+    pub fn source_dir(&self) -> &'static str {
+        self.source_dir
+    }
+
+    /// Returns the file at `path`, relative to the embedded directory.
+    pub fn get(&self, path: &str) -> Option<EmbeddedFile> {
+        let path = path.trim_start_matches('/');
+        self.iter().find(|file| &file.path()[1..] == path)
+    }
+
+    /// Returns an iterator over the embedded files, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = EmbeddedFile> {
+        self.entries.iter().map(|entry| EmbeddedFile { entry })
+    }
+
+    /// Returns the number of embedded files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no files are embedded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// A file compiled into the binary.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedFile {
+    entry: &'static EmbeddedEntry,
+}
+
+impl EmbeddedFile {
+    /// Returns the path of the file, such as `/css/site.css`.
+    pub fn path(&self) -> &'static str {
+        self.entry.0
+    }
+
+    /// Returns the content of the file.
+    pub fn contents(&self) -> &'static [u8] {
+        self.entry.1
+    }
+
+    /// Returns the strong entity tag computed from the content at build time.
+    pub fn etag(&self) -> ETag {
+        ETag::parse(self.entry.2).unwrap_or_else(|| ETag::from_content(self.entry.1))
+    }
+
+    /// Returns the modification time of the file at build time.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.entry
+            .3
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Returns the MIME type of the file, derived from its extension.
+    pub fn mime_type(&self) -> String {
+        get_mime_type_from_path(self.entry.0)
+    }
+
+    /// Returns the encodings of the precompressed variants of the file.
+    pub fn encodings(&self) -> Vec<ContentEncoding> {
+        self.entry
+            .4
+            .iter()
+            .filter_map(|variant| ContentEncoding::from_name(variant.0))
+            .collect()
+    }
+
+    /// Returns the content and entity tag of the variant precompressed with `encoding`.
+    pub fn variant(&self, encoding: ContentEncoding) -> Option<(&'static [u8], ETag)> {
+        self.entry
+            .4
+            .iter()
+            .find(|variant| ContentEncoding::from_name(variant.0) == Some(encoding))
+            .map(|variant| {
+                let etag = ETag::parse(variant.2).unwrap_or_else(|| ETag::from_content(variant.1));
+                (variant.1, etag)
+            })
+    }
+}
+
+/// Generates the code embedding a directory tree, for use in build scripts.
+///
+/// The generated file is loaded with the `include_assets!` macro. Content is included
+/// with `include_bytes!`, entity tags are computed from the content, and compressible
+/// files can be precompressed once at build time.
+///
+/// # Examples
+///
+/// In `build.rs`:
+///
+/// ```no_run
+/// use suika_server::embed::Embedder;
+/// use suika_server::encoding::ContentEncoding;
+///
+/// Embedder::new("public")
+///     .with_precompressed(&[ContentEncoding::Brotli, ContentEncoding::Gzip])
+///     .generate("assets.rs")
+///     .expect("Failed to embed assets");
+/// ```
+///
+/// In the application:
+///
+/// ```ignore
+/// use suika_server::embed::EmbeddedAssets;
+/// use suika_server::middleware::EmbeddedFileMiddleware;
+///
+/// static ASSETS: EmbeddedAssets = suika_server::include_assets!("assets.rs");
+///
+/// server.use_middleware(Arc::new(EmbeddedFileMiddleware::new("/static", ASSETS, 3600)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Embedder {
+    dir: PathBuf,
+    precompressed: Vec<ContentEncoding>,
+    hidden_files: bool,
+}
+
+impl Embedder {
+    /// Creates a new `Embedder` for the directory at `dir`, relative to the crate root
+    /// when run from a build script.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            precompressed: Vec::new(),
+            hidden_files: false,
+        }
+    }
+
+    /// Sets the encodings compressible files are precompressed with. Variants that are
+    /// not smaller than the original are left out. Deflate is ignored.
+    pub fn with_precompressed(mut self, encodings: &[ContentEncoding]) -> Self {
+        self.precompressed = encodings
+            .iter()
+            .copied()
+            .filter(|encoding| encoding.extension().is_some())
+            .collect();
+        self
+    }
+
+    /// Sets whether dotfiles and files in dot-directories are embedded.
+    pub fn with_hidden_files(mut self, hidden_files: bool) -> Self {
+        self.hidden_files = hidden_files;
+        self
+    }
+
+    /// Writes the generated code to `file_name` in the build script's `OUT_DIR`, and
+    /// tells Cargo to rerun the build script when the directory changes.
+    pub fn generate(&self, file_name: &str) -> IoResult<()> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                "OUT_DIR is not set; call generate from a build script",
+            )
+        })?;
+        println!("cargo:rerun-if-changed={}", self.dir.display());
+        self.write_to(&Path::new(&out_dir).join(file_name))
+    }
+
+    /// Writes the generated code to `out_file`. Precompressed variants are written to a
+    /// directory next to it.
+    pub fn write_to(&self, out_file: &Path) -> IoResult<()> {
+        let root = fs::canonicalize(&self.dir)?;
+        let mut files = Vec::new();
+        self.collect(&root, &mut files)?;
+        files.sort();
+
+        let mut variants_dir = out_file.as_os_str().to_owned();
+        variants_dir.push(".variants");
+        let variants_dir = PathBuf::from(variants_dir);
+        if !self.precompressed.is_empty() {
+            fs::create_dir_all(&variants_dir)?;
+        }
+
+        let mut code = format!("({:?}, &[\n", root.to_string_lossy());
+        for (index, file) in files.iter().enumerate() {
+            let path = relative_url_path(&root, file)?;
+            let content = fs::read(file)?;
+            let modified = fs::metadata(file)?
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or("None".to_string(), |d| format!("Some({})", d.as_secs()));
+
+            let mut variants = String::new();
+            if is_compressible(&get_mime_type_from_path(&path)) {
+                for encoding in &self.precompressed {
+                    let compressed = encoding.encode(&content)?;
+                    if compressed.len() >= content.len() {
+                        continue;
+                    }
+                    let extension = encoding.extension().unwrap_or_default();
+                    let variant_file = variants_dir.join(format!("{}.{}", index, extension));
+                    fs::write(&variant_file, &compressed)?;
+                    let _ = write!(
+                        variants,
+                        "({:?}, include_bytes!({:?}) as &[u8], {:?}), ",
+                        encoding.name(),
+                        variant_file.to_string_lossy(),
+                        ETag::from_content(&compressed).to_string()
+                    );
+                }
+            }
+
+            let _ = writeln!(
+                code,
+                "    ({:?}, include_bytes!({:?}) as &[u8], {:?}, {}, &[{}]),",
+                path,
+                file.to_string_lossy(),
+                ETag::from_content(&content).to_string(),
+                modified,
+                variants
+            );
+        }
+        code.push_str("])\n");
+
+        fs::write(out_file, code)
+    }
+
+    /// Collects the files below `dir`, following symbolic links.
+    fn collect(&self, dir: &Path, files: &mut Vec<PathBuf>) -> IoResult<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'));
+            if hidden && !self.hidden_files {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            if metadata.is_dir() {
+                self.collect(&path, files)?;
+            } else if metadata.is_file() {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the URL path of `file` relative to `root`, such as `/css/site.css`.
+fn relative_url_path(root: &Path, file: &Path) -> IoResult<String> {
+    let relative = file
+        .strip_prefix(root)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let mut path = String::new();
+    for component in relative.components() {
+        let component = component.as_os_str().to_str().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("File name is not valid UTF-8: {}", file.display()),
+            )
+        })?;
+        path.push('/');
+        path.push_str(component);
+    }
+    Ok(path)
+}
+
+/// Includes assets generated by an `Embedder` in a build script.
+///
+/// The argument is the file name passed to `Embedder::generate`, and the macro
+/// evaluates to an `EmbeddedAssets` that can be stored in a `static`.
+///
+/// # Examples
+///
+/// ```ignore
+/// use suika_server::embed::EmbeddedAssets;
+///
+/// static ASSETS: EmbeddedAssets = suika_server::include_assets!("assets.rs");
+/// ```
+#[macro_export]
+macro_rules! include_assets {
+    ($file:expr) => {
+        $crate::embed::EmbeddedAssets::from_entries(include!(concat!(env!("OUT_DIR"), "/", $file)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ENTRIES: &[EmbeddedEntry] = &[
+        (
+            "/app.js",
+            b"let a = 1;",
+            "\"js\"",
+            Some(60),
+            &[("br", b"br", "\"js-br\"")],
+        ),
+        ("/index.html", b"<h1>Home</h1>", "\"html\"", None, &[]),
+    ];
+
+    #[test]
+    fn test_embedded_assets() {
+        let assets = EmbeddedAssets::from_entries(("/srv/public", ENTRIES));
+        assert_eq!(assets.len(), 2);
+        assert_eq!(assets.source_dir(), "/srv/public");
+        assert!(assets.get("/missing.css").is_none());
+
+        let file = assets.get("app.js").unwrap();
+        assert_eq!(file.path(), "/app.js");
+        assert_eq!(file.contents(), b"let a = 1;");
+        assert_eq!(file.etag(), ETag::strong("js"));
+        assert_eq!(file.mime_type(), "application/javascript");
+        assert_eq!(file.modified(), Some(UNIX_EPOCH + Duration::from_secs(60)));
+        assert_eq!(file.encodings(), vec![ContentEncoding::Brotli]);
+        assert_eq!(
+            file.variant(ContentEncoding::Brotli),
+            Some((&b"br"[..], ETag::strong("js-br")))
+        );
+        assert_eq!(file.variant(ContentEncoding::Gzip), None);
+    }
+
+    #[test]
+    fn test_embedder_writes_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        fs::create_dir_all(public.join("css")).unwrap();
+        fs::write(
+            public.join("css").join("site.css"),
+            "body { margin: 0 }\n".repeat(20),
+        )
+        .unwrap();
+        fs::write(public.join("logo.png"), [0u8; 64]).unwrap();
+        fs::write(public.join(".env"), "SECRET=1").unwrap();
+        let out_file = dir.path().join("assets.rs");
+
+        Embedder::new(&public)
+            .with_precompressed(&[ContentEncoding::Gzip])
+            .write_to(&out_file)
+            .unwrap();
+
+        let code = fs::read_to_string(&out_file).unwrap();
+        let root = fs::canonicalize(&public).unwrap();
+        assert!(code.starts_with(&format!("({:?}, &[\n", root.to_string_lossy())));
+        assert!(code.contains(&format!(
+            "(\"/css/site.css\", include_bytes!({:?}) as &[u8], {:?}",
+            root.join("css").join("site.css").to_string_lossy(),
+            ETag::from_content("body { margin: 0 }\n".repeat(20).as_bytes()).to_string()
+        )));
+        assert!(code.contains("(\"gzip\", include_bytes!("));
+        assert!(code.contains("(\"/logo.png\", "));
+        assert!(!code.contains(".env"));
+        assert!(code.find("/css/site.css").unwrap() < code.find("/logo.png").unwrap());
+
+        let variant = fs::read(dir.path().join("assets.rs.variants").join("0.gz")).unwrap();
+        assert_eq!(
+            ContentEncoding::Gzip.decode(&variant, 1024).unwrap(),
+            "body { margin: 0 }\n".repeat(20).as_bytes()
+        );
+        assert!(!dir.path().join("assets.rs.variants").join("1.gz").exists());
+    }
+}
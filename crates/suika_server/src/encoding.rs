@@ -0,0 +1,356 @@
+use crate::response::ByteStream;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use futures::StreamExt;
+use std::fmt;
+use std::io::{Read, Result as IoResult, Write};
+
+/// An HTTP content coding supported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    /// Brotli (`br`).
+    Brotli,
+    /// Gzip (`gzip`).
+    Gzip,
+    /// Zlib-wrapped deflate (`deflate`).
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// The encodings supported by the server, in order of preference.
+    pub const ALL: [ContentEncoding; 3] = [
+        ContentEncoding::Brotli,
+        ContentEncoding::Gzip,
+        ContentEncoding::Deflate,
+    ];
+
+    /// Returns the encoding for a content-coding token, ignoring case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::encoding::ContentEncoding;
+    ///
+    /// assert_eq!(ContentEncoding::from_name("GZIP"), Some(ContentEncoding::Gzip));
+    /// assert_eq!(ContentEncoding::from_name("x-gzip"), Some(ContentEncoding::Gzip));
+    /// assert_eq!(ContentEncoding::from_name("zstd"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(ContentEncoding::Brotli),
+            "gzip" | "x-gzip" => Some(ContentEncoding::Gzip),
+            "deflate" => Some(ContentEncoding::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Returns the content-coding token used in headers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the file extension of files precompressed with this encoding, such as
+    /// `br` for `app.js.br`. Deflate has no conventional extension.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            ContentEncoding::Brotli => Some("br"),
+            ContentEncoding::Gzip => Some("gz"),
+            ContentEncoding::Deflate => None,
+        }
+    }
+
+    /// Compresses `data` with this encoding.
+    pub fn encode(&self, data: &[u8]) -> IoResult<Vec<u8>> {
+        let mut encoder = Encoder::new(*self);
+        let mut output = encoder.write(data)?;
+        output.extend(encoder.finish()?);
+        Ok(output)
+    }
+
+    /// Decompresses `data`, producing at most `max_size` bytes.
+    ///
+    /// Decompression stops as soon as the limit is exceeded, so small payloads that
+    /// expand enormously are rejected without being fully inflated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::encoding::{ContentEncoding, DecodeError};
+    ///
+    /// let data = vec![b'a'; 10_000];
+    /// let encoded = ContentEncoding::Gzip.encode(&data).unwrap();
+    ///
+    /// assert_eq!(ContentEncoding::Gzip.decode(&encoded, 10_000).unwrap(), data);
+    /// assert!(matches!(
+    ///     ContentEncoding::Gzip.decode(&encoded, 1_000),
+    ///     Err(DecodeError::TooLarge)
+    /// ));
+    /// ```
+    pub fn decode(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, DecodeError> {
+        let limit = max_size as u64 + 1;
+        let mut output = Vec::new();
+        let result = match self {
+            ContentEncoding::Brotli => brotli::Decompressor::new(data, 4096)
+                .take(limit)
+                .read_to_end(&mut output),
+            ContentEncoding::Gzip => MultiGzDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output),
+            ContentEncoding::Deflate => ZlibDecoder::new(data).take(limit).read_to_end(&mut output),
+        };
+        result.map_err(DecodeError::Invalid)?;
+        if output.len() > max_size {
+            return Err(DecodeError::TooLarge);
+        }
+        Ok(output)
+    }
+
+    /// Compresses a stream of chunks with this encoding.
+    ///
+    /// Each input chunk is flushed through the encoder so that output is produced as
+    /// soon as input is available.
+    pub fn encode_stream(&self, stream: ByteStream) -> ByteStream {
+        let encoder = Encoder::new(*self);
+        Box::pin(futures::stream::unfold(
+            Some((stream, encoder)),
+            |state| async move {
+                let (mut stream, mut encoder) = state?;
+                loop {
+                    match stream.next().await {
+                        Some(Ok(chunk)) => match encoder.write(&chunk) {
+                            Ok(output) if output.is_empty() => continue,
+                            Ok(output) => return Some((Ok(output), Some((stream, encoder)))),
+                            Err(e) => return Some((Err(e), None)),
+                        },
+                        Some(Err(e)) => return Some((Err(e), None)),
+                        None => return Some((encoder.finish(), None)),
+                    }
+                }
+            },
+        ))
+    }
+}
+
+/// An error produced while decompressing data.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The decompressed data exceeds the size limit.
+    TooLarge,
+    /// The data is not valid for the encoding.
+    Invalid(std::io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooLarge => write!(f, "decompressed data exceeds the size limit"),
+            DecodeError::Invalid(e) => write!(f, "invalid compressed data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// An incremental compressor writing into an in-memory buffer.
+enum Encoder {
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Creates an encoder for `encoding` with a balanced compression level.
+    fn new(encoding: ContentEncoding) -> Self {
+        match encoding {
+            ContentEncoding::Brotli => Encoder::Brotli(Box::new(brotli::CompressorWriter::new(
+                Vec::new(),
+                4096,
+                5,
+                22,
+            ))),
+            ContentEncoding::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default()))
+            }
+            ContentEncoding::Deflate => {
+                Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::default()))
+            }
+        }
+    }
+
+    /// Compresses and flushes `data`, returning the output produced so far.
+    fn write(&mut self, data: &[u8]) -> IoResult<Vec<u8>> {
+        match self {
+            Encoder::Brotli(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// Finishes the compressed stream, returning the remaining output.
+    fn finish(self) -> IoResult<Vec<u8>> {
+        match self {
+            Encoder::Brotli(encoder) => Ok(encoder.into_inner()),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Chooses the best encoding from an `Accept-Encoding` header value.
+///
+/// Codings are weighted by their `q` value; `*` matches every coding not listed
+/// explicitly and `q=0` rules a coding out. Ties are broken by the order of
+/// `supported`. Returns `None` if the response should not be encoded.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::encoding::{negotiate, ContentEncoding};
+///
+/// let all = &ContentEncoding::ALL;
+/// assert_eq!(negotiate("gzip, deflate, br", all), Some(ContentEncoding::Brotli));
+/// assert_eq!(negotiate("br;q=0.5, gzip;q=0.8", all), Some(ContentEncoding::Gzip));
+/// assert_eq!(negotiate("*;q=0.1, br;q=0", all), Some(ContentEncoding::Gzip));
+/// assert_eq!(negotiate("identity", all), None);
+/// ```
+pub fn negotiate(accept_encoding: &str, supported: &[ContentEncoding]) -> Option<ContentEncoding> {
+    let mut wildcard = None;
+    let mut explicit = Vec::new();
+
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        if coding.is_empty() {
+            continue;
+        }
+        let q = parts
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                if name.trim().eq_ignore_ascii_case("q") {
+                    value.trim().parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+
+        if coding == "*" {
+            wildcard = Some(q);
+        } else if let Some(encoding) = ContentEncoding::from_name(coding) {
+            explicit.push((encoding, q));
+        }
+    }
+
+    let mut best: Option<(ContentEncoding, f32)> = None;
+    for &encoding in supported {
+        let q = explicit
+            .iter()
+            .find(|(e, _)| *e == encoding)
+            .map(|(_, q)| *q)
+            .or(wildcard)
+            .unwrap_or(0.0);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoding: ContentEncoding, data: &[u8]) -> Vec<u8> {
+        encoding.decode(data, usize::MAX - 1).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let all = &ContentEncoding::ALL;
+        assert_eq!(negotiate("gzip", all), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate("deflate, gzip", all), Some(ContentEncoding::Gzip));
+        assert_eq!(
+            negotiate("gzip;q=0.5, deflate;q=0.9", all),
+            Some(ContentEncoding::Deflate)
+        );
+        assert_eq!(negotiate("*", all), Some(ContentEncoding::Brotli));
+        assert_eq!(negotiate("gzip;q=0, deflate;q=0", all), None);
+        assert_eq!(negotiate("", all), None);
+        assert_eq!(
+            negotiate("br", &[ContentEncoding::Gzip, ContentEncoding::Deflate]),
+            None
+        );
+        assert_eq!(
+            negotiate("GZIP; Q=0.3, zstd", all),
+            Some(ContentEncoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let data = b"hello hello hello hello hello hello".repeat(20);
+        for encoding in ContentEncoding::ALL {
+            let encoded = encoding.encode(&data).unwrap();
+            assert!(encoded.len() < data.len());
+            assert_eq!(decode(encoding, &encoded), data);
+        }
+    }
+
+    #[test]
+    fn test_decode_limits_and_errors() {
+        let bomb = ContentEncoding::Deflate
+            .encode(&vec![0; 1_000_000])
+            .unwrap();
+        assert!(bomb.len() < 10_000);
+        assert!(matches!(
+            ContentEncoding::Deflate.decode(&bomb, 64 * 1024),
+            Err(DecodeError::TooLarge)
+        ));
+        assert_eq!(
+            ContentEncoding::Deflate
+                .decode(&bomb, 1_000_000)
+                .unwrap()
+                .len(),
+            1_000_000
+        );
+
+        for encoding in ContentEncoding::ALL {
+            assert!(matches!(
+                encoding.decode(b"definitely not compressed", 1024),
+                Err(DecodeError::Invalid(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encode_stream_round_trip() {
+        for encoding in ContentEncoding::ALL {
+            let chunks: Vec<IoResult<Vec<u8>>> =
+                vec![Ok(b"first chunk, ".to_vec()), Ok(b"second chunk".to_vec())];
+            let encoded: Vec<u8> = encoding
+                .encode_stream(Box::pin(futures::stream::iter(chunks)))
+                .map(|chunk| chunk.unwrap())
+                .concat()
+                .await;
+            assert_eq!(decode(encoding, &encoded), b"first chunk, second chunk");
+        }
+    }
+}
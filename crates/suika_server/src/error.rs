@@ -11,6 +11,14 @@ pub enum HttpError {
     Forbidden(String),
     /// Not Found (404) error with a specific message.
     NotFound(String),
+    /// Precondition Failed (412) error with a specific message.
+    PreconditionFailed(String),
+    /// Payload Too Large (413) error with a specific message.
+    PayloadTooLarge(String),
+    /// Unsupported Media Type (415) error with a specific message.
+    UnsupportedMediaType(String),
+    /// Too Many Requests (429) error with a specific message.
+    TooManyRequests(String),
     /// Internal Server Error (500) with a specific message.
     InternalServerError(String),
 }
@@ -35,6 +43,10 @@ impl fmt::Display for HttpError {
             HttpError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             HttpError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             HttpError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+            HttpError::PreconditionFailed(msg) => write!(f, "Precondition Failed: {}", msg),
+            HttpError::PayloadTooLarge(msg) => write!(f, "Payload Too Large: {}", msg),
+            HttpError::UnsupportedMediaType(msg) => write!(f, "Unsupported Media Type: {}", msg),
+            HttpError::TooManyRequests(msg) => write!(f, "Too Many Requests: {}", msg),
             HttpError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
         }
     }
@@ -62,6 +74,10 @@ impl HttpError {
             HttpError::Unauthorized(msg) => (401, msg),
             HttpError::Forbidden(msg) => (403, msg),
             HttpError::NotFound(msg) => (404, msg),
+            HttpError::PreconditionFailed(msg) => (412, msg),
+            HttpError::PayloadTooLarge(msg) => (413, msg),
+            HttpError::UnsupportedMediaType(msg) => (415, msg),
+            HttpError::TooManyRequests(msg) => (429, msg),
             HttpError::InternalServerError(msg) => (500, msg),
         }
     }
@@ -77,6 +93,10 @@ mod tests {
         let unauthorized = HttpError::Unauthorized("No token".to_string());
         let forbidden = HttpError::Forbidden("Access denied".to_string());
         let not_found = HttpError::NotFound("Resource missing".to_string());
+        let precondition_failed = HttpError::PreconditionFailed("Stale".to_string());
+        let payload_too_large = HttpError::PayloadTooLarge("Too big".to_string());
+        let unsupported_media_type = HttpError::UnsupportedMediaType("Bad encoding".to_string());
+        let too_many_requests = HttpError::TooManyRequests("Slow down".to_string());
         let internal_server_error =
             HttpError::InternalServerError("Server malfunction".to_string());
 
@@ -84,6 +104,22 @@ mod tests {
         assert_eq!(format!("{}", unauthorized), "Unauthorized: No token");
         assert_eq!(format!("{}", forbidden), "Forbidden: Access denied");
         assert_eq!(format!("{}", not_found), "Not Found: Resource missing");
+        assert_eq!(
+            format!("{}", precondition_failed),
+            "Precondition Failed: Stale"
+        );
+        assert_eq!(
+            format!("{}", payload_too_large),
+            "Payload Too Large: Too big"
+        );
+        assert_eq!(
+            format!("{}", unsupported_media_type),
+            "Unsupported Media Type: Bad encoding"
+        );
+        assert_eq!(
+            format!("{}", too_many_requests),
+            "Too Many Requests: Slow down"
+        );
         assert_eq!(
             format!("{}", internal_server_error),
             "Internal Server Error: Server malfunction"
@@ -96,6 +132,10 @@ mod tests {
         let unauthorized = HttpError::Unauthorized("No token".to_string());
         let forbidden = HttpError::Forbidden("Access denied".to_string());
         let not_found = HttpError::NotFound("Resource missing".to_string());
+        let precondition_failed = HttpError::PreconditionFailed("Stale".to_string());
+        let payload_too_large = HttpError::PayloadTooLarge("Too big".to_string());
+        let unsupported_media_type = HttpError::UnsupportedMediaType("Bad encoding".to_string());
+        let too_many_requests = HttpError::TooManyRequests("Slow down".to_string());
         let internal_server_error =
             HttpError::InternalServerError("Server malfunction".to_string());
 
@@ -103,6 +143,10 @@ mod tests {
         assert_eq!(unauthorized.to_tuple(), (401, "No token"));
         assert_eq!(forbidden.to_tuple(), (403, "Access denied"));
         assert_eq!(not_found.to_tuple(), (404, "Resource missing"));
+        assert_eq!(precondition_failed.to_tuple(), (412, "Stale"));
+        assert_eq!(payload_too_large.to_tuple(), (413, "Too big"));
+        assert_eq!(unsupported_media_type.to_tuple(), (415, "Bad encoding"));
+        assert_eq!(too_many_requests.to_tuple(), (429, "Slow down"));
         assert_eq!(
             internal_server_error.to_tuple(),
             (500, "Server malfunction")
@@ -0,0 +1,170 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A value stored in `Extensions`, which can be cloned along with the map.
+trait Extension: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn Extension>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Clone + Send + Sync + 'static> Extension for T {
+    fn clone_box(&self) -> Box<dyn Extension> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// A map of values keyed by their type, attached to each `Request` and `Response`.
+///
+/// Middleware uses extensions to pass per-request data to the middleware and route
+/// handlers after it, such as the `RequestId` assigned by a `RequestIdMiddleware` or
+/// a user loaded from a session, without string keys or downcasting. Each type holds
+/// at most one value, so crates usually wrap their data in a type of their own.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::extensions::Extensions;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct TenantId(u32);
+///
+/// let mut extensions = Extensions::new();
+/// assert!(extensions.insert(TenantId(7)).is_none());
+/// assert_eq!(extensions.get::<TenantId>(), Some(&TenantId(7)));
+/// assert_eq!(extensions.remove::<TenantId>(), Some(TenantId(7)));
+/// assert!(extensions.is_empty());
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Extension>>,
+}
+
+impl Extensions {
+    /// Creates an empty `Extensions` map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type, if any.
+    pub fn insert<T: Clone + Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.into_any().downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns a reference to the value of type `T`, if any.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.as_ref().as_any().downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if any.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.as_mut().as_any_mut().downcast_mut())
+    }
+
+    /// Removes and returns the value of type `T`, if any.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.into_any().downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Returns `true` if the map holds a value of type `T`.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl Clone for Extensions {
+    fn clone(&self) -> Self {
+        Self {
+            map: self
+                .map
+                .iter()
+                .map(|(key, value)| (*key, (**value).clone_box()))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counter(u32);
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut extensions = Extensions::new();
+        assert!(extensions.get::<Counter>().is_none());
+        assert!(extensions.insert(Counter(1)).is_none());
+        assert_eq!(extensions.insert(Counter(2)), Some(Counter(1)));
+        assert!(extensions.insert("name".to_string()).is_none());
+
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(2)));
+        assert_eq!(extensions.get::<String>().map(String::as_str), Some("name"));
+        assert!(extensions.contains::<Counter>());
+        assert!(!extensions.contains::<u32>());
+        assert_eq!(extensions.len(), 2);
+    }
+
+    #[test]
+    fn test_get_mut_and_remove() {
+        let mut extensions = Extensions::new();
+        extensions.insert(Counter(1));
+        extensions.get_mut::<Counter>().unwrap().0 += 1;
+        assert_eq!(extensions.remove::<Counter>(), Some(Counter(2)));
+        assert!(extensions.remove::<Counter>().is_none());
+        assert!(extensions.is_empty());
+    }
+
+    #[test]
+    fn test_clone_is_independent() {
+        let mut extensions = Extensions::new();
+        extensions.insert(Counter(1));
+        let copy = extensions.clone();
+        extensions.insert(Counter(2));
+        assert_eq!(copy.get::<Counter>(), Some(&Counter(1)));
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(2)));
+    }
+}
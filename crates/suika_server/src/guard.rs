@@ -0,0 +1,188 @@
+use crate::request::Request;
+use std::fmt;
+use std::sync::Arc;
+
+/// An authorization check attached to a route or router.
+///
+/// A guard inspects the request after authentication middleware has run. If it
+/// rejects the request, the route handler is skipped and the request fails with
+/// `HttpError::Forbidden`. Guards are cheap to clone, so a single guard can be shared
+/// between several routes and routers, and they compose with `and`, `or` and `not`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::auth::Principal;
+/// use suika_server::guard::{require_permission, require_role, Guard};
+/// use suika_server::request::Request;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let editors = require_role("admin").or(require_permission("todos:write"));
+/// let not_readonly = Guard::new(|req: &Request| req.header("X-Read-Only").is_none());
+/// let guard = editors.and(not_readonly);
+///
+/// let mut request = Request::new(
+///     "GET /todos HTTP/1.1\r\n\r\n",
+///     Arc::new(Mutex::new(HashMap::new())),
+/// ).unwrap();
+/// assert!(!guard.check(&request));
+///
+/// request.set_principal(Principal::new("alice").with_permissions(vec!["todos:write"]));
+/// assert!(guard.check(&request));
+/// ```
+#[derive(Clone)]
+pub struct Guard {
+    check: Arc<dyn Fn(&Request) -> bool + Send + Sync>,
+}
+
+impl Guard {
+    /// Creates a guard from a closure over the request.
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - Returns `true` if the request is allowed.
+    pub fn new<F>(check: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            check: Arc::new(check),
+        }
+    }
+
+    /// Returns `true` if the guard allows the request.
+    pub fn check(&self, req: &Request) -> bool {
+        (self.check)(req)
+    }
+
+    /// Combines two guards, allowing the request only if both allow it.
+    pub fn and(self, other: Guard) -> Guard {
+        Guard::new(move |req| self.check(req) && other.check(req))
+    }
+
+    /// Combines two guards, allowing the request if either allows it.
+    pub fn or(self, other: Guard) -> Guard {
+        Guard::new(move |req| self.check(req) || other.check(req))
+    }
+
+    /// Inverts the guard.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Guard {
+        Guard::new(move |req| !self.check(req))
+    }
+}
+
+impl<F> From<F> for Guard
+where
+    F: Fn(&Request) -> bool + Send + Sync + 'static,
+{
+    fn from(check: F) -> Self {
+        Guard::new(check)
+    }
+}
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Guard")
+    }
+}
+
+/// Returns a guard that allows any request with an authenticated principal.
+pub fn require_authenticated() -> Guard {
+    Guard::new(|req| req.principal().is_some())
+}
+
+/// Returns a guard that allows requests whose principal has the given role.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::guard::require_role;
+/// use suika_server::router::Router;
+///
+/// let mut router = Router::new("/");
+/// router
+///     .get("/admin$", |_req, res| {
+///         Box::pin(async move {
+///             res.set_status(200).await;
+///             Ok(())
+///         })
+///     })
+///     .guard(require_role("admin"));
+/// ```
+pub fn require_role(role: &str) -> Guard {
+    let role = role.to_string();
+    Guard::new(move |req| req.principal().is_some_and(|p| p.has_role(&role)))
+}
+
+/// Returns a guard that allows requests whose principal has the given permission.
+pub fn require_permission(permission: &str) -> Guard {
+    let permission = permission.to_string();
+    Guard::new(move |req| {
+        req.principal()
+            .is_some_and(|p| p.has_permission(&permission))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Principal;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn request(principal: Option<Principal>) -> Request {
+        let mut req = Request::new(
+            "GET / HTTP/1.1\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        if let Some(principal) = principal {
+            req.set_principal(principal);
+        }
+        req
+    }
+
+    #[test]
+    fn test_require_role_and_permission() {
+        let anonymous = request(None);
+        let admin = request(Some(Principal::new("alice").with_roles(vec!["admin"])));
+        let writer = request(Some(
+            Principal::new("bob").with_permissions(vec!["todos:write"]),
+        ));
+
+        assert!(!require_authenticated().check(&anonymous));
+        assert!(require_authenticated().check(&writer));
+
+        assert!(!require_role("admin").check(&anonymous));
+        assert!(require_role("admin").check(&admin));
+        assert!(!require_role("admin").check(&writer));
+
+        assert!(require_permission("todos:write").check(&writer));
+        assert!(!require_permission("todos:write").check(&admin));
+    }
+
+    #[test]
+    fn test_guard_composition() {
+        let admin = request(Some(Principal::new("alice").with_roles(vec!["admin"])));
+        let writer = request(Some(
+            Principal::new("bob").with_permissions(vec!["todos:write"]),
+        ));
+
+        let either = require_role("admin").or(require_permission("todos:write"));
+        assert!(either.check(&admin));
+        assert!(either.check(&writer));
+
+        let both = require_role("admin").and(require_permission("todos:write"));
+        assert!(!both.check(&admin));
+        assert!(!both.check(&writer));
+
+        let not_admin = require_role("admin").not();
+        assert!(!not_admin.check(&admin));
+        assert!(not_admin.check(&writer));
+
+        let closure: Guard = (|req: &Request| req.method() == "GET").into();
+        assert!(closure.and(either).check(&admin));
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats a time as an HTTP date (IMF-fixdate), truncated to whole seconds.
+///
+/// Times before the Unix epoch are formatted as the epoch.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::http_date::format_http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(784111777);
+/// assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = secs / 86_400;
+    let remainder = secs % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        remainder / 3600,
+        (remainder % 3600) / 60,
+        remainder % 60
+    )
+}
+
+/// Parses an HTTP date.
+///
+/// Accepts the preferred IMF-fixdate format as well as the obsolete RFC 850 and asctime
+/// formats that recipients must still accept. Two-digit RFC 850 years are read as the
+/// most recent matching year that is no more than 50 years in the future.
+///
+/// Returns `None` for malformed dates, which callers should treat as absent.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::http_date::parse_http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = Some(UNIX_EPOCH + Duration::from_secs(784111777));
+/// assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), time);
+/// assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), time);
+/// assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), time);
+/// assert_eq!(parse_http_date("yesterday"), None);
+/// ```
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    match value.split_once(", ") {
+        Some((_, rest)) if rest.contains('-') => parse_rfc850_date(rest),
+        Some((_, rest)) => parse_imf_fixdate(rest),
+        None => parse_asctime_date(value),
+    }
+}
+
+/// Parses the part of an IMF-fixdate after the day name, such as
+/// `06 Nov 1994 08:49:37 GMT`.
+fn parse_imf_fixdate(rest: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = rest.split(' ').collect();
+    if parts.len() != 5 || parts[4] != "GMT" {
+        return None;
+    }
+    to_system_time(parts[2].parse().ok()?, parts[1], parts[0], parts[3])
+}
+
+/// Parses the part of an RFC 850 date after the day name, such as
+/// `06-Nov-94 08:49:37 GMT`.
+fn parse_rfc850_date(rest: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = rest.split(' ').collect();
+    if parts.len() != 3 || parts[2] != "GMT" {
+        return None;
+    }
+    let date: Vec<&str> = parts[0].split('-').collect();
+    if date.len() != 3 || date[2].len() != 2 {
+        return None;
+    }
+
+    let current_year = civil_from_days(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() / 86_400)
+            .unwrap_or(0) as i64,
+    )
+    .0;
+    let mut year = current_year - current_year % 100 + date[2].parse::<i64>().ok()?;
+    if year > current_year + 50 {
+        year -= 100;
+    }
+    to_system_time(year, date[1], date[0], parts[1])
+}
+
+/// Parses an asctime date, such as `Sun Nov  6 08:49:37 1994`.
+fn parse_asctime_date(value: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 5 {
+        return None;
+    }
+    to_system_time(parts[4].parse().ok()?, parts[1], parts[2], parts[3])
+}
+
+/// Converts the fields of a parsed date to a `SystemTime`, validating their ranges.
+fn to_system_time(year: i64, month: &str, day: &str, time: &str) -> Option<SystemTime> {
+    let day: u32 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    if time.len() != 3 || time[0] > 23 || time[1] > 59 || time[2] > 60 || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return None;
+    }
+    let secs = days as u64 * 86_400 + time[0] * 3600 + time[1] * 60 + time[2];
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Formats a time in UTC as used by the Common Log Format, such as
+/// `10/Oct/2000:13:55:36 +0000`.
+pub(crate) fn format_common_log_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let remainder = secs % 86_400;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        remainder / 3600,
+        (remainder % 3600) / 60,
+        remainder % 60
+    )
+}
+
+/// Formats a time in UTC as an RFC 3339 timestamp with millisecond precision, such as
+/// `2000-10-10T13:55:36.123Z`.
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let remainder = secs % 86_400;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        remainder / 3600,
+        (remainder % 3600) / 60,
+        remainder % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) civil date to days since the Unix epoch.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_http_date() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_millis(951_782_400_999)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_secs(1_735_689_599)),
+            "Tue, 31 Dec 2024 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn test_format_log_dates() {
+        let time = UNIX_EPOCH + Duration::from_millis(971_186_136_123);
+        assert_eq!(format_common_log_date(time), "10/Oct/2000:13:55:36 +0000");
+        assert_eq!(format_rfc3339(time), "2000-10-10T13:55:36.123Z");
+    }
+
+    #[test]
+    fn test_parse_http_date_round_trip() {
+        for secs in [0, 68_169_600, 951_782_400, 1_735_689_599, 4_102_444_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(parse_http_date(&format_http_date(time)), Some(time));
+        }
+    }
+
+    #[test]
+    fn test_parse_http_date_rejects_malformed() {
+        assert_eq!(parse_http_date(""), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37"), None);
+        assert_eq!(parse_http_date("Sun Nov 32 08:49:37 1994"), None);
+    }
+
+    #[test]
+    fn test_parse_obsolete_http_dates() {
+        let time = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), time);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), time);
+        assert_eq!(parse_http_date("Sun Nov 06 08:49:37 1994"), time);
+        assert_eq!(
+            parse_http_date("Tue Feb 29 00:00:00 2000"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_782_400))
+        );
+    }
+
+    #[test]
+    fn test_parse_rfc850_two_digit_years() {
+        let current_year = civil_from_days(
+            (SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                / 86_400) as i64,
+        )
+        .0;
+        for offset in [-49, 0, 50] {
+            let year = current_year + offset;
+            let date = format!("Monday, 01-Jan-{:02} 00:00:00 GMT", year % 100);
+            let expected =
+                UNIX_EPOCH + Duration::from_secs(days_from_civil(year, 1, 1) as u64 * 86_400);
+            assert_eq!(parse_http_date(&date), Some(expected));
+        }
+    }
+}
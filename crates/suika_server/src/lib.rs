@@ -1,7 +1,29 @@
+pub mod access_log;
+pub mod assets;
+pub mod auth;
+pub mod conditional;
+mod diagnostics;
+pub mod embed;
+pub mod encoding;
 pub mod error;
+pub mod extensions;
+pub mod guard;
+pub mod http_date;
+pub mod jwt;
 pub mod middleware;
+pub mod proxy;
+pub mod proxy_protocol;
+mod random;
+pub mod range;
 pub mod request;
 pub mod response;
 pub mod router;
+mod sendfile;
 pub mod server;
-pub use middleware::{CorsMiddleware, FaviconMiddleware, LoggerMiddleware, StaticFileMiddleware};
+pub mod state;
+pub use middleware::{
+    AssetMiddleware, AuthMiddleware, CompressionMiddleware, ConditionalMiddleware, CorsMiddleware,
+    CsrfMiddleware, DecompressionMiddleware, EmbeddedFileMiddleware, FaviconMiddleware,
+    IpFilterMiddleware, JwtMiddleware, LoggerMiddleware, RateLimitMiddleware, RequestIdMiddleware,
+    SecurityHeadersMiddleware, StaticFileMiddleware,
+};
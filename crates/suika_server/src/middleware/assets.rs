@@ -0,0 +1,244 @@
+use crate::assets::AssetManifest;
+use crate::conditional::respond;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::Response;
+use std::sync::Arc;
+use suika_utils::percent_decode;
+
+/// A middleware component for serving fingerprinted assets from an `AssetManifest`.
+///
+/// Requests for a fingerprinted path under the manifest's URL prefix, such as
+/// `/assets/style.3f9a1c2b.css`, are answered with the file, a strong `ETag` of its
+/// content and a long-lived `Cache-Control: public, max-age=31536000, immutable`
+/// header. All other requests, including plain paths like `/assets/style.css`, are
+/// passed to the next middleware so a `StaticFileMiddleware` can serve them. So are
+/// requests for files whose size or modification time changed since the manifest was
+/// built, until it is rebuilt.
+///
+/// # Examples
+///
+/// ```no_run
+/// use suika_server::assets::AssetManifest;
+/// use suika_server::middleware::{AssetMiddleware, StaticFileMiddleware};
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let manifest = Arc::new(AssetManifest::build("/assets", "public").unwrap());
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(AssetMiddleware::new(manifest)));
+/// server.use_middleware(Arc::new(StaticFileMiddleware::new("/assets", "public", 60)));
+/// ```
+pub struct AssetMiddleware {
+    manifest: Arc<AssetManifest>,
+    cache_control: String,
+}
+
+impl AssetMiddleware {
+    /// Creates a new `AssetMiddleware`.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The manifest of fingerprinted assets to serve.
+    pub fn new(manifest: Arc<AssetManifest>) -> Self {
+        Self {
+            manifest,
+            cache_control: "public, max-age=31536000, immutable".to_string(),
+        }
+    }
+
+    /// Sets the `Cache-Control` header for fingerprinted assets.
+    pub fn with_cache_control(mut self, cache_control: &str) -> Self {
+        self.cache_control = cache_control.to_string();
+        self
+    }
+}
+
+impl Middleware for AssetMiddleware {
+    /// Handles an incoming HTTP request by serving a fingerprinted asset if the request path matches one.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let asset = req
+                .path()
+                .strip_prefix(self.manifest.url_prefix())
+                .and_then(|relative| relative.strip_prefix('/'))
+                .filter(|_| matches!(req.method(), "GET" | "HEAD"))
+                .and_then(|relative| percent_decode(relative).ok())
+                .and_then(|relative| {
+                    self.manifest
+                        .resolve(&relative)
+                        .map(|(path, etag)| (relative.clone(), path.to_path_buf(), etag.clone()))
+                });
+
+            let (relative, path, etag) = match asset {
+                Some(asset) => asset,
+                None => return next.run(req, res).await,
+            };
+
+            // A file changed since the manifest was built no longer matches its
+            // fingerprint and must not be served under it with an immutable policy.
+            let current = tokio::fs::metadata(&path)
+                .await
+                .is_ok_and(|metadata| self.manifest.is_current(&relative, &metadata));
+            if !current {
+                return next.run(req, res).await;
+            }
+            let path = path.to_string_lossy().into_owned();
+
+            res.etag(&etag).await;
+            res.send_file_ranged(req, &path).await?;
+            res.header("Cache-Control", &self.cache_control).await;
+            respond(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HttpError;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Mutex;
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    async fn serve(
+        middleware: &AssetMiddleware,
+        path: &str,
+        headers: &str,
+    ) -> (Result<(), HttpError>, Response, bool) {
+        let mut req = Request::new(
+            &format!("GET {} HTTP/1.1\r\n{}\r\n", path, headers),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = middleware.handle(&mut req, &mut res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, res, called)
+    }
+
+    #[tokio::test]
+    async fn test_serves_fingerprinted_assets() {
+        let dir = tempfile::Builder::new().prefix("assets").tempdir().unwrap();
+        fs::write(dir.path().join("style.css"), "body { color: red }").unwrap();
+        let manifest = Arc::new(AssetManifest::build("/assets", dir.path()).unwrap());
+        let url = manifest.asset_url("style.css");
+        let middleware = AssetMiddleware::new(Arc::clone(&manifest));
+
+        let (result, res, called) = serve(&middleware, &url, "").await;
+        assert!(result.is_ok());
+        assert!(!called);
+        assert_eq!(res.status().await, Some(200));
+        assert_eq!(
+            res.get_header("Content-Type").await,
+            Some("text/css".to_string())
+        );
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("public, max-age=31536000, immutable".to_string())
+        );
+        let etag = res.get_header("ETag").await.unwrap();
+        assert!(!etag.starts_with("W/"));
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"body { color: red }"
+        );
+
+        let (_, res, _) = serve(&middleware, &url, &format!("If-None-Match: {}\r\n", etag)).await;
+        assert_eq!(res.status().await, Some(304));
+
+        let (_, _, called) = serve(&middleware, "/assets/style.css", "").await;
+        assert!(called);
+        let (_, _, called) = serve(&middleware, "/assets/style.00000000.css", "").await;
+        assert!(called);
+        let (_, _, called) = serve(&middleware, "/other/style.css", "").await;
+        assert!(called);
+    }
+
+    #[tokio::test]
+    async fn test_changed_assets_are_not_served() {
+        let dir = tempfile::Builder::new().prefix("assets").tempdir().unwrap();
+        let file = dir.path().join("style.css");
+        fs::write(&file, "body { color: red }").unwrap();
+        let manifest = Arc::new(AssetManifest::build("/assets", dir.path()).unwrap());
+        let url = manifest.asset_url("style.css");
+        let middleware = AssetMiddleware::new(manifest);
+
+        fs::write(&file, "body { color: blue; }").unwrap();
+        let (result, res, called) = serve(&middleware, &url, "").await;
+        assert!(result.is_ok());
+        assert!(called);
+        assert!(res.get_header("Cache-Control").await.is_none());
+
+        fs::remove_file(&file).unwrap();
+        let (_, _, called) = serve(&middleware, &url, "").await;
+        assert!(called);
+    }
+
+    #[tokio::test]
+    async fn test_custom_cache_control() {
+        let dir = tempfile::Builder::new().prefix("assets").tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "run()").unwrap();
+        let manifest = Arc::new(AssetManifest::build("/assets", dir.path()).unwrap());
+        let url = manifest.asset_url("app.js");
+        let middleware = AssetMiddleware::new(manifest).with_cache_control("public, max-age=86400");
+
+        let (_, res, _) = serve(&middleware, &url, "").await;
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("public, max-age=86400".to_string())
+        );
+    }
+}
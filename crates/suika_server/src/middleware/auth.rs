@@ -0,0 +1,303 @@
+use crate::auth::{BasicVerifier, BearerVerifier, Principal};
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::Response;
+use std::sync::Arc;
+use suika_utils::base64_decode;
+
+/// A middleware component that authenticates requests using the HTTP Basic and/or Bearer
+/// schemes.
+///
+/// Credentials from the `Authorization` header are checked against the configured
+/// verifiers. On success the resulting `Principal` is attached to the request and the
+/// next middleware runs. Otherwise the request is rejected with `HttpError::Unauthorized`
+/// and a `WWW-Authenticate` challenge for every configured scheme.
+///
+/// To protect only part of an application, add the middleware to a `Router` with
+/// `Router::use_middleware` instead of to the `Server`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::auth::Principal;
+/// use suika_server::middleware::AuthMiddleware;
+/// use suika_server::router::Router;
+/// use std::sync::Arc;
+///
+/// let auth = AuthMiddleware::new("api")
+///     .with_basic(|username: &str, password: &str| {
+///         (username == "alice" && password == "secret").then(|| Principal::new(username))
+///     })
+///     .with_bearer(|token: &str| (token == "let-me-in").then(|| Principal::new("service")));
+///
+/// let mut api = Router::new("/api");
+/// api.use_middleware(Arc::new(auth));
+/// ```
+pub struct AuthMiddleware {
+    realm: String,
+    basic: Option<Arc<dyn BasicVerifier>>,
+    bearer: Option<Arc<dyn BearerVerifier>>,
+}
+
+impl AuthMiddleware {
+    /// Creates a new `AuthMiddleware` for the given realm with no schemes enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `realm` - The protection space reported in `WWW-Authenticate` challenges.
+    pub fn new(realm: &str) -> Self {
+        Self {
+            realm: realm.to_string(),
+            basic: None,
+            bearer: None,
+        }
+    }
+
+    /// Creates a new `AuthMiddleware` accepting only HTTP Basic credentials.
+    pub fn basic<V: BasicVerifier + 'static>(realm: &str, verifier: V) -> Self {
+        Self::new(realm).with_basic(verifier)
+    }
+
+    /// Creates a new `AuthMiddleware` accepting only Bearer tokens.
+    pub fn bearer<V: BearerVerifier + 'static>(realm: &str, verifier: V) -> Self {
+        Self::new(realm).with_bearer(verifier)
+    }
+
+    /// Enables the HTTP Basic scheme using the given verifier.
+    pub fn with_basic<V: BasicVerifier + 'static>(mut self, verifier: V) -> Self {
+        self.basic = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Enables the Bearer scheme using the given verifier.
+    pub fn with_bearer<V: BearerVerifier + 'static>(mut self, verifier: V) -> Self {
+        self.bearer = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Authenticates the `Authorization` header value.
+    ///
+    /// Returns the principal on success, or the scheme whose credentials were rejected.
+    fn authenticate(&self, authorization: Option<&str>) -> Result<Principal, Option<Scheme>> {
+        let (scheme, credentials) = authorization
+            .and_then(|value| value.trim().split_once(' '))
+            .ok_or(None)?;
+        let credentials = credentials.trim();
+
+        if scheme.eq_ignore_ascii_case("Basic") {
+            let verifier = self.basic.as_ref().ok_or(None)?;
+            let decoded = base64_decode(credentials)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or(Some(Scheme::Basic))?;
+            let (username, password) = decoded.split_once(':').ok_or(Some(Scheme::Basic))?;
+            verifier
+                .verify(username, password)
+                .ok_or(Some(Scheme::Basic))
+        } else if scheme.eq_ignore_ascii_case("Bearer") {
+            let verifier = self.bearer.as_ref().ok_or(None)?;
+            verifier.verify(credentials).ok_or(Some(Scheme::Bearer))
+        } else {
+            Err(None)
+        }
+    }
+
+    /// Builds the `WWW-Authenticate` header value, flagging the rejected scheme if any.
+    fn challenge(&self, rejected: Option<Scheme>) -> String {
+        let mut challenges = Vec::new();
+        if self.basic.is_some() {
+            challenges.push(format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm));
+        }
+        if self.bearer.is_some() {
+            let mut challenge = format!("Bearer realm=\"{}\"", self.realm);
+            if rejected == Some(Scheme::Bearer) {
+                challenge.push_str(", error=\"invalid_token\"");
+            }
+            challenges.push(challenge);
+        }
+        challenges.join(", ")
+    }
+}
+
+/// The authentication schemes supported by `AuthMiddleware`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scheme {
+    Basic,
+    Bearer,
+}
+
+impl Middleware for AuthMiddleware {
+    /// Handles an incoming HTTP request by authenticating it before passing it on.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            match self.authenticate(req.header("Authorization")) {
+                Ok(principal) => {
+                    req.set_principal(principal);
+                    next.run(req, res).await
+                }
+                Err(rejected) => {
+                    res.header("WWW-Authenticate", &self.challenge(rejected))
+                        .await;
+                    let message = if rejected.is_some() {
+                        "Invalid credentials"
+                    } else {
+                        "Authentication required"
+                    };
+                    Err(HttpError::Unauthorized(message.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use crate::request::Request;
+    use crate::response::Response;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware that records the principal it saw
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        principal: Arc<TokioMutex<Option<Principal>>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                principal: Arc::new(TokioMutex::new(None)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let principal = Arc::clone(&self.principal);
+            Box::pin(async move {
+                *principal.lock().await = req.principal().cloned();
+                Ok(())
+            })
+        }
+    }
+
+    fn auth_middleware() -> AuthMiddleware {
+        AuthMiddleware::new("test")
+            .with_basic(|username: &str, password: &str| {
+                (username == "alice" && password == "secret")
+                    .then(|| Principal::new(username).with_roles(vec!["admin"]))
+            })
+            .with_bearer(|token: &str| (token == "good-token").then(|| Principal::new("bot")))
+    }
+
+    async fn run(
+        auth: &AuthMiddleware,
+        authorization: Option<&str>,
+    ) -> (Result<(), HttpError>, Response, Option<Principal>) {
+        let request = match authorization {
+            Some(value) => format!("GET /api HTTP/1.1\r\nAuthorization: {}\r\n\r\n", value),
+            None => "GET /api HTTP/1.1\r\n\r\n".to_string(),
+        };
+        let mut req = Request::new(&request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = auth.handle(&mut req, &mut res, next).await;
+        let principal = next_middleware.principal.lock().await.clone();
+        (result, res, principal)
+    }
+
+    #[tokio::test]
+    async fn test_auth_missing_credentials() {
+        let (result, res, principal) = run(&auth_middleware(), None).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        assert!(principal.is_none());
+
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("WWW-Authenticate"),
+            Some(&"Basic realm=\"test\", charset=\"UTF-8\", Bearer realm=\"test\"".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_valid_basic() {
+        // alice:secret
+        let (result, _, principal) = run(&auth_middleware(), Some("Basic YWxpY2U6c2VjcmV0")).await;
+
+        assert!(result.is_ok());
+        let principal = principal.unwrap();
+        assert_eq!(principal.id(), "alice");
+        assert!(principal.has_role("admin"));
+    }
+
+    #[tokio::test]
+    async fn test_auth_invalid_basic() {
+        // alice:wrong
+        let (result, _, principal) = run(&auth_middleware(), Some("Basic YWxpY2U6d3Jvbmc=")).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        assert!(principal.is_none());
+
+        let (result, _, _) = run(&auth_middleware(), Some("Basic !!!")).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_auth_valid_bearer() {
+        let (result, _, principal) = run(&auth_middleware(), Some("bearer good-token")).await;
+
+        assert!(result.is_ok());
+        assert_eq!(principal.unwrap().id(), "bot");
+    }
+
+    #[tokio::test]
+    async fn test_auth_invalid_bearer() {
+        let auth = AuthMiddleware::bearer("api", |_: &str| None);
+        let (result, res, _) = run(&auth, Some("Bearer bad-token")).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("WWW-Authenticate"),
+            Some(&"Bearer realm=\"api\", error=\"invalid_token\"".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_disabled_scheme() {
+        let auth = AuthMiddleware::bearer("api", |_: &str| Some(Principal::new("bot")));
+        let (result, _, _) = run(&auth, Some("Basic YWxpY2U6c2VjcmV0")).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+    }
+}
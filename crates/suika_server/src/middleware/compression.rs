@@ -0,0 +1,409 @@
+use crate::encoding::{negotiate, ContentEncoding};
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::{Body, Response};
+use suika_mime::is_compressible;
+
+/// A middleware component that compresses response bodies.
+///
+/// The encoding is negotiated from the request's `Accept-Encoding` header, honouring
+/// `q` values, and defaults to preferring brotli, then gzip, then deflate. Text and
+/// binary bodies are compressed when they are at least the minimum size (1 KiB by
+/// default); streamed bodies are always compressed chunk by chunk.
+///
+/// Responses are left untouched when they already have a `Content-Encoding`, carry
+/// `Cache-Control: no-transform`, have no body (`HEAD`, 1xx, 204 and 304) or are
+/// partial content, or when their `Content-Type` is already compressed, such as images,
+/// video or archives. Compressed responses get `Content-Encoding`, `Vary: Accept-Encoding`
+/// and a corrected `Content-Length`, and a strong `ETag` is made weak.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::encoding::ContentEncoding;
+/// use suika_server::middleware::CompressionMiddleware;
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let compression = CompressionMiddleware::new()
+///     .with_min_size(512)
+///     .with_encodings(&[ContentEncoding::Gzip, ContentEncoding::Deflate]);
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(compression));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompressionMiddleware {
+    min_size: usize,
+    encodings: Vec<ContentEncoding>,
+}
+
+impl CompressionMiddleware {
+    /// Creates a new `CompressionMiddleware` supporting brotli, gzip and deflate.
+    pub fn new() -> Self {
+        Self {
+            min_size: 1024,
+            encodings: ContentEncoding::ALL.to_vec(),
+        }
+    }
+
+    /// Sets the minimum body size in bytes for a response to be compressed.
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Sets the supported encodings, in order of preference.
+    pub fn with_encodings(mut self, encodings: &[ContentEncoding]) -> Self {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Returns `true` if the response is eligible for compression.
+    async fn is_eligible(&self, req: &Request, res: &Response) -> bool {
+        if req.method() == "HEAD" {
+            return false;
+        }
+        if let Some(status) = res.status().await {
+            if status < 200 || status == 204 || status == 206 || status == 304 {
+                return false;
+            }
+        }
+        if res.get_header("Content-Encoding").await.is_some() {
+            return false;
+        }
+        if let Some(cache_control) = res.get_header("Cache-Control").await {
+            if cache_control
+                .split(',')
+                .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+            {
+                return false;
+            }
+        }
+        match res.get_header("Content-Type").await {
+            Some(content_type) => is_compressible(&content_type),
+            None => true,
+        }
+    }
+}
+
+impl Default for CompressionMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for CompressionMiddleware {
+    /// Handles an incoming HTTP request by compressing the response produced by the
+    /// rest of the stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            next.run(req, res).await?;
+
+            if !self.is_eligible(req, res).await {
+                return Ok(());
+            }
+
+            let body = match res.take_body().await {
+                Some(body) => body,
+                None => return Ok(()),
+            };
+
+            let size = match &body {
+                Body::Text(text) => Some(text.len()),
+                Body::Binary(bytes) => Some(bytes.len()),
+                Body::Stream(_) => None,
+                Body::File(file) => Some(file.len() as usize),
+            };
+            if size.is_some_and(|size| size < self.min_size) {
+                res.set_body(Some(body)).await;
+                return Ok(());
+            }
+
+            res.append_header("Vary", "Accept-Encoding").await;
+
+            let encoding = match req
+                .header("Accept-Encoding")
+                .and_then(|accept| negotiate(accept, &self.encodings))
+            {
+                Some(encoding) => encoding,
+                None => {
+                    res.set_body(Some(body)).await;
+                    return Ok(());
+                }
+            };
+
+            match body {
+                Body::Text(text) => {
+                    let compressed = encode(encoding, text.as_bytes())?;
+                    res.header("Content-Length", &compressed.len().to_string())
+                        .await;
+                    res.body_bytes(compressed).await;
+                }
+                Body::Binary(bytes) => {
+                    let compressed = encode(encoding, &bytes)?;
+                    res.header("Content-Length", &compressed.len().to_string())
+                        .await;
+                    res.body_bytes(compressed).await;
+                }
+                Body::Stream(stream) => match stream.take() {
+                    Some(stream) => {
+                        res.remove_header("Content-Length").await;
+                        res.body_stream(encoding.encode_stream(stream)).await;
+                    }
+                    None => {
+                        res.set_body(Some(Body::Stream(stream))).await;
+                        return Ok(());
+                    }
+                },
+                Body::File(file) => match file.take_stream() {
+                    Some(stream) => {
+                        res.remove_header("Content-Length").await;
+                        res.body_stream(encoding.encode_stream(stream)).await;
+                    }
+                    None => {
+                        res.set_body(Some(Body::File(file))).await;
+                        return Ok(());
+                    }
+                },
+            }
+
+            res.header("Content-Encoding", encoding.name()).await;
+            if let Some(etag) = res.get_header("ETag").await {
+                if !etag.starts_with("W/") {
+                    res.header("ETag", &format!("W/{}", etag)).await;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Compresses a complete body, mapping failures to an internal server error.
+fn encode(encoding: ContentEncoding, data: &[u8]) -> Result<Vec<u8>, HttpError> {
+    encoding
+        .encode(data)
+        .map_err(|e| HttpError::InternalServerError(format!("Failed to compress response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use flate2::read::GzDecoder;
+    use futures::StreamExt;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    // Mock handler producing a fixed response
+    struct MockHandler {
+        content_type: &'static str,
+        body: Body,
+    }
+
+    impl Middleware for MockHandler {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                res.set_status(200).await;
+                res.header("Content-Type", self.content_type).await;
+                res.header("ETag", "\"abc\"").await;
+                let body = match &self.body {
+                    Body::Stream(_) => {
+                        Body::Stream(crate::response::StreamBody::new(futures::stream::iter(
+                            vec![Ok(b"streamed ".repeat(100)), Ok(b"body".to_vec())],
+                        )))
+                    }
+                    body => body.clone(),
+                };
+                res.set_body(Some(body)).await;
+                Ok(())
+            })
+        }
+    }
+
+    async fn run(
+        compression: &CompressionMiddleware,
+        accept_encoding: Option<&str>,
+        content_type: &'static str,
+        body: Body,
+    ) -> Response {
+        let raw = match accept_encoding {
+            Some(accept) => format!("GET / HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n", accept),
+            None => "GET / HTTP/1.1\r\n\r\n".to_string(),
+        };
+        let mut req = Request::new(&raw, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockHandler { content_type, body })];
+        let next = Next::new(middleware_stack.as_slice());
+
+        compression.handle(&mut req, &mut res, next).await.unwrap();
+        res
+    }
+
+    fn large_text() -> String {
+        "The quick brown fox jumps over the lazy dog. ".repeat(100)
+    }
+
+    #[tokio::test]
+    async fn test_compresses_text_with_gzip() {
+        let res = run(
+            &CompressionMiddleware::new(),
+            Some("gzip, deflate"),
+            "text/html",
+            Body::Text(large_text()),
+        )
+        .await;
+
+        let inner = res.get_inner().await;
+        let headers = inner.headers();
+        assert_eq!(headers.get("Content-Encoding"), Some(&"gzip".to_string()));
+        assert_eq!(headers.get("Vary"), Some(&"Accept-Encoding".to_string()));
+        assert_eq!(headers.get("ETag"), Some(&"W/\"abc\"".to_string()));
+
+        let compressed = match inner.body() {
+            Some(Body::Binary(bytes)) => bytes.clone(),
+            other => panic!("Unexpected body: {:?}", other),
+        };
+        assert_eq!(
+            headers.get("Content-Length"),
+            Some(&compressed.len().to_string())
+        );
+
+        let mut decompressed = String::new();
+        GzDecoder::new(&compressed[..])
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, large_text());
+    }
+
+    #[tokio::test]
+    async fn test_prefers_brotli_and_honours_q_values() {
+        let compression = CompressionMiddleware::new();
+
+        let res = run(
+            &compression,
+            Some("gzip, br"),
+            "application/json",
+            Body::Text(large_text()),
+        )
+        .await;
+        assert_eq!(
+            res.get_header("Content-Encoding").await,
+            Some("br".to_string())
+        );
+
+        let res = run(
+            &compression,
+            Some("br;q=0.2, deflate;q=0.9"),
+            "application/json",
+            Body::Text(large_text()),
+        )
+        .await;
+        assert_eq!(
+            res.get_header("Content-Encoding").await,
+            Some("deflate".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skips_small_and_unacceptable_bodies() {
+        let compression = CompressionMiddleware::new();
+
+        let res = run(
+            &compression,
+            Some("gzip"),
+            "text/plain",
+            Body::Text("tiny".to_string()),
+        )
+        .await;
+        assert_eq!(res.get_header("Content-Encoding").await, None);
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Text("tiny".to_string()))
+        );
+
+        let res = run(&compression, None, "text/plain", Body::Text(large_text())).await;
+        assert_eq!(res.get_header("Content-Encoding").await, None);
+        assert_eq!(
+            res.get_header("Vary").await,
+            Some("Accept-Encoding".to_string())
+        );
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Text(large_text()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skips_already_compressed_types() {
+        let res = run(
+            &CompressionMiddleware::new(),
+            Some("gzip"),
+            "image/png",
+            Body::Binary(vec![0; 4096]),
+        )
+        .await;
+        assert_eq!(res.get_header("Content-Encoding").await, None);
+        assert_eq!(res.get_header("Vary").await, None);
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Binary(vec![0; 4096]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compresses_streaming_bodies() {
+        let res = run(
+            &CompressionMiddleware::new().with_encodings(&[ContentEncoding::Gzip]),
+            Some("br, gzip"),
+            "text/plain",
+            Body::Stream(crate::response::StreamBody::new(futures::stream::empty())),
+        )
+        .await;
+        assert_eq!(
+            res.get_header("Content-Encoding").await,
+            Some("gzip".to_string())
+        );
+        assert_eq!(res.get_header("Content-Length").await, None);
+
+        let stream = match res.take_body().await {
+            Some(Body::Stream(stream)) => stream.take().unwrap(),
+            other => panic!("Unexpected body: {:?}", other),
+        };
+        let compressed: Vec<u8> = stream.map(|chunk| chunk.unwrap()).concat().await;
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&compressed[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(
+            decompressed,
+            [b"streamed ".repeat(100), b"body".to_vec()].concat()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::conditional::{respond, ETag};
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::{Body, Response};
+
+/// A middleware component that answers conditional `GET` and `HEAD` requests.
+///
+/// After the rest of the stack has produced a successful response, the middleware adds
+/// an `ETag` computed from a hash of the body unless the handler already set one with
+/// `Response::etag`. It then evaluates `If-None-Match`, `If-Modified-Since`, `If-Match`
+/// and `If-Unmodified-Since` against the response's `ETag` and `Last-Modified` headers,
+/// turning the response into `304 Not Modified` or failing with `412 Precondition Failed`.
+///
+/// Register it after a `CompressionMiddleware` so that tags are computed from the
+/// uncompressed body. Handlers of state-changing requests should check preconditions
+/// themselves with `conditional::evaluate` before applying changes.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::ConditionalMiddleware;
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(ConditionalMiddleware::new()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConditionalMiddleware {
+    weak: bool,
+}
+
+impl ConditionalMiddleware {
+    /// Creates a new `ConditionalMiddleware` generating strong entity tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether generated entity tags are weak.
+    pub fn with_weak_etags(mut self, weak: bool) -> Self {
+        self.weak = weak;
+        self
+    }
+}
+
+impl Middleware for ConditionalMiddleware {
+    /// Handles an incoming HTTP request by evaluating its conditional headers against
+    /// the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            next.run(req, res).await?;
+
+            if !matches!(req.method(), "GET" | "HEAD") || res.status().await != Some(200) {
+                return Ok(());
+            }
+
+            if res.get_header("ETag").await.is_none() {
+                let etag = match res.get_inner().await.body() {
+                    Some(Body::Text(text)) => Some(ETag::from_content(text.as_bytes())),
+                    Some(Body::Binary(bytes)) => Some(ETag::from_content(bytes)),
+                    _ => None,
+                };
+                if let Some(etag) = etag {
+                    if self.weak {
+                        res.etag(&ETag::weak(etag.tag())).await;
+                    } else {
+                        res.etag(&etag).await;
+                    }
+                }
+            }
+
+            respond(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HttpError;
+    use crate::middleware::{Middleware, Next};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    // Mock handler returning a JSON document
+    struct MockHandler {
+        etag: Option<ETag>,
+    }
+
+    impl Middleware for MockHandler {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                res.set_status(200).await;
+                if let Some(etag) = &self.etag {
+                    res.etag(etag).await;
+                }
+                res.body("{\"todos\":[]}".to_string()).await;
+                Ok(())
+            })
+        }
+    }
+
+    async fn run(
+        conditional: &ConditionalMiddleware,
+        handler: MockHandler,
+        request: &str,
+    ) -> (Result<(), HttpError>, Response) {
+        let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![Arc::new(handler)];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = conditional.handle(&mut req, &mut res, next).await;
+        (result, res)
+    }
+
+    #[tokio::test]
+    async fn test_generates_etag_and_returns_not_modified() {
+        let conditional = ConditionalMiddleware::new();
+        let (result, res) = run(
+            &conditional,
+            MockHandler { etag: None },
+            "GET /todos HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        assert!(result.is_ok());
+        let etag = res.get_header("ETag").await.unwrap();
+        assert_eq!(etag, ETag::from_content(b"{\"todos\":[]}").to_string());
+        assert_eq!(res.status().await, Some(200));
+
+        let (result, res) = run(
+            &conditional,
+            MockHandler { etag: None },
+            &format!("GET /todos HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n", etag),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(res.status().await, Some(304));
+        assert_eq!(res.get_inner().await.body(), &None);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_handler_etag() {
+        let conditional = ConditionalMiddleware::new().with_weak_etags(true);
+        let (_, res) = run(
+            &conditional,
+            MockHandler {
+                etag: Some(ETag::strong("rev-7")),
+            },
+            "GET /todos HTTP/1.1\r\nIf-None-Match: \"rev-6\"\r\n\r\n",
+        )
+        .await;
+        assert_eq!(res.get_header("ETag").await, Some("\"rev-7\"".to_string()));
+        assert_eq!(res.status().await, Some(200));
+
+        let (_, res) = run(
+            &conditional,
+            MockHandler { etag: None },
+            "GET /todos HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        assert!(res.get_header("ETag").await.unwrap().starts_with("W/\""));
+    }
+
+    #[tokio::test]
+    async fn test_if_match_failure() {
+        let (result, _) = run(
+            &ConditionalMiddleware::new(),
+            MockHandler {
+                etag: Some(ETag::strong("rev-7")),
+            },
+            "GET /todos HTTP/1.1\r\nIf-Match: \"rev-6\"\r\n\r\n",
+        )
+        .await;
+        assert!(matches!(result, Err(HttpError::PreconditionFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ignores_unsafe_methods() {
+        let (result, res) = run(
+            &ConditionalMiddleware::new(),
+            MockHandler { etag: None },
+            "POST /todos HTTP/1.1\r\nIf-None-Match: *\r\n\r\n",
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(res.status().await, Some(200));
+        assert_eq!(res.get_header("ETag").await, None);
+    }
+}
@@ -1,9 +1,191 @@
+use crate::error::HttpError;
 use crate::middleware::{Middleware, MiddlewareFuture, Next};
 use crate::request::Request;
 use crate::response::Response;
+use std::sync::Arc;
+
+/// The origins a `CorsMiddleware` accepts.
+#[derive(Clone)]
+enum AllowedOrigins {
+    Any,
+    List(Vec<String>),
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
 
 /// A middleware component for handling CORS (Cross-Origin Resource Sharing).
-pub struct CorsMiddleware;
+///
+/// Requests without an `Origin` header, and same-origin requests such as the app's own
+/// form posts, which browsers also send with an `Origin` header, are passed on without
+/// CORS headers unless their origin is allowed. Requests from any other origin that is
+/// not allowed are rejected with `HttpError::Forbidden`. For allowed origins the
+/// middleware answers preflight requests itself, after checking
+/// `Access-Control-Request-Method` and `Access-Control-Request-Headers` against the
+/// allowed methods and headers, and adds the CORS response headers to other requests.
+///
+/// By default every origin is allowed and `Access-Control-Allow-Origin: *` is sent. With
+/// an explicit origin list or predicate the matched origin is echoed back together with
+/// `Vary: Origin`.
+///
+/// Credentials can only be allowed together with an origin list or predicate, as
+/// browsers refuse credentialed responses with the wildcard origin.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::CorsMiddleware;
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let cors = CorsMiddleware::new()
+///     .with_allowed_origins(vec!["https://app.example.com"])
+///     .with_allowed_headers(vec!["Content-Type", "X-CSRF-Token"])
+///     .with_exposed_headers(vec!["RateLimit-Remaining"])
+///     .with_credentials(true)
+///     .with_max_age(600);
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(cors));
+/// ```
+#[derive(Clone)]
+pub struct CorsMiddleware {
+    origins: AllowedOrigins,
+    methods: Vec<String>,
+    headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+impl CorsMiddleware {
+    /// Creates a new `CorsMiddleware` allowing any origin, the `GET`, `POST`, `PUT`,
+    /// `DELETE` and `OPTIONS` methods and the `Content-Type` and `Authorization` headers.
+    pub fn new() -> Self {
+        Self {
+            origins: AllowedOrigins::Any,
+            methods: ["GET", "POST", "PUT", "DELETE", "OPTIONS"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
+            exposed_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allows only the given origins, e.g. `https://app.example.com`.
+    pub fn with_allowed_origins<T: Into<String>>(mut self, origins: Vec<T>) -> Self {
+        self.origins = AllowedOrigins::List(origins.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Allows the origins for which `predicate` returns `true`.
+    pub fn with_origin_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins = AllowedOrigins::Predicate(Arc::new(predicate));
+        self
+    }
+
+    /// Sets the methods allowed in cross-origin requests.
+    pub fn with_allowed_methods<T: Into<String>>(mut self, methods: Vec<T>) -> Self {
+        self.methods = methods
+            .into_iter()
+            .map(|m| m.into().to_ascii_uppercase())
+            .collect();
+        self
+    }
+
+    /// Sets the request headers allowed in cross-origin requests.
+    pub fn with_allowed_headers<T: Into<String>>(mut self, headers: Vec<T>) -> Self {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the response headers exposed to cross-origin scripts.
+    pub fn with_exposed_headers<T: Into<String>>(mut self, headers: Vec<T>) -> Self {
+        self.exposed_headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether cross-origin requests may include credentials such as cookies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed while any origin is allowed. Call
+    /// `with_allowed_origins` or `with_origin_predicate` first.
+    pub fn with_credentials(mut self, credentials: bool) -> Self {
+        assert!(
+            !credentials || !matches!(self.origins, AllowedOrigins::Any),
+            "CORS credentials cannot be allowed for any origin; set allowed origins first"
+        );
+        self.credentials = credentials;
+        self
+    }
+
+    /// Sets how long, in seconds, browsers may cache preflight results.
+    pub fn with_max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Returns `true` if `origin` is allowed.
+    fn is_allowed_origin(&self, origin: &str) -> bool {
+        match &self.origins {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins.iter().any(|o| o == origin),
+            AllowedOrigins::Predicate(predicate) => predicate(origin),
+        }
+    }
+
+    /// Returns `true` if the requested method and headers of a preflight are allowed.
+    fn is_allowed_preflight(&self, method: &str, headers: Option<&str>) -> bool {
+        let method_allowed = self.methods.iter().any(|m| m == method);
+        let headers_allowed = headers
+            .map(|headers| {
+                headers
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty())
+                    .all(|h| self.headers.iter().any(|a| a.eq_ignore_ascii_case(h)))
+            })
+            .unwrap_or(true);
+        method_allowed && headers_allowed
+    }
+
+    /// Adds the headers shared by preflight and actual responses.
+    async fn add_origin_headers(&self, res: &Response, origin: &str) {
+        if matches!(self.origins, AllowedOrigins::Any) {
+            res.header("Access-Control-Allow-Origin", "*").await;
+        } else {
+            res.header("Access-Control-Allow-Origin", origin).await;
+            res.append_header("Vary", "Origin").await;
+        }
+        if self.credentials {
+            res.header("Access-Control-Allow-Credentials", "true").await;
+        }
+    }
+}
+
+/// Returns `true` if `origin` is the origin the request was sent to, comparing the
+/// scheme and host and ignoring default ports.
+fn is_same_origin(req: &Request, origin: &str) -> bool {
+    let scheme = req.scheme();
+    let host = match req.host() {
+        Some(host) => host,
+        None => return false,
+    };
+    let default_port = if scheme == "https" { ":443" } else { ":80" };
+    let host = host.strip_suffix(default_port).unwrap_or(host);
+    origin.eq_ignore_ascii_case(&format!("{}://{}", scheme, host))
+}
+
+impl Default for CorsMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Middleware for CorsMiddleware {
     /// Handles an incoming HTTP request by adding CORS headers.
@@ -24,23 +206,60 @@ impl Middleware for CorsMiddleware {
         mut next: Next<'a>,
     ) -> MiddlewareFuture<'a> {
         Box::pin(async move {
-            res.header("Access-Control-Allow-Origin", "*").await;
-            res.header(
-                "Access-Control-Allow-Methods",
-                "GET, POST, PUT, DELETE, OPTIONS",
-            )
-            .await;
-            res.header(
-                "Access-Control-Allow-Headers",
-                "Content-Type, Authorization",
-            )
-            .await;
+            if !matches!(self.origins, AllowedOrigins::Any) {
+                res.append_header("Vary", "Origin").await;
+            }
+
+            let origin = match req.header("Origin") {
+                Some(origin) => origin.to_string(),
+                None => return next.run(req, res).await,
+            };
+
+            if !self.is_allowed_origin(&origin) {
+                if is_same_origin(req, &origin) {
+                    return next.run(req, res).await;
+                }
+                return Err(HttpError::Forbidden("Origin not allowed".to_string()));
+            }
+
+            let requested_method = req
+                .header("Access-Control-Request-Method")
+                .map(|m| m.trim().to_ascii_uppercase());
 
-            if req.method() == "OPTIONS" {
+            if let (true, Some(method)) = (req.method() == "OPTIONS", requested_method) {
+                let requested_headers = req.header("Access-Control-Request-Headers");
+                if !self.is_allowed_preflight(&method, requested_headers) {
+                    return Err(HttpError::Forbidden(
+                        "CORS preflight not allowed".to_string(),
+                    ));
+                }
+
+                self.add_origin_headers(res, &origin).await;
+                res.append_header("Vary", "Access-Control-Request-Method")
+                    .await;
+                res.append_header("Vary", "Access-Control-Request-Headers")
+                    .await;
+                res.header("Access-Control-Allow-Methods", &self.methods.join(", "))
+                    .await;
+                res.header("Access-Control-Allow-Headers", &self.headers.join(", "))
+                    .await;
+                if let Some(max_age) = self.max_age {
+                    res.header("Access-Control-Max-Age", &max_age.to_string())
+                        .await;
+                }
                 res.set_status(204).await;
                 return Ok(());
             }
 
+            self.add_origin_headers(res, &origin).await;
+            if !self.exposed_headers.is_empty() {
+                res.header(
+                    "Access-Control-Expose-Headers",
+                    &self.exposed_headers.join(", "),
+                )
+                .await;
+            }
+
             next.run(req, res).await
         })
     }
@@ -89,14 +308,14 @@ mod tests {
     #[tokio::test]
     async fn test_cors_middleware_headers() {
         let mut req = Request::new(
-            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n",
             Arc::new(Mutex::new(HashMap::new())),
         )
         .unwrap();
 
         let mut res = Response::new(None);
 
-        let cors_middleware = CorsMiddleware;
+        let cors_middleware = CorsMiddleware::new();
         let next_middleware = MockNextMiddleware::new();
         let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
             vec![Arc::new(next_middleware.clone())];
@@ -112,14 +331,8 @@ mod tests {
             inner.headers().get("Access-Control-Allow-Origin"),
             Some(&"*".to_string())
         );
-        assert_eq!(
-            inner.headers().get("Access-Control-Allow-Methods"),
-            Some(&"GET, POST, PUT, DELETE, OPTIONS".to_string())
-        );
-        assert_eq!(
-            inner.headers().get("Access-Control-Allow-Headers"),
-            Some(&"Content-Type, Authorization".to_string())
-        );
+        assert!(!inner.headers().contains_key("Access-Control-Allow-Methods"));
+        assert!(!inner.headers().contains_key("Vary"));
 
         let next_called = *next_middleware.called.lock().await;
         assert!(next_called);
@@ -128,14 +341,14 @@ mod tests {
     #[tokio::test]
     async fn test_cors_middleware_options_request() {
         let mut req = Request::new(
-            "OPTIONS / HTTP/1.1\r\n\r\n",
+            "OPTIONS / HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: PUT\r\n\r\n",
             Arc::new(Mutex::new(HashMap::new())),
         )
         .unwrap();
 
         let mut res = Response::new(None);
 
-        let cors_middleware = CorsMiddleware;
+        let cors_middleware = CorsMiddleware::new();
         let next_middleware = MockNextMiddleware::new();
         let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
             vec![Arc::new(next_middleware.clone())];
@@ -164,4 +377,182 @@ mod tests {
         let next_called = *next_middleware.called.lock().await;
         assert!(!next_called);
     }
+
+    async fn run(
+        cors: &CorsMiddleware,
+        request: &str,
+    ) -> (Result<(), HttpError>, crate::response::ResponseInner, bool) {
+        let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = cors.handle(&mut req, &mut res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, res.get_inner().await, called)
+    }
+
+    fn spa_cors() -> CorsMiddleware {
+        CorsMiddleware::new()
+            .with_allowed_origins(vec!["https://app.example.com"])
+            .with_allowed_methods(vec!["get", "post"])
+            .with_allowed_headers(vec!["Content-Type", "X-CSRF-Token"])
+            .with_exposed_headers(vec!["X-Request-Id"])
+            .with_credentials(true)
+            .with_max_age(600)
+    }
+
+    #[tokio::test]
+    async fn test_cors_without_origin_passes_through() {
+        let (result, inner, called) = run(&spa_cors(), "GET / HTTP/1.1\r\n\r\n").await;
+
+        assert!(result.is_ok());
+        assert!(called);
+        assert!(!inner.headers().contains_key("Access-Control-Allow-Origin"));
+        assert_eq!(inner.headers().get("Vary"), Some(&"Origin".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cors_echoes_allowed_origin_with_credentials() {
+        let (result, inner, called) = run(
+            &spa_cors(),
+            "POST / HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n",
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert!(called);
+        let headers = inner.headers();
+        assert_eq!(
+            headers.get("Access-Control-Allow-Origin"),
+            Some(&"https://app.example.com".to_string())
+        );
+        assert_eq!(
+            headers.get("Access-Control-Allow-Credentials"),
+            Some(&"true".to_string())
+        );
+        assert_eq!(
+            headers.get("Access-Control-Expose-Headers"),
+            Some(&"X-Request-Id".to_string())
+        );
+        assert_eq!(headers.get("Vary"), Some(&"Origin".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cors_rejects_disallowed_origin() {
+        let (result, inner, called) = run(
+            &spa_cors(),
+            "GET / HTTP/1.1\r\nOrigin: https://evil.example.com\r\n\r\n",
+        )
+        .await;
+
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!called);
+        assert!(!inner.headers().contains_key("Access-Control-Allow-Origin"));
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_validation() {
+        let (result, inner, called) = run(
+            &spa_cors(),
+            "OPTIONS /todos HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: content-type, x-csrf-token\r\n\r\n",
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert!(!called);
+        assert_eq!(inner.status_code(), Some(204));
+        let headers = inner.headers();
+        assert_eq!(
+            headers.get("Access-Control-Allow-Methods"),
+            Some(&"GET, POST".to_string())
+        );
+        assert_eq!(
+            headers.get("Access-Control-Allow-Headers"),
+            Some(&"Content-Type, X-CSRF-Token".to_string())
+        );
+        assert_eq!(
+            headers.get("Access-Control-Max-Age"),
+            Some(&"600".to_string())
+        );
+        assert_eq!(
+            headers.get("Vary"),
+            Some(
+                &"Origin, Access-Control-Request-Method, Access-Control-Request-Headers"
+                    .to_string()
+            )
+        );
+
+        let (result, _, _) = run(
+            &spa_cors(),
+            "OPTIONS /todos HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: DELETE\r\n\r\n",
+        )
+        .await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+
+        let (result, _, _) = run(
+            &spa_cors(),
+            "OPTIONS /todos HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: X-Secret\r\n\r\n",
+        )
+        .await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_cors_origin_predicate() {
+        let cors = CorsMiddleware::new()
+            .with_origin_predicate(|origin: &str| origin.ends_with(".example.com"));
+
+        let (result, inner, _) = run(
+            &cors,
+            "GET / HTTP/1.1\r\nOrigin: https://admin.example.com\r\n\r\n",
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(
+            inner.headers().get("Access-Control-Allow-Origin"),
+            Some(&"https://admin.example.com".to_string())
+        );
+        assert!(!inner
+            .headers()
+            .contains_key("Access-Control-Allow-Credentials"));
+
+        let (result, _, _) = run(
+            &cors,
+            "GET / HTTP/1.1\r\nOrigin: https://example.org\r\n\r\n",
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cors_passes_same_origin_requests() {
+        for request in [
+            "POST /todos HTTP/1.1\r\nHost: todo.example.com\r\nOrigin: http://todo.example.com\r\n\r\n",
+            "POST /todos HTTP/1.1\r\nHost: todo.example.com:80\r\nOrigin: http://todo.example.com\r\n\r\n",
+            "POST /todos HTTP/1.1\r\nHost: localhost:8080\r\nOrigin: http://localhost:8080\r\n\r\n",
+        ] {
+            let (result, inner, called) = run(&spa_cors(), request).await;
+            assert!(result.is_ok());
+            assert!(called);
+            assert!(!inner.headers().contains_key("Access-Control-Allow-Origin"));
+        }
+
+        let (result, _, called) = run(
+            &spa_cors(),
+            "POST /todos HTTP/1.1\r\nHost: todo.example.com\r\nOrigin: https://todo.example.com\r\n\r\n",
+        )
+        .await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!called);
+    }
+
+    #[test]
+    #[should_panic(expected = "CORS credentials cannot be allowed for any origin")]
+    fn test_cors_rejects_credentials_for_any_origin() {
+        CorsMiddleware::new().with_credentials(true);
+    }
 }
@@ -0,0 +1,371 @@
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::random::{constant_time_eq, random_hex};
+use crate::request::Request;
+use crate::response::Response;
+use suika_json::JsonValue;
+
+/// Number of random bytes in a generated CSRF token.
+const TOKEN_BYTES: usize = 32;
+
+/// A middleware component providing CSRF (Cross-Site Request Forgery) protection.
+///
+/// The middleware implements the double-submit cookie pattern. Every response carries a
+/// random token in a cookie, and requests using an unsafe method (anything other than
+/// `GET`, `HEAD`, `OPTIONS` and `TRACE`) must echo that token back in a form field, a
+/// request header or a top-level field of a JSON body. Requests whose token is missing or
+/// does not match the cookie are rejected with `HttpError::Forbidden`.
+///
+/// The token is exposed to templates through the `csrf_token` and `csrf_field` template
+/// globals, so a form can include it with `<%= csrf_field %>`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::CsrfMiddleware;
+///
+/// let csrf = CsrfMiddleware::new()
+///     .with_cookie_name("my_csrf")
+///     .with_header_name("X-My-Csrf")
+///     .with_field_name("my_csrf");
+/// ```
+pub struct CsrfMiddleware {
+    cookie_name: String,
+    header_name: String,
+    field_name: String,
+    secure: bool,
+}
+
+impl CsrfMiddleware {
+    /// Creates a new `CsrfMiddleware` using the `csrf_token` cookie, the `X-CSRF-Token`
+    /// header and the `_csrf` form field.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::CsrfMiddleware;
+    ///
+    /// let csrf = CsrfMiddleware::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            cookie_name: "csrf_token".to_string(),
+            header_name: "X-CSRF-Token".to_string(),
+            field_name: "_csrf".to_string(),
+            secure: false,
+        }
+    }
+
+    /// Sets the name of the cookie holding the token.
+    pub fn with_cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// Sets the name of the request header checked for the token.
+    pub fn with_header_name(mut self, name: &str) -> Self {
+        self.header_name = name.to_string();
+        self
+    }
+
+    /// Sets the name of the form or JSON field checked for the token.
+    pub fn with_field_name(mut self, name: &str) -> Self {
+        self.field_name = name.to_string();
+        self
+    }
+
+    /// Marks the token cookie as `Secure`, restricting it to HTTPS connections.
+    pub fn with_secure_cookie(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Builds the `Set-Cookie` header value for a freshly issued token.
+    fn cookie_header(&self, token: &str) -> String {
+        let mut cookie = format!(
+            "{}={}; Path=/; SameSite=Strict; HttpOnly",
+            self.cookie_name, token
+        );
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        cookie
+    }
+
+    /// Finds the token submitted with the request, if any.
+    fn submitted_token<'r>(&self, req: &'r Request) -> Option<&'r str> {
+        if let Some(token) = req.header(&self.header_name) {
+            return Some(token);
+        }
+
+        if let Some(token) = req.form_data().and_then(|form| form.get(&self.field_name)) {
+            return Some(token);
+        }
+
+        match req.json_body() {
+            Some(JsonValue::Object(fields)) => fields.iter().find_map(|(key, value)| match value {
+                JsonValue::String(token) if key == &self.field_name => Some(token.as_str()),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Default for CsrfMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` if the method is considered safe and does not require a token.
+fn is_safe_method(method: &str) -> bool {
+    matches!(
+        method.to_uppercase().as_str(),
+        "GET" | "HEAD" | "OPTIONS" | "TRACE"
+    )
+}
+
+/// Returns `true` if the value looks like a token issued by this middleware.
+fn is_valid_token(token: &str) -> bool {
+    token.len() == TOKEN_BYTES * 2 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl Middleware for CsrfMiddleware {
+    /// Handles an incoming HTTP request by issuing a CSRF token and validating it on
+    /// unsafe methods.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let cookie_token = req
+                .cookie(&self.cookie_name)
+                .filter(|token| is_valid_token(token))
+                .map(|token| token.to_string());
+
+            let token = match &cookie_token {
+                Some(token) => token.clone(),
+                None => {
+                    let token = random_hex(TOKEN_BYTES);
+                    res.header("Set-Cookie", &self.cookie_header(&token)).await;
+                    token
+                }
+            };
+
+            res.set_template_global("csrf_token", token.as_str()).await;
+            res.set_template_global(
+                "csrf_field",
+                format!(
+                    "<input type=\"hidden\" name=\"{}\" value=\"{}\">",
+                    self.field_name, token
+                ),
+            )
+            .await;
+
+            if !is_safe_method(req.method()) {
+                let valid = match (&cookie_token, self.submitted_token(req)) {
+                    (Some(expected), Some(submitted)) => {
+                        constant_time_eq(expected.as_bytes(), submitted.as_bytes())
+                    }
+                    _ => false,
+                };
+
+                if !valid {
+                    return Err(HttpError::Forbidden(
+                        "Missing or invalid CSRF token".to_string(),
+                    ));
+                }
+            }
+
+            next.run(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use crate::request::Request;
+    use crate::response::Response;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    async fn run(request: &str) -> (Result<(), HttpError>, Response, bool) {
+        let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let csrf_middleware = CsrfMiddleware::new();
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = csrf_middleware.handle(&mut req, &mut res, next).await;
+        let next_called = *next_middleware.called.lock().await;
+        (result, res, next_called)
+    }
+
+    #[tokio::test]
+    async fn test_csrf_issues_token_on_safe_request() {
+        let (result, res, next_called) = run("GET /form HTTP/1.1\r\n\r\n").await;
+
+        assert!(result.is_ok());
+        assert!(next_called);
+
+        let inner = res.get_inner().await;
+        let cookie = inner.headers().get("Set-Cookie").unwrap();
+        assert!(cookie.starts_with("csrf_token="));
+        assert!(cookie.contains("SameSite=Strict"));
+
+        let token = res.template_global("csrf_token").await;
+        match token {
+            Some(JsonValue::String(token)) => {
+                assert!(is_valid_token(&token));
+                assert!(cookie.contains(&token));
+            }
+            _ => panic!("Expected csrf_token template global"),
+        }
+
+        match res.template_global("csrf_field").await {
+            Some(JsonValue::String(field)) => assert!(field.contains("name=\"_csrf\"")),
+            _ => panic!("Expected csrf_field template global"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_csrf_reuses_existing_cookie() {
+        let (result, res, _) = run(&format!(
+            "GET /form HTTP/1.1\r\nCookie: csrf_token={}\r\n\r\n",
+            TOKEN
+        ))
+        .await;
+
+        assert!(result.is_ok());
+        let inner = res.get_inner().await;
+        assert!(inner.headers().get("Set-Cookie").is_none());
+        assert_eq!(
+            res.template_global("csrf_token").await,
+            Some(JsonValue::String(TOKEN.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_csrf_rejects_missing_token() {
+        let (result, _, next_called) = run(&format!(
+            "POST /form HTTP/1.1\r\nCookie: csrf_token={}\r\n\r\n",
+            TOKEN
+        ))
+        .await;
+
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!next_called);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_rejects_missing_cookie() {
+        let (result, _, next_called) = run(&format!(
+            "POST /form HTTP/1.1\r\nX-CSRF-Token: {}\r\n\r\n",
+            TOKEN
+        ))
+        .await;
+
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!next_called);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_rejects_mismatched_token() {
+        let (result, _, next_called) = run(&format!(
+            "DELETE /form HTTP/1.1\r\nCookie: csrf_token={}\r\nX-CSRF-Token: {}\r\n\r\n",
+            TOKEN,
+            TOKEN.replace('0', "1")
+        ))
+        .await;
+
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!next_called);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_accepts_header_token() {
+        let (result, _, next_called) = run(&format!(
+            "PUT /form HTTP/1.1\r\nCookie: csrf_token={0}\r\nX-CSRF-Token: {0}\r\n\r\n",
+            TOKEN
+        ))
+        .await;
+
+        assert!(result.is_ok());
+        assert!(next_called);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_accepts_form_token() {
+        let (result, _, next_called) = run(&format!(
+            "POST /form HTTP/1.1\r\nCookie: csrf_token={0}\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\ntitle=hi&_csrf={0}",
+            TOKEN
+        ))
+        .await;
+
+        assert!(result.is_ok());
+        assert!(next_called);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_accepts_json_token() {
+        let (result, _, next_called) = run(&format!(
+            "POST /form HTTP/1.1\r\nCookie: csrf_token={0}\r\nContent-Type: application/json\r\n\r\n{{\"_csrf\":\"{0}\"}}",
+            TOKEN
+        ))
+        .await;
+
+        assert!(result.is_ok());
+        assert!(next_called);
+    }
+}
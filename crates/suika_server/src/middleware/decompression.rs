@@ -0,0 +1,295 @@
+use crate::encoding::{ContentEncoding, DecodeError};
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::Response;
+
+/// A middleware component that transparently decompresses request bodies.
+///
+/// Bodies sent with `Content-Encoding: gzip`, `deflate` or `br` are decompressed before
+/// the rest of the stack runs, and JSON and form bodies are parsed from the decompressed
+/// data. The `Content-Encoding` header is removed and `Content-Length` is updated.
+/// Multiple codings such as `gzip, deflate` are undone in reverse order.
+///
+/// Decompression stops once the body exceeds the size limit (10 MiB by default), which
+/// is answered with `413 Payload Too Large`. Unsupported encodings are answered with
+/// `415 Unsupported Media Type` and an `Accept-Encoding` header listing the supported
+/// ones, and corrupt data with `400 Bad Request`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::DecompressionMiddleware;
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(DecompressionMiddleware::new().with_max_size(1024 * 1024)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecompressionMiddleware {
+    max_size: usize,
+    encodings: Vec<ContentEncoding>,
+}
+
+impl DecompressionMiddleware {
+    /// Creates a new `DecompressionMiddleware` accepting gzip, deflate and brotli bodies
+    /// of up to 10 MiB.
+    pub fn new() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            encodings: vec![
+                ContentEncoding::Gzip,
+                ContentEncoding::Deflate,
+                ContentEncoding::Brotli,
+            ],
+        }
+    }
+
+    /// Sets the maximum size of a decompressed body in bytes.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets the accepted encodings.
+    pub fn with_encodings(mut self, encodings: &[ContentEncoding]) -> Self {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Returns the accepted encodings as an `Accept-Encoding` header value.
+    fn accept_encoding(&self) -> String {
+        self.encodings
+            .iter()
+            .map(|encoding| encoding.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Decompresses the request body in place.
+    fn decompress(&self, req: &mut Request, content_encoding: &str) -> Result<(), HttpError> {
+        let mut encodings = Vec::new();
+        for name in content_encoding.split(',').map(str::trim) {
+            if name.is_empty() || name.eq_ignore_ascii_case("identity") {
+                continue;
+            }
+            match ContentEncoding::from_name(name).filter(|e| self.encodings.contains(e)) {
+                Some(encoding) => encodings.push(encoding),
+                None => {
+                    return Err(HttpError::UnsupportedMediaType(format!(
+                        "Unsupported content encoding: {}",
+                        name
+                    )))
+                }
+            }
+        }
+
+        let mut body = req.body_bytes().to_vec();
+        for encoding in encodings.iter().rev() {
+            body = encoding.decode(&body, self.max_size).map_err(|e| match e {
+                DecodeError::TooLarge => {
+                    HttpError::PayloadTooLarge("Decompressed request body is too large".to_string())
+                }
+                DecodeError::Invalid(_) => {
+                    HttpError::BadRequest(format!("Invalid {} request body", encoding.name()))
+                }
+            })?;
+        }
+
+        req.remove_header("Content-Encoding");
+        req.set_header("Content-Length", &body.len().to_string());
+        req.set_body_bytes(body);
+        Ok(())
+    }
+}
+
+impl Default for DecompressionMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for DecompressionMiddleware {
+    /// Handles an incoming HTTP request by decompressing its body.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            if let Some(content_encoding) = req.header("Content-Encoding").map(str::to_string) {
+                if let Err(e) = self.decompress(req, &content_encoding) {
+                    if matches!(e, HttpError::UnsupportedMediaType(_)) {
+                        res.header("Accept-Encoding", &self.accept_encoding()).await;
+                    }
+                    return Err(e);
+                }
+            }
+            next.run(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use suika_json::JsonValue;
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    fn request(content_encoding: &str, body: &[u8]) -> Request {
+        let head = format!(
+            "POST /ingest HTTP/1.1\r\nContent-Type: application/json\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+            content_encoding,
+            body.len()
+        );
+        Request::from_bytes(
+            &[head.as_bytes(), body].concat(),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap()
+    }
+
+    async fn run(
+        decompression: &DecompressionMiddleware,
+        req: &mut Request,
+        res: &mut Response,
+    ) -> (Result<(), HttpError>, bool) {
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = decompression.handle(req, res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, called)
+    }
+
+    #[tokio::test]
+    async fn test_decompresses_json_body() {
+        let json = br#"{"event":"signup"}"#;
+        for encoding in ContentEncoding::ALL {
+            let mut req = request(encoding.name(), &encoding.encode(json).unwrap());
+            let mut res = Response::new(None);
+            assert!(req.json_body().is_none());
+
+            let (result, called) = run(&DecompressionMiddleware::new(), &mut req, &mut res).await;
+            assert!(result.is_ok());
+            assert!(called);
+            assert_eq!(req.body_bytes(), json);
+            assert_eq!(req.header("Content-Encoding"), None);
+            assert_eq!(req.header("Content-Length"), Some("18"));
+            assert_eq!(
+                req.json_body().and_then(|body| body.get("event")),
+                Some(&JsonValue::String("signup".to_string()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decompresses_stacked_encodings() {
+        let body = ContentEncoding::Gzip
+            .encode(&ContentEncoding::Deflate.encode(b"{}").unwrap())
+            .unwrap();
+        let mut req = request("deflate, gzip", &body);
+        let mut res = Response::new(None);
+
+        let (result, _) = run(&DecompressionMiddleware::new(), &mut req, &mut res).await;
+        assert!(result.is_ok());
+        assert_eq!(req.body(), Some("{}"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unsupported_encoding() {
+        let mut req = request("zstd", b"data");
+        let mut res = Response::new(None);
+
+        let decompression = DecompressionMiddleware::new().with_encodings(&[ContentEncoding::Gzip]);
+        let (result, called) = run(&decompression, &mut req, &mut res).await;
+        assert!(matches!(result, Err(HttpError::UnsupportedMediaType(_))));
+        assert!(!called);
+        assert_eq!(
+            res.get_header("Accept-Encoding").await,
+            Some("gzip".to_string())
+        );
+
+        let mut req = request("br", b"data");
+        let (result, _) = run(&decompression, &mut req, &mut res).await;
+        assert!(matches!(result, Err(HttpError::UnsupportedMediaType(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_and_corrupt_bodies() {
+        let bomb = ContentEncoding::Gzip.encode(&vec![b' '; 100_000]).unwrap();
+        let mut req = request("gzip", &bomb);
+        let mut res = Response::new(None);
+
+        let decompression = DecompressionMiddleware::new().with_max_size(10_000);
+        let (result, called) = run(&decompression, &mut req, &mut res).await;
+        assert!(matches!(result, Err(HttpError::PayloadTooLarge(_))));
+        assert!(!called);
+
+        let mut req = request("gzip", b"not gzip");
+        let (result, _) = run(&decompression, &mut req, &mut res).await;
+        assert!(matches!(result, Err(HttpError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_passes_through_uncompressed_bodies() {
+        let mut req = Request::new(
+            "POST / HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{}",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+
+        let (result, called) = run(&DecompressionMiddleware::new(), &mut req, &mut res).await;
+        assert!(result.is_ok());
+        assert!(called);
+        assert_eq!(req.body(), Some("{}"));
+    }
+}
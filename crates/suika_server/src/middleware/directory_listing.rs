@@ -0,0 +1,403 @@
+use crate::http_date::format_http_date;
+use std::cmp::Ordering;
+use std::io::Result as IoResult;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use suika_json::JsonValue;
+use suika_mime::get_mime_type_from_path;
+use suika_templates::context::Context;
+use suika_utils::percent_decode;
+
+/// An entry of a directory listing.
+#[derive(Debug, Clone)]
+pub(crate) struct DirectoryEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    mime_type: Option<String>,
+}
+
+/// The column a directory listing is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Type,
+}
+
+impl SortKey {
+    /// Parses the `sort` query parameter, defaulting to sorting by name.
+    pub(crate) fn from_query(value: Option<&str>) -> Self {
+        match value {
+            Some("size") => SortKey::Size,
+            Some("modified") | Some("mtime") => SortKey::Modified,
+            Some("type") => SortKey::Type,
+            _ => SortKey::Name,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Type => "type",
+        }
+    }
+}
+
+/// Reads the entries of a directory, skipping dotfiles unless `show_hidden` is set.
+pub(crate) async fn read_entries(dir: &Path, show_hidden: bool) -> IoResult<Vec<DirectoryEntry>> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if name.starts_with('.') && !show_hidden {
+            continue;
+        }
+        // Follows symbolic links; broken links are left out.
+        let metadata = match tokio::fs::metadata(entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let is_dir = metadata.is_dir();
+        entries.push(DirectoryEntry {
+            mime_type: (!is_dir).then(|| get_mime_type_from_path(&name)),
+            name,
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Sorts entries by a column, always listing directories before files.
+pub(crate) fn sort_entries(entries: &mut [DirectoryEntry], key: SortKey, descending: bool) {
+    entries.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Type => a.mime_type.cmp(&b.mime_type),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        b.is_dir.cmp(&a.is_dir).then(ordering)
+    });
+}
+
+/// Returns `true` if the `Accept` header prefers JSON over HTML.
+pub(crate) fn prefers_json(accept: Option<&str>) -> bool {
+    let mut html = 0.0;
+    let mut json = 0.0;
+    for media_range in accept.unwrap_or("").split(',') {
+        let mut parts = media_range.split(';');
+        let media_type = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "application/json" => json = quality,
+            "text/html" | "text/*" | "*/*" if quality > html => html = quality,
+            _ => {}
+        }
+    }
+    json > html
+}
+
+/// Builds a JSON listing with unescaped names, sizes and Unix modification times.
+pub(crate) fn to_json(request_path: &str, entries: &[DirectoryEntry]) -> JsonValue {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            JsonValue::Object(vec![
+                ("name".to_string(), entry.name.clone().into()),
+                ("is_dir".to_string(), entry.is_dir.into()),
+                (
+                    "size".to_string(),
+                    if entry.is_dir {
+                        JsonValue::Null
+                    } else {
+                        entry.size.into()
+                    },
+                ),
+                (
+                    "modified".to_string(),
+                    entry
+                        .modified
+                        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                        .map_or(JsonValue::Null, |d| d.as_secs().into()),
+                ),
+                (
+                    "mime_type".to_string(),
+                    entry
+                        .mime_type
+                        .clone()
+                        .map_or(JsonValue::Null, JsonValue::from),
+                ),
+            ])
+        })
+        .collect();
+    JsonValue::Object(vec![
+        (
+            "path".to_string(),
+            decode_path(&base_path(request_path)).into(),
+        ),
+        ("entries".to_string(), JsonValue::Array(entries)),
+    ])
+}
+
+/// Builds the template context for an HTML listing.
+///
+/// Templates do not escape values, so names and links are HTML-escaped here.
+pub(crate) fn to_context(
+    request_path: &str,
+    entries: &[DirectoryEntry],
+    sort: SortKey,
+    descending: bool,
+    at_root: bool,
+) -> Context {
+    let base = base_path(request_path);
+    let rows: Vec<JsonValue> = entries
+        .iter()
+        .map(|entry| {
+            let suffix = if entry.is_dir { "/" } else { "" };
+            JsonValue::Object(vec![
+                (
+                    "name".to_string(),
+                    escape_html(&format!("{}{}", entry.name, suffix)).into(),
+                ),
+                (
+                    "href".to_string(),
+                    escape_html(&format!(
+                        "{}{}{}",
+                        base,
+                        encode_segment(&entry.name),
+                        suffix
+                    ))
+                    .into(),
+                ),
+                ("is_dir".to_string(), entry.is_dir.into()),
+                (
+                    "size".to_string(),
+                    if entry.is_dir {
+                        "-".to_string()
+                    } else {
+                        entry.size.to_string()
+                    }
+                    .into(),
+                ),
+                (
+                    "modified".to_string(),
+                    entry
+                        .modified
+                        .map_or("-".to_string(), format_http_date)
+                        .into(),
+                ),
+                (
+                    "mime_type".to_string(),
+                    escape_html(entry.mime_type.as_deref().unwrap_or("-")).into(),
+                ),
+            ])
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("path", escape_html(&decode_path(&base)));
+    context.insert("entries", JsonValue::Array(rows));
+    context.insert("sort", sort.name());
+    context.insert("order", if descending { "desc" } else { "asc" });
+    if !at_root && base != "/" {
+        let parent = base[..base.len() - 1]
+            .rfind('/')
+            .map_or("/", |i| &base[..=i]);
+        context.insert("parent", escape_html(parent));
+    }
+    context
+}
+
+/// Renders the built-in HTML listing from a context built by `to_context`.
+pub(crate) fn render_html(context: &Context) -> String {
+    let text = |key: &str| {
+        context
+            .get(key)
+            .and_then(JsonValue::as_str)
+            .unwrap_or("")
+            .to_string()
+    };
+    let path = text("path");
+    let sort = text("sort");
+    let order = text("order");
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {path}</title>\n</head>\n<body>\n<h1>Index of {path}</h1>\n<table>\n<tr>"
+    );
+    for (key, label) in [
+        ("name", "Name"),
+        ("size", "Size"),
+        ("modified", "Last modified"),
+        ("type", "Type"),
+    ] {
+        let next_order = if sort == key && order == "asc" {
+            "desc"
+        } else {
+            "asc"
+        };
+        html.push_str(&format!(
+            "<th><a href=\"?sort={key}&amp;order={next_order}\">{label}</a></th>"
+        ));
+    }
+    html.push_str("</tr>\n");
+
+    if let Some(parent) = context.get("parent").and_then(JsonValue::as_str) {
+        html.push_str(&format!(
+            "<tr><td><a href=\"{parent}\">../</a></td><td>-</td><td>-</td><td>-</td></tr>\n"
+        ));
+    }
+    for entry in context
+        .get("entries")
+        .and_then(JsonValue::as_array)
+        .unwrap_or(&[])
+    {
+        let field = |key: &str| entry.get(key).and_then(JsonValue::as_str).unwrap_or("");
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            field("href"),
+            field("name"),
+            field("size"),
+            field("modified"),
+            field("mime_type")
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Returns the request path with a trailing slash.
+fn base_path(request_path: &str) -> String {
+    if request_path.ends_with('/') {
+        request_path.to_string()
+    } else {
+        format!("{}/", request_path)
+    }
+}
+
+/// Decodes a request path for display, keeping it as is if it is not valid.
+fn decode_path(path: &str) -> String {
+    percent_decode(path).unwrap_or_else(|_| path.to_string())
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a file name for use as a URL path segment.
+fn encode_segment(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: u64, secs: u64) -> DirectoryEntry {
+        DirectoryEntry {
+            name: name.to_string(),
+            is_dir,
+            size,
+            modified: Some(UNIX_EPOCH + std::time::Duration::from_secs(secs)),
+            mime_type: (!is_dir).then(|| get_mime_type_from_path(name)),
+        }
+    }
+
+    fn names(entries: &[DirectoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_entries() {
+        let mut entries = vec![
+            entry("b.txt", false, 30, 3),
+            entry("docs", true, 0, 1),
+            entry("a.png", false, 10, 2),
+            entry("c.css", false, 20, 1),
+        ];
+
+        sort_entries(&mut entries, SortKey::Name, false);
+        assert_eq!(names(&entries), ["docs", "a.png", "b.txt", "c.css"]);
+
+        sort_entries(&mut entries, SortKey::Size, true);
+        assert_eq!(names(&entries), ["docs", "b.txt", "c.css", "a.png"]);
+
+        sort_entries(&mut entries, SortKey::Modified, false);
+        assert_eq!(names(&entries), ["docs", "c.css", "a.png", "b.txt"]);
+
+        sort_entries(&mut entries, SortKey::Type, false);
+        assert_eq!(names(&entries), ["docs", "a.png", "c.css", "b.txt"]);
+    }
+
+    #[test]
+    fn test_prefers_json() {
+        assert!(prefers_json(Some("application/json")));
+        assert!(prefers_json(Some("text/html;q=0.5, application/json")));
+        assert!(!prefers_json(Some("text/html, application/json;q=0.9")));
+        assert!(!prefers_json(Some("*/*")));
+        assert!(!prefers_json(None));
+    }
+
+    #[test]
+    fn test_context_escapes_names() {
+        let entries = vec![entry("<x> & y.txt", false, 1, 0)];
+        let context = to_context("/files/a%20b", &entries, SortKey::Name, false, false);
+        let row = &context.get("entries").unwrap().as_array().unwrap()[0];
+        assert_eq!(
+            row.get("name").and_then(JsonValue::as_str),
+            Some("&lt;x&gt; &amp; y.txt")
+        );
+        assert_eq!(
+            row.get("href").and_then(JsonValue::as_str),
+            Some("/files/a%20b/%3Cx%3E%20%26%20y.txt")
+        );
+        assert_eq!(
+            context.get("parent").and_then(JsonValue::as_str),
+            Some("/files/")
+        );
+
+        let html = render_html(&context);
+        assert!(html.contains("<title>Index of /files/a b/</title>"));
+        assert!(html
+            .contains("<a href=\"/files/a%20b/%3Cx%3E%20%26%20y.txt\">&lt;x&gt; &amp; y.txt</a>"));
+        assert!(!html.contains("<x>"));
+    }
+}
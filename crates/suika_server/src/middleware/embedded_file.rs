@@ -0,0 +1,450 @@
+use crate::conditional::respond;
+use crate::embed::{EmbeddedAssets, EmbeddedFile};
+use crate::encoding::negotiate;
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next, StaticFileMiddleware};
+use crate::request::Request;
+use crate::response::Response;
+use std::collections::HashMap;
+use std::path::Path;
+use suika_utils::percent_decode;
+
+/// The outcome of mapping a request path to an embedded file.
+enum Resolved {
+    File(EmbeddedFile),
+    Redirect(String),
+    NotFound,
+    Forbidden,
+}
+
+/// A middleware component for serving assets compiled into the binary.
+///
+/// Assets are embedded by an `Embedder` in a build script and included with the
+/// `include_assets!` macro. They are served like `StaticFileMiddleware` serves files:
+/// with a MIME type derived from the extension, the strong `ETag` computed at build
+/// time, `Last-Modified`, conditional and `Range` requests, `index.html` for
+/// directories with trailing-slash redirects, and per-extension `Cache-Control`.
+/// Precompressed variants are served when the client accepts them, with
+/// `Content-Encoding` and `Vary: Accept-Encoding`.
+///
+/// In dev mode, files are read from the directory they were embedded from instead, so
+/// changes show up without rebuilding.
+///
+/// # Examples
+///
+/// ```ignore
+/// use suika_server::embed::EmbeddedAssets;
+/// use suika_server::middleware::EmbeddedFileMiddleware;
+/// use std::sync::Arc;
+///
+/// static ASSETS: EmbeddedAssets = suika_server::include_assets!("assets.rs");
+///
+/// server.use_middleware(Arc::new(
+///     EmbeddedFileMiddleware::new("/static", ASSETS, 3600)
+///         .with_dev_mode(cfg!(debug_assertions)),
+/// ));
+/// ```
+pub struct EmbeddedFileMiddleware {
+    url_prefix: String,
+    assets: EmbeddedAssets,
+    files: HashMap<&'static str, EmbeddedFile>,
+    cache_duration: u64,
+    index: Option<String>,
+    redirect: bool,
+    fallthrough: bool,
+    cache_control: HashMap<String, String>,
+    dev_mode: bool,
+}
+
+impl EmbeddedFileMiddleware {
+    /// Creates a new `EmbeddedFileMiddleware`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url_prefix` - The URL prefix for serving the assets.
+    /// * `assets` - The assets created with `include_assets!`.
+    /// * `cache_duration` - The cache duration in seconds.
+    pub fn new(url_prefix: &str, assets: EmbeddedAssets, cache_duration: u64) -> Self {
+        Self {
+            url_prefix: url_prefix.trim_end_matches('/').to_string(),
+            assets,
+            files: assets.iter().map(|file| (file.path(), file)).collect(),
+            cache_duration,
+            index: Some("index.html".to_string()),
+            redirect: true,
+            fallthrough: true,
+            cache_control: HashMap::new(),
+            dev_mode: false,
+        }
+    }
+
+    /// Sets the file served for directory requests, or `None` to serve no index.
+    /// Defaults to `index.html`.
+    pub fn with_index(mut self, index: Option<&str>) -> Self {
+        self.index = index.map(str::to_string);
+        self
+    }
+
+    /// Sets whether requests for a directory without a trailing slash are redirected
+    /// with `301 Moved Permanently`. When disabled, the index file is served directly.
+    pub fn with_redirect(mut self, redirect: bool) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// Sets whether requests for missing files are passed to the next middleware.
+    /// When disabled, they are answered with `404 Not Found`.
+    pub fn with_fallthrough(mut self, fallthrough: bool) -> Self {
+        self.fallthrough = fallthrough;
+        self
+    }
+
+    /// Sets the `Cache-Control` header for files with the given extension, overriding
+    /// the `public, max-age` value derived from the cache duration.
+    pub fn with_cache_control(mut self, extension: &str, cache_control: &str) -> Self {
+        self.cache_control.insert(
+            extension.trim_start_matches('.').to_ascii_lowercase(),
+            cache_control.to_string(),
+        );
+        self
+    }
+
+    /// Sets whether files are read from the directory the assets were embedded from,
+    /// using a `StaticFileMiddleware` with the same options.
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.dev_mode = dev_mode;
+        self
+    }
+
+    /// Returns a `StaticFileMiddleware` serving the source directory for dev mode.
+    fn dev_middleware(&self) -> StaticFileMiddleware {
+        let mut middleware = StaticFileMiddleware::new(
+            &self.url_prefix,
+            self.assets.source_dir(),
+            self.cache_duration,
+        )
+        .with_content_etags(true)
+        .with_index(self.index.as_deref())
+        .with_redirect(self.redirect)
+        .with_fallthrough(self.fallthrough);
+        for (extension, cache_control) in &self.cache_control {
+            middleware = middleware.with_cache_control(extension, cache_control);
+        }
+        middleware
+    }
+
+    /// Returns the `Cache-Control` header value for a file.
+    fn cache_control_for(&self, path: &str) -> String {
+        Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.cache_control.get(&ext.to_ascii_lowercase()))
+            .cloned()
+            .unwrap_or_else(|| format!("public, max-age={}", self.cache_duration))
+    }
+
+    /// Maps the part of a request path after the URL prefix to an embedded file.
+    fn resolve(&self, relative: &str, request_path: &str) -> Resolved {
+        let decoded = match percent_decode(relative) {
+            Ok(decoded) => decoded,
+            Err(_) => return Resolved::NotFound,
+        };
+
+        let mut path = String::new();
+        for segment in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if segment == ".." || segment.contains('\\') {
+                return Resolved::Forbidden;
+            }
+            path.push('/');
+            path.push_str(segment);
+        }
+
+        if !decoded.ends_with('/') {
+            if let Some(file) = self.files.get(path.as_str()) {
+                return Resolved::File(*file);
+            }
+        }
+
+        let index = match &self.index {
+            Some(index) => format!("{}/{}", path, index),
+            None => return Resolved::NotFound,
+        };
+        match self.files.get(index.as_str()) {
+            Some(_) if self.redirect && !request_path.ends_with('/') => {
+                Resolved::Redirect(format!("{}/", request_path))
+            }
+            Some(file) => Resolved::File(*file),
+            None => Resolved::NotFound,
+        }
+    }
+
+    /// Sends an embedded file, choosing a precompressed variant if one is accepted.
+    async fn send(&self, file: EmbeddedFile, req: &Request, res: &mut Response) {
+        let encodings = file.encodings();
+        let variant = req
+            .header("Accept-Encoding")
+            .and_then(|accept_encoding| negotiate(accept_encoding, &encodings))
+            .and_then(|encoding| file.variant(encoding).map(|variant| (encoding, variant)));
+        if !encodings.is_empty() {
+            res.append_header("Vary", "Accept-Encoding").await;
+        }
+
+        let (contents, etag) = match &variant {
+            Some((_, (contents, etag))) => (*contents, etag.clone()),
+            None => (file.contents(), file.etag()),
+        };
+        res.etag(&etag).await;
+        if let Some(modified) = file.modified() {
+            res.last_modified(modified).await;
+        }
+
+        res.send_bytes_ranged(req, contents, &file.mime_type())
+            .await;
+        res.header("Cache-Control", &self.cache_control_for(file.path()))
+            .await;
+        if let Some((encoding, _)) = variant {
+            res.header("Content-Encoding", encoding.name()).await;
+        }
+    }
+}
+
+impl Middleware for EmbeddedFileMiddleware {
+    /// Handles an incoming HTTP request by serving an embedded file if the request path matches the URL prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            if self.dev_mode {
+                return self.dev_middleware().handle(req, res, next).await;
+            }
+
+            let relative = match req.path().strip_prefix(&self.url_prefix) {
+                Some(relative)
+                    if (relative.is_empty() || relative.starts_with('/'))
+                        && matches!(req.method(), "GET" | "HEAD") =>
+                {
+                    relative.to_string()
+                }
+                _ => return next.run(req, res).await,
+            };
+
+            match self.resolve(&relative, req.original_path()) {
+                Resolved::File(file) => {
+                    self.send(file, req, res).await;
+                    respond(req, res).await
+                }
+                Resolved::Redirect(location) => {
+                    res.set_status(301).await;
+                    res.header("Location", &location).await;
+                    res.body(format!("Redirecting to {}", location)).await;
+                    Ok(())
+                }
+                Resolved::Forbidden => Err(HttpError::Forbidden("Forbidden".to_string())),
+                Resolved::NotFound if self.fallthrough => next.run(req, res).await,
+                Resolved::NotFound => Err(HttpError::NotFound("File not found".to_string())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditional::ETag;
+    use crate::embed::EmbeddedEntry;
+    use crate::middleware::{Middleware, Next};
+    use crate::response::Body;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Mutex as TokioMutex;
+
+    static ENTRIES: &[EmbeddedEntry] = &[
+        (
+            "/app.js",
+            b"console.log('embedded');",
+            "\"app\"",
+            Some(1_000_000_000),
+            &[("br", b"compressed", "\"app-br\"")],
+        ),
+        ("/docs/index.html", b"<h1>Docs</h1>", "\"docs\"", None, &[]),
+        ("/index.html", b"<h1>Home</h1>", "\"home\"", None, &[]),
+    ];
+
+    fn assets() -> EmbeddedAssets {
+        EmbeddedAssets::from_entries(("/nonexistent", ENTRIES))
+    }
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    async fn serve(
+        middleware: &EmbeddedFileMiddleware,
+        path: &str,
+        headers: &str,
+    ) -> (Result<(), HttpError>, Response, bool) {
+        let mut req = Request::new(
+            &format!("GET {} HTTP/1.1\r\n{}\r\n", path, headers),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = middleware.handle(&mut req, &mut res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, res, called)
+    }
+
+    #[tokio::test]
+    async fn test_serves_embedded_files() {
+        let middleware = EmbeddedFileMiddleware::new("/static", assets(), 60)
+            .with_cache_control("html", "no-cache");
+
+        let (result, res, called) = serve(&middleware, "/static/app.js", "").await;
+        assert!(result.is_ok());
+        assert!(!called);
+        assert_eq!(res.status().await, Some(200));
+        assert_eq!(
+            res.get_header("Content-Type").await,
+            Some("application/javascript".to_string())
+        );
+        assert_eq!(res.get_header("ETag").await, Some("\"app\"".to_string()));
+        assert_eq!(
+            res.get_header("Last-Modified").await,
+            Some("Sun, 09 Sep 2001 01:46:40 GMT".to_string())
+        );
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("public, max-age=60".to_string())
+        );
+        assert_eq!(res.get_header("Content-Encoding").await, None);
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Binary(b"console.log('embedded');".to_vec()))
+        );
+
+        let (_, res, _) = serve(&middleware, "/static/app.js", "If-None-Match: \"app\"\r\n").await;
+        assert_eq!(res.status().await, Some(304));
+
+        let (_, res, _) = serve(&middleware, "/static/app.js", "Range: bytes=0-6\r\n").await;
+        assert_eq!(res.status().await, Some(206));
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Binary(b"console".to_vec()))
+        );
+
+        let (_, _, called) = serve(&middleware, "/static/missing.js", "").await;
+        assert!(called);
+    }
+
+    #[tokio::test]
+    async fn test_serves_precompressed_variants() {
+        let middleware = EmbeddedFileMiddleware::new("/static", assets(), 60);
+        let (_, res, _) = serve(
+            &middleware,
+            "/static/app.js",
+            "Accept-Encoding: gzip, br\r\n",
+        )
+        .await;
+        assert_eq!(
+            res.get_header("Content-Encoding").await,
+            Some("br".to_string())
+        );
+        assert_eq!(
+            res.get_header("Vary").await,
+            Some("Accept-Encoding".to_string())
+        );
+        assert_eq!(
+            res.get_header("ETag").await,
+            Some(ETag::strong("app-br").to_string())
+        );
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Binary(b"compressed".to_vec()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_files_and_redirects() {
+        let middleware = EmbeddedFileMiddleware::new("/static", assets(), 60);
+
+        let (_, res, _) = serve(&middleware, "/static/", "").await;
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Binary(b"<h1>Home</h1>".to_vec()))
+        );
+
+        let (_, res, _) = serve(&middleware, "/static/docs", "").await;
+        assert_eq!(res.status().await, Some(301));
+        assert_eq!(
+            res.get_header("Location").await,
+            Some("/static/docs/".to_string())
+        );
+
+        let (result, _, _) = serve(&middleware, "/static/%2e%2e/secret", "").await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+
+        let strict = EmbeddedFileMiddleware::new("/static", assets(), 60).with_fallthrough(false);
+        let (result, _, called) = serve(&strict, "/static/app.js/", "").await;
+        assert!(matches!(result, Err(HttpError::NotFound(_))));
+        assert!(!called);
+    }
+
+    #[tokio::test]
+    async fn test_dev_mode_reads_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("app.js"), "console.log('disk');").unwrap();
+        let source_dir: &'static str =
+            Box::leak(dir.path().to_str().unwrap().to_string().into_boxed_str());
+        let assets = EmbeddedAssets::from_entries((source_dir, ENTRIES));
+
+        let middleware = EmbeddedFileMiddleware::new("/static", assets, 60).with_dev_mode(true);
+        let (_, res, _) = serve(&middleware, "/static/app.js", "").await;
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"console.log('disk');"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::diagnostics::log_warn;
 use crate::error::HttpError;
 use crate::middleware::{Middleware, MiddlewareFuture, Next};
 use crate::request::Request;
@@ -13,12 +14,12 @@ fn map_io_error(err: std::io::Error) -> HttpError {
 
 async fn handle_favicon_request(path: PathBuf, res: &mut Response) -> Result<(), HttpError> {
     let absolute_path = path.canonicalize().map_err(|e| {
-        println!("Failed to get absolute path: {:?}", e);
+        log_warn!(path = %path.display(), error = %e, "Favicon not found");
         HttpError::NotFound("Favicon not found".to_string())
     })?;
 
     if !absolute_path.exists() {
-        println!("File does not exist: {:?}", absolute_path);
+        log_warn!(path = %absolute_path.display(), "Favicon does not exist");
         res.set_status(404).await;
         res.body("Favicon not found".to_string()).await;
         return Err(HttpError::NotFound("Favicon not found".to_string()));
@@ -0,0 +1,354 @@
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::proxy::Cidr;
+use crate::request::Request;
+use crate::response::Response;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use suika_utils::percent_decode;
+
+/// A set of allowed and denied CIDR ranges.
+///
+/// Denied ranges take precedence. If any allowed ranges are configured, only addresses
+/// inside them are allowed; otherwise every address that is not denied is allowed.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::ip_filter::IpRules;
+///
+/// let rules = IpRules::new()
+///     .with_allow("10.0.0.0/8").unwrap()
+///     .with_deny("10.0.0.13").unwrap();
+///
+/// assert!(rules.is_allowed("10.1.2.3".parse().unwrap()));
+/// assert!(!rules.is_allowed("10.0.0.13".parse().unwrap()));
+/// assert!(!rules.is_allowed("192.168.0.1".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IpRules {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpRules {
+    /// Creates an empty rule set that allows every address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an allowed address or CIDR range.
+    pub fn with_allow(mut self, cidr: &str) -> Result<Self, String> {
+        self.allow.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    /// Adds a denied address or CIDR range.
+    pub fn with_deny(mut self, cidr: &str) -> Result<Self, String> {
+        self.deny.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    /// Returns `true` if `addr` is allowed by the rules.
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        if self.deny.iter().any(|cidr| cidr.contains(addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(addr))
+    }
+}
+
+/// A middleware component that allows or denies requests by client IP address.
+///
+/// The client IP is taken from `Request::client_ip`, so it honours the server's trusted
+/// proxy configuration. Rejected requests fail with `HttpError::Forbidden`, as do
+/// requests without a known client address when an allow list is configured.
+///
+/// The middleware can be limited to a path prefix, or added to a `Router` with
+/// `Router::use_middleware`. Its rules can be replaced at runtime with `reload`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::ip_filter::{IpFilterMiddleware, IpRules};
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let rules = IpRules::new().with_allow("10.0.0.0/8").unwrap();
+/// let filter = Arc::new(IpFilterMiddleware::new(rules).with_path_prefix("/admin"));
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(filter.clone());
+///
+/// // Later, e.g. after the configuration file changed:
+/// filter.reload(IpRules::new().with_allow("10.1.0.0/16").unwrap());
+/// ```
+#[derive(Debug)]
+pub struct IpFilterMiddleware {
+    rules: RwLock<IpRules>,
+    path_prefix: Option<String>,
+}
+
+impl IpFilterMiddleware {
+    /// Creates a new `IpFilterMiddleware` applying `rules` to every request.
+    pub fn new(rules: IpRules) -> Self {
+        Self {
+            rules: RwLock::new(rules),
+            path_prefix: None,
+        }
+    }
+
+    /// Applies the rules only to requests for `prefix` and the paths below it.
+    ///
+    /// Paths are percent-decoded and normalized before matching, so `/%61dmin` and
+    /// `//admin` are filtered like `/admin`, while `/administrator` is not.
+    pub fn with_path_prefix(mut self, prefix: &str) -> Self {
+        self.path_prefix = Some(normalize_path(prefix));
+        self
+    }
+
+    /// Replaces the rules used for subsequent requests.
+    pub fn reload(&self, rules: IpRules) {
+        *self.rules.write().unwrap() = rules;
+    }
+
+    /// Returns a copy of the current rules.
+    pub fn rules(&self) -> IpRules {
+        self.rules.read().unwrap().clone()
+    }
+
+    /// Returns `true` if the rules apply to the given path.
+    fn applies_to(&self, path: &str) -> bool {
+        let prefix = match &self.path_prefix {
+            Some(prefix) => prefix,
+            None => return true,
+        };
+        // Paths that cannot be decoded are filtered rather than let through.
+        let path = match percent_decode(path) {
+            Ok(decoded) => normalize_path(&decoded),
+            Err(_) => return true,
+        };
+        path.strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Returns `true` if the request is allowed.
+    fn is_allowed(&self, req: &Request) -> bool {
+        let rules = self.rules.read().unwrap();
+        match req.client_ip() {
+            Some(ip) => rules.is_allowed(ip),
+            None => rules.allow.is_empty(),
+        }
+    }
+}
+
+/// Collapses repeated slashes and resolves `.` and `..` segments in a path, returning
+/// it without a trailing slash (the root path becomes empty).
+fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments
+        .iter()
+        .map(|segment| format!("/{}", segment))
+        .collect()
+}
+
+impl Middleware for IpFilterMiddleware {
+    /// Handles an incoming HTTP request by checking its client address against the rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            if self.applies_to(req.path()) && !self.is_allowed(req) {
+                return Err(HttpError::Forbidden("Access denied".to_string()));
+            }
+            next.run(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    async fn run(
+        filter: &IpFilterMiddleware,
+        path: &str,
+        peer: Option<&str>,
+    ) -> (Result<(), HttpError>, bool) {
+        let mut req = Request::new(
+            &format!("GET {} HTTP/1.1\r\n\r\n", path),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        if let Some(peer) = peer {
+            req.set_peer_addr(peer.parse().unwrap());
+        }
+        let mut res = Response::new(None);
+
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = filter.handle(&mut req, &mut res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, called)
+    }
+
+    #[test]
+    fn test_ip_rules() {
+        let rules = IpRules::new()
+            .with_allow("2001:db8::/32")
+            .unwrap()
+            .with_allow("192.168.0.0/16")
+            .unwrap()
+            .with_deny("192.168.1.0/24")
+            .unwrap();
+
+        assert!(rules.is_allowed("2001:db8::1".parse().unwrap()));
+        assert!(rules.is_allowed("192.168.2.1".parse().unwrap()));
+        assert!(!rules.is_allowed("192.168.1.1".parse().unwrap()));
+        assert!(!rules.is_allowed("8.8.8.8".parse().unwrap()));
+
+        let deny_only = IpRules::new().with_deny("8.8.8.8").unwrap();
+        assert!(deny_only.is_allowed("1.1.1.1".parse().unwrap()));
+        assert!(!deny_only.is_allowed("8.8.8.8".parse().unwrap()));
+
+        assert!(IpRules::new().with_allow("nope").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter_allows_and_denies() {
+        let filter = IpFilterMiddleware::new(IpRules::new().with_allow("10.0.0.0/8").unwrap());
+
+        let (result, called) = run(&filter, "/", Some("10.1.1.1:5000")).await;
+        assert!(result.is_ok());
+        assert!(called);
+
+        let (result, called) = run(&filter, "/", Some("192.168.0.1:5000")).await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!called);
+
+        let (result, _) = run(&filter, "/", None).await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter_path_prefix() {
+        let filter = IpFilterMiddleware::new(IpRules::new().with_allow("127.0.0.1").unwrap())
+            .with_path_prefix("/admin");
+
+        let (result, _) = run(&filter, "/admin/users", Some("203.0.113.7:5000")).await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+
+        let (result, _) = run(&filter, "/admin/users", Some("127.0.0.1:5000")).await;
+        assert!(result.is_ok());
+
+        let (result, called) = run(&filter, "/public", Some("203.0.113.7:5000")).await;
+        assert!(result.is_ok());
+        assert!(called);
+
+        let (result, _) = run(&filter, "/admin", Some("203.0.113.7:5000")).await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        let (result, called) = run(&filter, "/administrator", Some("203.0.113.7:5000")).await;
+        assert!(result.is_ok());
+        assert!(called);
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter_path_prefix_bypasses() {
+        let filter = IpFilterMiddleware::new(IpRules::new().with_allow("127.0.0.1").unwrap())
+            .with_path_prefix("/admin/");
+
+        for path in [
+            "/%61dmin/users",
+            "//admin/users",
+            "/./admin",
+            "/public/../admin/users",
+            "/admin%2Fusers",
+            "/%zz",
+        ] {
+            let (result, called) = run(&filter, path, Some("203.0.113.7:5000")).await;
+            assert!(matches!(result, Err(HttpError::Forbidden(_))), "{}", path);
+            assert!(!called, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("//admin///users/"), "/admin/users");
+        assert_eq!(normalize_path("/a/./b/../c"), "/a/c");
+        assert_eq!(normalize_path("/../.."), "");
+        assert_eq!(normalize_path("/"), "");
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter_reload() {
+        let filter = IpFilterMiddleware::new(IpRules::new());
+        assert!(run(&filter, "/", Some("203.0.113.7:5000")).await.0.is_ok());
+
+        filter.reload(IpRules::new().with_deny("203.0.113.0/24").unwrap());
+        assert!(run(&filter, "/", Some("203.0.113.7:5000")).await.0.is_err());
+        assert!(run(&filter, "/", Some("198.51.100.1:5000")).await.0.is_ok());
+        assert_eq!(
+            filter.rules(),
+            IpRules::new().with_deny("203.0.113.0/24").unwrap()
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use crate::auth::Principal;
+use crate::error::HttpError;
+use crate::jwt::{decode, JwkSet, JwtError, Validation};
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::Response;
+use suika_json::JsonValue;
+
+/// A middleware component that authenticates requests carrying a JSON Web Token.
+///
+/// The token is read from a `Bearer` `Authorization` header and verified against the
+/// configured keys (HS256 or RS256) and validation rules. On success the claims are
+/// attached to the request (see `Request::claims`) together with a `Principal` built from
+/// them:
+///
+/// * `sub` becomes the principal id.
+/// * `roles` (an array of strings) becomes the principal's roles.
+/// * `permissions` (an array of strings) or `scope` (a space separated string) becomes the
+///   principal's permissions.
+///
+/// Requests without a valid token are rejected with `HttpError::Unauthorized` and a
+/// `WWW-Authenticate: Bearer` challenge with a fixed description of the failure, such as
+/// `token expired`. Details from the token parser, which can contain bytes chosen by
+/// the client, are only included in the response body.
+///
+/// # Examples
+///
+/// ```no_run
+/// use suika_server::jwt::{JwkSet, Validation};
+/// use suika_server::middleware::JwtMiddleware;
+/// use suika_server::router::Router;
+/// use std::sync::Arc;
+///
+/// let keys = JwkSet::from_file("jwks.json").expect("Failed to load JWKS");
+/// let validation = Validation::new()
+///     .with_issuer("https://auth.example.com")
+///     .with_audience("my-api");
+///
+/// let mut api = Router::new("/api");
+/// api.use_middleware(Arc::new(JwtMiddleware::new(keys, validation)));
+/// ```
+pub struct JwtMiddleware {
+    keys: JwkSet,
+    validation: Validation,
+    realm: Option<String>,
+}
+
+impl JwtMiddleware {
+    /// Creates a new `JwtMiddleware`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys used to verify token signatures.
+    /// * `validation` - The rules applied to the token claims.
+    pub fn new(keys: JwkSet, validation: Validation) -> Self {
+        Self {
+            keys,
+            validation,
+            realm: None,
+        }
+    }
+
+    /// Sets the realm reported in `WWW-Authenticate` challenges.
+    pub fn with_realm(mut self, realm: &str) -> Self {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Builds the `WWW-Authenticate` header value for a rejected request.
+    fn challenge(&self, error: Option<&JwtError>) -> String {
+        let mut params = Vec::new();
+        if let Some(realm) = &self.realm {
+            params.push(format!("realm=\"{}\"", realm));
+        }
+        if let Some(error) = error {
+            params.push("error=\"invalid_token\"".to_string());
+            params.push(format!(
+                "error_description=\"{}\"",
+                error_description(error)
+            ));
+        }
+
+        if params.is_empty() {
+            "Bearer".to_string()
+        } else {
+            format!("Bearer {}", params.join(", "))
+        }
+    }
+}
+
+/// Returns a fixed description of a token error that is safe to send in a header.
+fn error_description(error: &JwtError) -> &'static str {
+    match error {
+        JwtError::Malformed(_) => "malformed token",
+        JwtError::UnsupportedAlgorithm(_) => "unsupported algorithm",
+        JwtError::UnknownKey => "unknown key",
+        JwtError::InvalidSignature => "invalid signature",
+        JwtError::Expired => "token expired",
+        JwtError::NotYetValid => "token not yet valid",
+        JwtError::MissingClaim(_) => "missing claim",
+        JwtError::InvalidIssuer => "invalid issuer",
+        JwtError::InvalidAudience => "invalid audience",
+        JwtError::InvalidKey(_) => "invalid key",
+    }
+}
+
+/// Builds a principal from verified token claims.
+fn principal_from_claims(claims: &JsonValue) -> Principal {
+    let strings = |name: &str| -> Vec<String> {
+        claims
+            .get(name)
+            .and_then(JsonValue::as_array)
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(JsonValue::as_str)
+                    .map(|value| value.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut permissions = strings("permissions");
+    if let Some(scope) = claims.get("scope").and_then(JsonValue::as_str) {
+        permissions.extend(scope.split_whitespace().map(|s| s.to_string()));
+    }
+
+    Principal::new(claims.get("sub").and_then(JsonValue::as_str).unwrap_or(""))
+        .with_roles(strings("roles"))
+        .with_permissions(permissions)
+}
+
+impl Middleware for JwtMiddleware {
+    /// Handles an incoming HTTP request by verifying its bearer token before passing it on.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let token = req
+                .header("Authorization")
+                .and_then(|value| value.trim().split_once(' '))
+                .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+                .map(|(_, token)| token.trim().to_string());
+
+            let token = match token {
+                Some(token) => token,
+                None => {
+                    res.header("WWW-Authenticate", &self.challenge(None)).await;
+                    return Err(HttpError::Unauthorized(
+                        "Authentication required".to_string(),
+                    ));
+                }
+            };
+
+            match decode(&token, &self.keys, &self.validation) {
+                Ok(claims) => {
+                    req.set_principal(principal_from_claims(&claims));
+                    req.set_claims(claims);
+                    next.run(req, res).await
+                }
+                Err(error) => {
+                    res.header("WWW-Authenticate", &self.challenge(Some(&error)))
+                        .await;
+                    Err(HttpError::Unauthorized(error.to_string()))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::{encode, Jwk, SigningKey};
+    use crate::middleware::{Middleware, Next};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use suika_json::json;
+    use suika_utils::base64url_encode;
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware that records the request it saw
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        seen: Arc<TokioMutex<Option<Request>>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                seen: Arc::new(TokioMutex::new(None)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let seen = Arc::clone(&self.seen);
+            Box::pin(async move {
+                *seen.lock().await = Some(req.clone());
+                Ok(())
+            })
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn middleware() -> JwtMiddleware {
+        JwtMiddleware::new(
+            JwkSet::new().with_key(Jwk::hmac(b"secret")),
+            Validation::new().with_issuer("suika"),
+        )
+        .with_realm("api")
+    }
+
+    async fn run(
+        jwt: &JwtMiddleware,
+        authorization: Option<&str>,
+    ) -> (Result<(), HttpError>, Response, Option<Request>) {
+        let request = match authorization {
+            Some(value) => format!("GET /api HTTP/1.1\r\nAuthorization: {}\r\n\r\n", value),
+            None => "GET /api HTTP/1.1\r\n\r\n".to_string(),
+        };
+        let mut req = Request::new(&request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        let mut res = Response::new(None);
+
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = jwt.handle(&mut req, &mut res, next).await;
+        let seen = next_middleware.seen.lock().await.clone();
+        (result, res, seen)
+    }
+
+    #[tokio::test]
+    async fn test_jwt_valid_token() {
+        let claims = json!({
+            "sub" => "alice",
+            "iss" => "suika",
+            "exp" => now() + 60,
+            "roles" => vec!["admin"],
+            "scope" => "todos:read todos:write",
+        });
+        let token = encode(&claims, &SigningKey::hmac(b"secret"), None).unwrap();
+
+        let (result, _, seen) = run(&middleware(), Some(&format!("Bearer {}", token))).await;
+
+        assert!(result.is_ok());
+        let req = seen.unwrap();
+        assert_eq!(req.claims(), Some(&claims));
+        assert_eq!(
+            req.extensions().get::<crate::jwt::Claims>(),
+            Some(&crate::jwt::Claims(claims.clone()))
+        );
+        assert_eq!(req.extensions().get::<Principal>(), req.principal());
+
+        let principal = req.principal().unwrap();
+        assert_eq!(principal.id(), "alice");
+        assert!(principal.has_role("admin"));
+        assert!(principal.has_permission("todos:read"));
+        assert!(principal.has_permission("todos:write"));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_missing_token() {
+        let (result, res, seen) = run(&middleware(), None).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        assert!(seen.is_none());
+
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("WWW-Authenticate"),
+            Some(&"Bearer realm=\"api\"".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jwt_expired_token() {
+        let claims = json!({ "sub" => "alice", "iss" => "suika", "exp" => now() - 3600 });
+        let token = encode(&claims, &SigningKey::hmac(b"secret"), None).unwrap();
+
+        let (result, res, seen) = run(&middleware(), Some(&format!("Bearer {}", token))).await;
+
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        assert!(seen.is_none());
+
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("WWW-Authenticate"),
+            Some(
+                &"Bearer realm=\"api\", error=\"invalid_token\", error_description=\"token expired\""
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jwt_wrong_key_and_issuer() {
+        let claims = json!({ "sub" => "alice", "iss" => "suika", "exp" => now() + 60 });
+        let token = encode(&claims, &SigningKey::hmac(b"other"), None).unwrap();
+        let (result, _, _) = run(&middleware(), Some(&format!("Bearer {}", token))).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+
+        let claims = json!({ "sub" => "alice", "iss" => "elsewhere", "exp" => now() + 60 });
+        let token = encode(&claims, &SigningKey::hmac(b"secret"), None).unwrap();
+        let (result, _, _) = run(&middleware(), Some(&format!("Bearer {}", token))).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+
+        let (result, _, _) = run(&middleware(), Some("Basic YWxpY2U6c2VjcmV0")).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_challenge_excludes_parser_details() {
+        let header = base64url_encode(b"{\"alg\":\"\\\r\"}");
+        let token = format!("{}.e30.c2ln", header);
+        let (result, res, seen) = run(&middleware(), Some(&format!("Bearer {}", token))).await;
+
+        assert!(seen.is_none());
+        match result {
+            Err(HttpError::Unauthorized(message)) => assert!(message.contains('\r')),
+            other => panic!("Unexpected result: {:?}", other.map_err(|e| e.to_string())),
+        }
+
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("WWW-Authenticate"),
+            Some(
+                &"Bearer realm=\"api\", error=\"invalid_token\", error_description=\"malformed token\""
+                    .to_string()
+            )
+        );
+    }
+}
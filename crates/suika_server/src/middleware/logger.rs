@@ -1,76 +1,107 @@
-use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::access_log::{AccessLogEntry, LogFormat, LogSink, StdoutSink};
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next, RequestId};
 use crate::request::Request;
-use crate::response::Response;
+use crate::response::{Body, Response};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
-/// A middleware component for logging HTTP requests.
+/// A middleware component for writing access logs.
 ///
-/// This middleware logs the HTTP method and path of incoming requests.
-/// It then passes the request to the next middleware in the stack.
+/// After the rest of the stack has handled a request, an `AccessLogEntry` with the
+/// client IP, request line, status, response size, latency, user agent, referer and
+/// request ID is formatted with a `LogFormat` and written to a `LogSink`. Errors
+/// returned by handlers are logged with the status they are answered with, and then
+/// passed on unchanged.
 ///
-/// # Examples
-///
-/// ```
-/// use suika_server::request::Request;
-/// use suika_server::response::Response;
-/// use suika_server::middleware::{Middleware, Next, MiddlewareFuture};
-/// use suika_server::LoggerMiddleware;
-/// use std::collections::HashMap;
-/// use std::sync::{Arc, Mutex};
-/// use tokio::sync::Mutex as TokioMutex;
-///
-/// #[derive(Clone)]
-/// struct MockNextMiddleware {
-///     called: Arc<TokioMutex<bool>>,
-/// }
-///
-/// impl MockNextMiddleware {
-///     fn new() -> Self {
-///         Self {
-///             called: Arc::new(TokioMutex::new(false)),
-///         }
-///     }
-/// }
-///
-/// impl Middleware for MockNextMiddleware {
-///     fn handle<'a>(
-///         &'a self,
-///         _req: &'a mut Request,
-///         _res: &'a mut Response,
-///         _next: Next<'a>,
-///     ) -> MiddlewareFuture<'a> {
-///         let called = Arc::clone(&self.called);
-///         Box::pin(async move {
-///             let mut called_lock = called.lock().await;
-///             *called_lock = true;
-///             Ok(())
-///         })
-///     }
-/// }
+/// By default entries are written in the Combined Log Format to standard output.
 ///
-/// #[tokio::main]
-/// async fn main() {
-///     let mut req = Request::new(
-///         "GET /test HTTP/1.1\r\n\r\n",
-///         Arc::new(Mutex::new(HashMap::new())),
-///     ).unwrap();
-///
-///     let mut res = Response::new(None);
-///
-///     let logger_middleware = LoggerMiddleware;
-///     let next_middleware = MockNextMiddleware::new();
-///     let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![Arc::new(next_middleware.clone())];
-///     let next = Next::new(middleware_stack.as_slice());
+/// # Examples
 ///
-///     logger_middleware.handle(&mut req, &mut res, next.clone()).await.unwrap();
+/// ```no_run
+/// use suika_server::access_log::{FileSink, LogFormat};
+/// use suika_server::middleware::LoggerMiddleware;
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
 ///
-///     let next_called = *next_middleware.called.lock().await;
-///     assert!(next_called);
-/// }
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(
+///     LoggerMiddleware::new()
+///         .with_format(LogFormat::Json)
+///         .with_sink(FileSink::new("access.log").unwrap().with_max_size(10 * 1024 * 1024))
+///         .with_excluded_path("/health"),
+/// ));
 /// ```
-pub struct LoggerMiddleware;
+pub struct LoggerMiddleware {
+    format: LogFormat,
+    sink: Arc<dyn LogSink>,
+    excluded_paths: Vec<String>,
+}
+
+impl LoggerMiddleware {
+    /// Creates a new `LoggerMiddleware` writing the Combined Log Format to standard output.
+    pub fn new() -> Self {
+        Self {
+            format: LogFormat::Combined,
+            sink: Arc::new(StdoutSink),
+            excluded_paths: Vec::new(),
+        }
+    }
+
+    /// Sets the format of log lines.
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the sink log lines are written to.
+    pub fn with_sink<S: LogSink + 'static>(mut self, sink: S) -> Self {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    /// Excludes requests for a path, and the paths below it, from the log.
+    pub fn with_excluded_path(mut self, path: &str) -> Self {
+        self.excluded_paths
+            .push(path.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Returns `true` if requests for `path` are not logged.
+    fn is_excluded(&self, path: &str) -> bool {
+        self.excluded_paths.iter().any(|excluded| {
+            path.strip_prefix(excluded.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+impl Default for LoggerMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the size of a response body, if it is known before sending.
+async fn response_size(res: &Response) -> Option<u64> {
+    if let Some(length) = res
+        .get_header("Content-Length")
+        .await
+        .and_then(|length| length.parse().ok())
+    {
+        return Some(length);
+    }
+    match res.get_inner().await.body() {
+        Some(Body::Text(text)) => Some(text.len() as u64),
+        Some(Body::Binary(bytes)) => Some(bytes.len() as u64),
+        Some(Body::File(file)) => Some(file.len()),
+        Some(Body::Stream(_)) => None,
+        None => Some(0),
+    }
+}
 
 impl Middleware for LoggerMiddleware {
-    /// Handles an incoming HTTP request by logging the method and path.
+    /// Handles an incoming HTTP request by logging it once the rest of the stack has handled it.
     ///
     /// # Arguments
     ///
@@ -85,42 +116,13 @@ impl Middleware for LoggerMiddleware {
     /// # Examples
     ///
     /// ```
+    /// use suika_server::access_log::{ChannelSink, LogFormat};
     /// use suika_server::request::Request;
     /// use suika_server::response::Response;
-    /// use suika_server::middleware::{Middleware, Next, MiddlewareFuture};
+    /// use suika_server::middleware::{Middleware, Next};
     /// use suika_server::LoggerMiddleware;
     /// use std::collections::HashMap;
     /// use std::sync::{Arc, Mutex};
-    /// use tokio::sync::Mutex as TokioMutex;
-    ///
-    /// #[derive(Clone)]
-    /// struct MockNextMiddleware {
-    ///     called: Arc<TokioMutex<bool>>,
-    /// }
-    ///
-    /// impl MockNextMiddleware {
-    ///     fn new() -> Self {
-    ///         Self {
-    ///             called: Arc::new(TokioMutex::new(false)),
-    ///         }
-    ///     }
-    /// }
-    ///
-    /// impl Middleware for MockNextMiddleware {
-    ///     fn handle<'a>(
-    ///         &'a self,
-    ///         _req: &'a mut Request,
-    ///         _res: &'a mut Response,
-    ///         _next: Next<'a>,
-    ///     ) -> MiddlewareFuture<'a> {
-    ///         let called = Arc::clone(&self.called);
-    ///         Box::pin(async move {
-    ///             let mut called_lock = called.lock().await;
-    ///             *called_lock = true;
-    ///             Ok(())
-    ///         })
-    ///     }
-    /// }
     ///
     /// #[tokio::main]
     /// async fn main() {
@@ -128,18 +130,17 @@ impl Middleware for LoggerMiddleware {
     ///         "GET /test HTTP/1.1\r\n\r\n",
     ///         Arc::new(Mutex::new(HashMap::new())),
     ///     ).unwrap();
-    ///
     ///     let mut res = Response::new(None);
     ///
-    ///     let logger_middleware = LoggerMiddleware;
-    ///     let next_middleware = MockNextMiddleware::new();
-    ///     let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![Arc::new(next_middleware.clone())];
-    ///     let next = Next::new(middleware_stack.as_slice());
+    ///     let (sink, mut lines) = ChannelSink::unbounded();
+    ///     let logger_middleware = LoggerMiddleware::new()
+    ///         .with_format(LogFormat::Custom("{method} {target} {status}".to_string()))
+    ///         .with_sink(sink);
+    ///     let next = Next::new(&[]);
     ///
-    ///     logger_middleware.handle(&mut req, &mut res, next.clone()).await.unwrap();
+    ///     logger_middleware.handle(&mut req, &mut res, next).await.unwrap();
     ///
-    ///     let next_called = *next_middleware.called.lock().await;
-    ///     assert!(next_called);
+    ///     assert_eq!(lines.recv().await.unwrap(), "GET /test 404");
     /// }
     /// ```
     fn handle<'a>(
@@ -149,8 +150,55 @@ impl Middleware for LoggerMiddleware {
         mut next: Next<'a>,
     ) -> MiddlewareFuture<'a> {
         Box::pin(async move {
-            println!("Logger => Method: {}, Path: {}", req.method(), req.path());
-            next.run(req, res).await
+            if self.is_excluded(req.path()) {
+                return next.run(req, res).await;
+            }
+
+            let timestamp = SystemTime::now();
+            let start = Instant::now();
+            let result = next.run(req, res).await;
+            let latency = start.elapsed();
+
+            let (status, bytes, error) = match &result {
+                Ok(()) => (
+                    res.status().await.unwrap_or(404),
+                    response_size(res).await,
+                    None,
+                ),
+                Err(e) => {
+                    let (status, message) = HttpError::to_tuple(e);
+                    (
+                        status,
+                        Some(message.len() as u64),
+                        Some(message.to_string()),
+                    )
+                }
+            };
+            // Only IDs validated or generated by a `RequestIdMiddleware` are logged, never
+            // the raw request header.
+            let request_id = match req.extensions().get::<RequestId>() {
+                Some(request_id) => Some(request_id.to_string()),
+                None => res.get_header("X-Request-Id").await,
+            };
+
+            let entry = AccessLogEntry {
+                timestamp,
+                client_ip: req.client_ip(),
+                user: req.principal().map(|principal| principal.id().to_string()),
+                method: req.method().to_string(),
+                target: req.target().to_string(),
+                version: req.version().to_string(),
+                status,
+                bytes,
+                latency,
+                user_agent: req.header("User-Agent").map(str::to_string),
+                referer: req.header("Referer").map(str::to_string),
+                request_id,
+                error,
+            };
+            self.sink.write(&self.format.format(&entry));
+
+            result
         })
     }
 }
@@ -158,6 +206,7 @@ impl Middleware for LoggerMiddleware {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::access_log::ChannelSink;
     use crate::middleware::{Middleware, Next};
     use crate::request::Request;
     use crate::response::Response;
@@ -205,7 +254,7 @@ mod tests {
 
         let mut res = Response::new(None);
 
-        let logger_middleware = LoggerMiddleware;
+        let logger_middleware = LoggerMiddleware::new();
         let next_middleware = MockNextMiddleware::new();
         let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
             vec![Arc::new(next_middleware.clone())];
@@ -230,7 +279,7 @@ mod tests {
 
         let mut res = Response::new(None);
 
-        let logger_middleware = LoggerMiddleware;
+        let logger_middleware = LoggerMiddleware::new();
         let next_middleware = MockNextMiddleware::new();
         let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
             vec![Arc::new(next_middleware.clone())];
@@ -244,4 +293,139 @@ mod tests {
         let next_called = *next_middleware.called.lock().await;
         assert!(next_called);
     }
+
+    // Mock handler responding with a fixed status and body, or a forbidden error
+    struct MockHandler {
+        result: Result<&'static str, &'static str>,
+    }
+
+    impl Middleware for MockHandler {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                match &self.result {
+                    Ok(body) => {
+                        res.set_status(201).await;
+                        res.body(body.to_string()).await;
+                        Ok(())
+                    }
+                    Err(message) => Err(HttpError::Forbidden(message.to_string())),
+                }
+            })
+        }
+    }
+
+    async fn log(
+        logger: &LoggerMiddleware,
+        request: &str,
+        result: Result<&'static str, &'static str>,
+    ) -> Result<(), HttpError> {
+        let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        req.set_peer_addr("192.0.2.1:50000".parse().unwrap());
+        let mut res = Response::new(None);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockHandler { result })];
+        let next = Next::new(middleware_stack.as_slice());
+        logger.handle(&mut req, &mut res, next).await
+    }
+
+    #[tokio::test]
+    async fn test_logger_middleware_writes_entries() {
+        let (sink, mut lines) = ChannelSink::unbounded();
+        let logger = LoggerMiddleware::new()
+            .with_format(LogFormat::Custom(
+                "{client_ip} \"{request_line}\" {status} {bytes} \"{referer}\" \"{user_agent}\" {request_id} {error}".to_string(),
+            ))
+            .with_sink(sink);
+
+        let result = log(
+            &logger,
+            "GET /items?page=2 HTTP/1.1\r\nUser-Agent: curl/8.0\r\nReferer: /home\r\nX-Request-Id: abc\r\n\r\n",
+            Ok("created"),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(
+            lines.try_recv().unwrap(),
+            "192.0.2.1 \"GET /items?page=2 HTTP/1.1\" 201 7 \"/home\" \"curl/8.0\" - -"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logger_middleware_logs_validated_request_id() {
+        let (sink, mut lines) = ChannelSink::unbounded();
+        let logger = LoggerMiddleware::new()
+            .with_format(LogFormat::Custom("{request_id}".to_string()))
+            .with_sink(sink);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![
+            Arc::new(crate::middleware::RequestIdMiddleware::new()),
+            Arc::new(MockHandler { result: Ok("") }),
+        ];
+
+        for (header, expected) in [("abc-123", Some("abc-123")), ("a b\"c", None)] {
+            let mut req = Request::new(
+                &format!("GET / HTTP/1.1\r\nX-Request-Id: {}\r\n\r\n", header),
+                Arc::new(Mutex::new(HashMap::new())),
+            )
+            .unwrap();
+            let mut res = Response::new(None);
+            let next = Next::new(middleware_stack.as_slice());
+            logger.handle(&mut req, &mut res, next).await.unwrap();
+
+            let line = lines.try_recv().unwrap();
+            match expected {
+                Some(expected) => assert_eq!(line, expected),
+                None => assert_eq!(line.len(), 36, "Logged: {}", line),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logger_middleware_logs_errors() {
+        let (sink, mut lines) = ChannelSink::unbounded();
+        let logger = LoggerMiddleware::new()
+            .with_format(LogFormat::Json)
+            .with_sink(sink);
+
+        let result = log(
+            &logger,
+            "POST /items HTTP/1.1\r\n\r\n",
+            Err("Access denied"),
+        )
+        .await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+
+        let json = suika_json::parse_json(&lines.try_recv().unwrap()).unwrap();
+        assert_eq!(json.get("status").and_then(|v| v.as_f64()), Some(403.0));
+        assert_eq!(
+            json.get("error").and_then(|v| v.as_str()),
+            Some("Access denied")
+        );
+        assert_eq!(json.get("method").and_then(|v| v.as_str()), Some("POST"));
+    }
+
+    #[tokio::test]
+    async fn test_logger_middleware_excludes_paths() {
+        let (sink, mut lines) = ChannelSink::unbounded();
+        let logger = LoggerMiddleware::new()
+            .with_format(LogFormat::Common)
+            .with_sink(sink)
+            .with_excluded_path("/health/");
+
+        for path in ["/health", "/health/live", "/healthy"] {
+            log(&logger, &format!("GET {} HTTP/1.1\r\n\r\n", path), Ok("ok"))
+                .await
+                .unwrap();
+        }
+
+        assert!(lines
+            .try_recv()
+            .unwrap()
+            .contains("\"GET /healthy HTTP/1.1\" 201 2"));
+        assert!(lines.try_recv().is_err());
+    }
 }
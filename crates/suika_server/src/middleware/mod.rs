@@ -1,11 +1,36 @@
+pub mod assets;
+pub mod auth;
+pub mod compression;
+pub mod conditional;
 pub mod cors;
+pub mod csrf;
+pub mod decompression;
+mod directory_listing;
+pub mod embedded_file;
 pub mod favicon;
+pub mod ip_filter;
+pub mod jwt;
 pub mod logger;
+pub mod rate_limit;
+pub mod request_id;
+pub mod security_headers;
 pub mod static_file;
 pub mod traits;
 
+pub use assets::AssetMiddleware;
+pub use auth::AuthMiddleware;
+pub use compression::CompressionMiddleware;
+pub use conditional::ConditionalMiddleware;
 pub use cors::CorsMiddleware;
+pub use csrf::CsrfMiddleware;
+pub use decompression::DecompressionMiddleware;
+pub use embedded_file::EmbeddedFileMiddleware;
 pub use favicon::FaviconMiddleware;
+pub use ip_filter::IpFilterMiddleware;
+pub use jwt::JwtMiddleware;
 pub use logger::LoggerMiddleware;
-pub use static_file::StaticFileMiddleware;
+pub use rate_limit::RateLimitMiddleware;
+pub use request_id::{RequestId, RequestIdFormat, RequestIdMiddleware};
+pub use security_headers::SecurityHeadersMiddleware;
+pub use static_file::{DotFiles, StaticFileMiddleware};
 pub use traits::{Middleware, MiddlewareFuture, Next};
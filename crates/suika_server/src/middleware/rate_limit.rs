@@ -0,0 +1,677 @@
+use crate::error::HttpError;
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::request::Request;
+use crate::response::Response;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A rate limit applied to each client key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quota {
+    /// Allows bursts of up to `capacity` requests, refilling `capacity` tokens
+    /// continuously over every `period`.
+    TokenBucket { capacity: u32, period: Duration },
+    /// Allows `limit` requests in any `window`, approximated by weighting the count of
+    /// the previous fixed window.
+    SlidingWindow { limit: u32, window: Duration },
+}
+
+impl Quota {
+    /// Returns the maximum number of requests allowed by the quota.
+    pub fn limit(&self) -> u32 {
+        match self {
+            Quota::TokenBucket { capacity, .. } => *capacity,
+            Quota::SlidingWindow { limit, .. } => *limit,
+        }
+    }
+
+    /// Returns the period over which the limit applies.
+    pub fn period(&self) -> Duration {
+        match self {
+            Quota::TokenBucket { period, .. } => *period,
+            Quota::SlidingWindow { window, .. } => *window,
+        }
+    }
+
+    /// Checks that the quota allows at least one request over a non-zero period.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.limit() == 0 {
+            return Err(format!("Rate limit quota must allow requests: {:?}", self));
+        }
+        if self.period().is_zero() {
+            return Err(format!(
+                "Rate limit quota period must not be zero: {:?}",
+                self
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of counting a request against a quota.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    /// Whether the request is allowed.
+    pub allowed: bool,
+    /// The maximum number of requests allowed by the quota.
+    pub limit: u32,
+    /// The number of requests still allowed right now.
+    pub remaining: u32,
+    /// The time until the quota is fully available again.
+    pub reset: Duration,
+    /// The time until the next request will be allowed, if this one was rejected.
+    pub retry_after: Option<Duration>,
+}
+
+/// Storage for rate limit counters.
+///
+/// Implement this trait to share counters between several server instances, for
+/// example in an external key-value store.
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a request for `key` against `quota` and returns the decision.
+    fn hit<'a>(&'a self, key: &'a str, quota: &'a Quota) -> BoxFuture<'a, RateLimitDecision>;
+}
+
+/// Counter state of a single key.
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Bucket {
+        tokens: f64,
+        updated: Instant,
+    },
+    Window {
+        start: Instant,
+        previous: u32,
+        current: u32,
+    },
+}
+
+/// An in-process `RateLimitStore`.
+///
+/// Entries that have been idle long enough to be fully reset are removed every
+/// `cleanup_interval` (one minute by default).
+#[derive(Debug)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+    cleanup_interval: Duration,
+    last_cleanup: Mutex<Instant>,
+}
+
+impl MemoryStore {
+    /// Creates an empty `MemoryStore`.
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            cleanup_interval: Duration::from_secs(60),
+            last_cleanup: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Sets how often idle entries are removed.
+    pub fn with_cleanup_interval(mut self, interval: Duration) -> Self {
+        self.cleanup_interval = interval;
+        self
+    }
+
+    /// Returns the number of keys currently tracked.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts a request for `key` as of `now`.
+    fn hit_at(&self, key: &str, quota: &Quota, now: Instant) -> RateLimitDecision {
+        self.cleanup(quota, now);
+
+        let mut entries = self.entries.lock().unwrap();
+        match *quota {
+            Quota::TokenBucket { capacity, period } => {
+                let entry = entries.entry(key.to_string()).or_insert(Entry::Bucket {
+                    tokens: capacity as f64,
+                    updated: now,
+                });
+                token_bucket(entry, capacity, period, now)
+            }
+            Quota::SlidingWindow { limit, window } => {
+                let entry = entries.entry(key.to_string()).or_insert(Entry::Window {
+                    start: now,
+                    previous: 0,
+                    current: 0,
+                });
+                sliding_window(entry, limit, window, now)
+            }
+        }
+    }
+
+    /// Removes entries idle for long enough that they no longer affect any decision.
+    fn cleanup(&self, quota: &Quota, now: Instant) {
+        {
+            let mut last_cleanup = self.last_cleanup.lock().unwrap();
+            if now.saturating_duration_since(*last_cleanup) < self.cleanup_interval {
+                return;
+            }
+            *last_cleanup = now;
+        }
+
+        let period = quota.period();
+        self.entries.lock().unwrap().retain(|_, entry| match entry {
+            Entry::Bucket { updated, .. } => now.saturating_duration_since(*updated) < period,
+            Entry::Window { start, .. } => now.saturating_duration_since(*start) < period * 2,
+        });
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    fn hit<'a>(&'a self, key: &'a str, quota: &'a Quota) -> BoxFuture<'a, RateLimitDecision> {
+        Box::pin(async move { self.hit_at(key, quota, Instant::now()) })
+    }
+}
+
+/// Applies the token bucket algorithm to `entry`.
+fn token_bucket(
+    entry: &mut Entry,
+    capacity: u32,
+    period: Duration,
+    now: Instant,
+) -> RateLimitDecision {
+    let capacity_f = capacity as f64;
+    let rate = capacity_f / period.as_secs_f64().max(f64::EPSILON);
+
+    let mut current = match *entry {
+        Entry::Bucket { tokens, updated } => {
+            let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+            (tokens + elapsed * rate).min(capacity_f)
+        }
+        Entry::Window { .. } => capacity_f,
+    };
+
+    let allowed = current >= 1.0;
+    if allowed {
+        current -= 1.0;
+    }
+    *entry = Entry::Bucket {
+        tokens: current,
+        updated: now,
+    };
+
+    RateLimitDecision {
+        allowed,
+        limit: capacity,
+        remaining: current.floor() as u32,
+        reset: seconds((capacity_f - current) / rate),
+        retry_after: (!allowed).then(|| seconds((1.0 - current) / rate)),
+    }
+}
+
+/// Converts seconds to a `Duration`, saturating for degenerate quotas whose rate is
+/// zero.
+fn seconds(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+}
+
+/// Applies the sliding window counter algorithm to `entry`.
+fn sliding_window(
+    entry: &mut Entry,
+    limit: u32,
+    window: Duration,
+    now: Instant,
+) -> RateLimitDecision {
+    let (mut start, mut previous, mut current) = match *entry {
+        Entry::Window {
+            start,
+            previous,
+            current,
+        } => (start, previous, current),
+        Entry::Bucket { .. } => (now, 0, 0),
+    };
+
+    let since_start = now.saturating_duration_since(start).as_nanos();
+    let window_nanos = window.as_nanos().max(1);
+    let windows_passed = since_start / window_nanos;
+    if windows_passed > 0 {
+        previous = if windows_passed == 1 { current } else { 0 };
+        current = 0;
+        start = now - Duration::from_nanos((since_start % window_nanos) as u64);
+    }
+
+    let elapsed = now.saturating_duration_since(start);
+    let weight = 1.0 - elapsed.as_secs_f64() / window.as_secs_f64().max(f64::EPSILON);
+    let estimated = previous as f64 * weight + current as f64;
+
+    let allowed = estimated + 1.0 <= limit as f64;
+    if allowed {
+        current += 1;
+    }
+    *entry = Entry::Window {
+        start,
+        previous,
+        current,
+    };
+
+    let used = (previous as f64 * weight + current as f64).ceil() as u32;
+    let retry_after = (!allowed).then(|| {
+        if current >= limit || previous == 0 {
+            window - elapsed
+        } else {
+            // The previous window's weight must drop enough to make room for one request.
+            let target_weight = (limit - current - 1) as f64 / previous as f64;
+            let target_elapsed = window.mul_f64(1.0 - target_weight);
+            target_elapsed.saturating_sub(elapsed)
+        }
+    });
+
+    RateLimitDecision {
+        allowed,
+        limit,
+        remaining: limit.saturating_sub(used),
+        reset: window - elapsed,
+        retry_after,
+    }
+}
+
+/// A function that derives the rate limit key of a request.
+type KeyExtractor = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// A middleware component that limits how many requests each client can make.
+///
+/// Requests are grouped by a key, which defaults to the client IP address. Every
+/// response carries `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` and
+/// `RateLimit-Policy` headers. Requests over the quota are rejected with
+/// `HttpError::TooManyRequests` and a `Retry-After` header.
+///
+/// Requests for which the key extractor returns `None` are not limited.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::rate_limit::{Quota, RateLimitMiddleware};
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+///
+/// // 100 requests per minute for each API key, with bursts of up to 100.
+/// let limiter = RateLimitMiddleware::new(Quota::TokenBucket {
+///     capacity: 100,
+///     period: Duration::from_secs(60),
+/// })
+/// .with_header_key("X-API-Key");
+///
+/// server.use_middleware(Arc::new(limiter));
+/// ```
+pub struct RateLimitMiddleware {
+    quota: Quota,
+    key: KeyExtractor,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimitMiddleware {
+    /// Creates a new `RateLimitMiddleware` keyed by client IP with an in-memory store.
+    ///
+    /// # Arguments
+    ///
+    /// * `quota` - The limit applied to each key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the quota allows no requests or has a zero period.
+    pub fn new(quota: Quota) -> Self {
+        if let Err(message) = quota.validate() {
+            panic!("{}", message);
+        }
+        Self {
+            quota,
+            key: Arc::new(|req: &Request| {
+                Some(
+                    req.client_ip()
+                        .map(|ip| ip.to_string())
+                        .unwrap_or_else(|| "unknown".to_string()),
+                )
+            }),
+            store: Arc::new(MemoryStore::new()),
+        }
+    }
+
+    /// Keys requests by the value of a header such as `X-API-Key`.
+    ///
+    /// Requests without the header are not limited.
+    pub fn with_header_key(self, header: &str) -> Self {
+        let header = header.to_string();
+        self.with_key(move |req: &Request| req.header(&header).map(|value| value.to_string()))
+    }
+
+    /// Keys requests with a custom extractor.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Returns the key of a request, or `None` to exempt it from limiting.
+    pub fn with_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// Replaces the in-memory store with a custom store.
+    pub fn with_store(mut self, store: Arc<dyn RateLimitStore>) -> Self {
+        self.store = store;
+        self
+    }
+}
+
+/// Formats a duration as whole seconds, rounding up.
+fn ceil_secs(duration: Duration) -> String {
+    (duration.as_nanos().div_ceil(1_000_000_000)).to_string()
+}
+
+impl Middleware for RateLimitMiddleware {
+    /// Handles an incoming HTTP request by counting it against the client's quota.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let key = match (self.key)(req) {
+                Some(key) => key,
+                None => return next.run(req, res).await,
+            };
+
+            let decision = self.store.hit(&key, &self.quota).await;
+
+            res.header("RateLimit-Limit", &decision.limit.to_string())
+                .await;
+            res.header("RateLimit-Remaining", &decision.remaining.to_string())
+                .await;
+            res.header("RateLimit-Reset", &ceil_secs(decision.reset))
+                .await;
+            res.header(
+                "RateLimit-Policy",
+                &format!(
+                    "{};w={}",
+                    self.quota.limit(),
+                    ceil_secs(self.quota.period())
+                ),
+            )
+            .await;
+
+            if let Some(retry_after) = decision.retry_after {
+                res.header("Retry-After", &ceil_secs(retry_after)).await;
+                return Err(HttpError::TooManyRequests(
+                    "Rate limit exceeded".to_string(),
+                ));
+            }
+
+            next.run(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, Next};
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        called: Arc<TokioMutex<bool>>,
+    }
+
+    impl MockNextMiddleware {
+        fn new() -> Self {
+            Self {
+                called: Arc::new(TokioMutex::new(false)),
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            let called = Arc::clone(&self.called);
+            Box::pin(async move {
+                let mut called_lock = called.lock().await;
+                *called_lock = true;
+                Ok(())
+            })
+        }
+    }
+
+    async fn run(
+        limiter: &RateLimitMiddleware,
+        request: &str,
+        peer: &str,
+    ) -> (Result<(), HttpError>, Response, bool) {
+        let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+        req.set_peer_addr(peer.parse().unwrap());
+        let mut res = Response::new(None);
+
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = limiter.handle(&mut req, &mut res, next).await;
+        let called = *next_middleware.called.lock().await;
+        (result, res, called)
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let store = MemoryStore::new();
+        let quota = Quota::TokenBucket {
+            capacity: 2,
+            period: Duration::from_secs(10),
+        };
+        let start = Instant::now();
+
+        let first = store.hit_at("a", &quota, start);
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert!(store.hit_at("a", &quota, start).allowed);
+
+        let rejected = store.hit_at("a", &quota, start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+        assert_eq!(rejected.retry_after, Some(Duration::from_secs(5)));
+
+        // Other keys have their own bucket.
+        assert!(store.hit_at("b", &quota, start).allowed);
+
+        // One token is refilled every 5 seconds.
+        assert!(
+            store
+                .hit_at("a", &quota, start + Duration::from_secs(5))
+                .allowed
+        );
+        assert!(
+            !store
+                .hit_at("a", &quota, start + Duration::from_secs(5))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let store = MemoryStore::new();
+        let quota = Quota::SlidingWindow {
+            limit: 4,
+            window: Duration::from_secs(10),
+        };
+        let start = Instant::now();
+
+        for _ in 0..4 {
+            assert!(store.hit_at("a", &quota, start).allowed);
+        }
+        let rejected = store.hit_at("a", &quota, start + Duration::from_secs(2));
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Some(Duration::from_secs(8)));
+
+        // Halfway through the next window, half of the previous count still applies.
+        let next = start + Duration::from_secs(15);
+        assert!(store.hit_at("a", &quota, next).allowed);
+        assert!(store.hit_at("a", &quota, next).allowed);
+        let rejected = store.hit_at("a", &quota, next);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+        assert_eq!(rejected.retry_after, Some(Duration::from_millis(2500)));
+
+        // After two full windows the counters are reset.
+        assert!(
+            store
+                .hit_at("a", &quota, start + Duration::from_secs(30))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_degenerate_quotas() {
+        let empty = Quota::TokenBucket {
+            capacity: 0,
+            period: Duration::from_secs(1),
+        };
+        let instant = Quota::SlidingWindow {
+            limit: 1,
+            window: Duration::ZERO,
+        };
+        assert!(empty.validate().is_err());
+        assert!(instant.validate().is_err());
+        assert!(Quota::SlidingWindow {
+            limit: 0,
+            window: Duration::from_secs(1),
+        }
+        .validate()
+        .is_err());
+
+        // Stores used directly deny the request instead of panicking.
+        let store = MemoryStore::new();
+        let decision = store.hit_at("a", &empty, Instant::now());
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Some(Duration::MAX));
+    }
+
+    #[test]
+    #[should_panic(expected = "must allow requests")]
+    fn test_rejects_empty_quota() {
+        RateLimitMiddleware::new(Quota::TokenBucket {
+            capacity: 0,
+            period: Duration::from_secs(60),
+        });
+    }
+
+    #[test]
+    fn test_memory_store_cleanup() {
+        let store = MemoryStore::new().with_cleanup_interval(Duration::from_secs(1));
+        let quota = Quota::TokenBucket {
+            capacity: 1,
+            period: Duration::from_secs(1),
+        };
+        let start = Instant::now();
+
+        store.hit_at("a", &quota, start);
+        store.hit_at("b", &quota, start);
+        assert_eq!(store.len(), 2);
+
+        store.hit_at("c", &quota, start + Duration::from_secs(5));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_by_ip() {
+        let limiter = RateLimitMiddleware::new(Quota::TokenBucket {
+            capacity: 2,
+            period: Duration::from_secs(60),
+        });
+        let request = "GET / HTTP/1.1\r\n\r\n";
+
+        let (result, res, called) = run(&limiter, request, "10.0.0.1:4000").await;
+        assert!(result.is_ok());
+        assert!(called);
+        let inner = res.get_inner().await;
+        assert_eq!(
+            inner.headers().get("RateLimit-Limit"),
+            Some(&"2".to_string())
+        );
+        assert_eq!(
+            inner.headers().get("RateLimit-Remaining"),
+            Some(&"1".to_string())
+        );
+        assert_eq!(
+            inner.headers().get("RateLimit-Reset"),
+            Some(&"30".to_string())
+        );
+        assert_eq!(
+            inner.headers().get("RateLimit-Policy"),
+            Some(&"2;w=60".to_string())
+        );
+
+        // A different port on the same host shares the limit.
+        let (result, _, _) = run(&limiter, request, "10.0.0.1:4001").await;
+        assert!(result.is_ok());
+
+        let (result, res, called) = run(&limiter, request, "10.0.0.1:4002").await;
+        assert!(matches!(result, Err(HttpError::TooManyRequests(_))));
+        assert!(!called);
+        let inner = res.get_inner().await;
+        assert_eq!(inner.headers().get("Retry-After"), Some(&"30".to_string()));
+
+        let (result, _, _) = run(&limiter, request, "10.0.0.2:4000").await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_by_header() {
+        let limiter = RateLimitMiddleware::new(Quota::SlidingWindow {
+            limit: 1,
+            window: Duration::from_secs(60),
+        })
+        .with_header_key("X-API-Key");
+
+        let with_key = "GET / HTTP/1.1\r\nX-API-Key: abc\r\n\r\n";
+        assert!(run(&limiter, with_key, "10.0.0.1:4000").await.0.is_ok());
+        assert!(run(&limiter, with_key, "10.0.0.2:4000").await.0.is_err());
+
+        // Requests without a key are not limited.
+        let without_key = "GET / HTTP/1.1\r\n\r\n";
+        for _ in 0..3 {
+            let (result, res, _) = run(&limiter, without_key, "10.0.0.1:4000").await;
+            assert!(result.is_ok());
+            assert!(!res
+                .get_inner()
+                .await
+                .headers()
+                .contains_key("RateLimit-Limit"));
+        }
+    }
+}
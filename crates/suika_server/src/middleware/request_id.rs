@@ -0,0 +1,298 @@
+use crate::middleware::{Middleware, MiddlewareFuture, Next};
+use crate::random::{ulid, uuid_v4};
+use crate::request::Request;
+use crate::response::Response;
+use std::fmt;
+
+/// The longest incoming request ID that is accepted.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// The ID of a request, stored in the request's extensions by a `RequestIdMiddleware`.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::middleware::RequestId;
+/// use suika_server::request::Request;
+///
+/// fn request_id(req: &Request) -> &str {
+///     req.extensions().get::<RequestId>().map_or("-", RequestId::as_str)
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Returns the ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The format of request IDs generated by a `RequestIdMiddleware`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestIdFormat {
+    /// A random UUID, such as `0f8fad5b-d9cb-469f-a165-70867728950e`.
+    UuidV4,
+    /// A ULID, such as `01ARZ3NDEKTSV4RRFFQ69G5FAV`, which sorts by creation time.
+    Ulid,
+}
+
+impl RequestIdFormat {
+    /// Generates a new ID in this format.
+    fn generate(self) -> String {
+        match self {
+            RequestIdFormat::UuidV4 => uuid_v4(),
+            RequestIdFormat::Ulid => ulid(),
+        }
+    }
+}
+
+/// A middleware component for assigning an ID to every request.
+///
+/// An ID sent by the client or a proxy in the `X-Request-Id` header is kept if it is
+/// at most 128 characters of letters, digits and `-_.:+/=`; otherwise a new one is
+/// generated. The ID is stored as a `RequestId` in the request's extensions, replaces
+/// the request header, and is echoed in the response header, including on error
+/// responses. A `LoggerMiddleware` logs it with each request and, with the `tracing`
+/// feature, it is recorded on the request span.
+///
+/// # Examples
+///
+/// ```no_run
+/// use suika_server::middleware::{LoggerMiddleware, RequestIdFormat, RequestIdMiddleware};
+/// use suika_server::server::Server;
+/// use std::sync::Arc;
+///
+/// let mut server = Server::new("127.0.0.1:8080");
+/// server.use_middleware(Arc::new(
+///     RequestIdMiddleware::new().with_format(RequestIdFormat::Ulid),
+/// ));
+/// server.use_middleware(Arc::new(LoggerMiddleware::new()));
+/// ```
+pub struct RequestIdMiddleware {
+    header: String,
+    format: RequestIdFormat,
+    trust_incoming: bool,
+}
+
+impl RequestIdMiddleware {
+    /// Creates a new `RequestIdMiddleware` using the `X-Request-Id` header and
+    /// generating random UUIDs.
+    pub fn new() -> Self {
+        Self {
+            header: "X-Request-Id".to_string(),
+            format: RequestIdFormat::UuidV4,
+            trust_incoming: true,
+        }
+    }
+
+    /// Sets the header the ID is read from and echoed in.
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = header.to_string();
+        self
+    }
+
+    /// Sets the format of generated IDs.
+    pub fn with_format(mut self, format: RequestIdFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets whether valid incoming IDs are kept. When `false`, every request is given
+    /// a new ID, for servers exposed directly to clients.
+    pub fn with_trust_incoming(mut self, trust_incoming: bool) -> Self {
+        self.trust_incoming = trust_incoming;
+        self
+    }
+}
+
+impl Default for RequestIdMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` if an incoming request ID is safe to log and echo.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:+/=".contains(&b))
+}
+
+impl Middleware for RequestIdMiddleware {
+    /// Handles an incoming HTTP request by assigning it an ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - A mutable reference to the incoming request.
+    /// * `res` - A mutable reference to the response to be sent.
+    /// * `next` - The next middleware in the stack.
+    ///
+    /// # Returns
+    ///
+    /// A future that resolves to a `Result<(), HttpError>`.
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        mut next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        Box::pin(async move {
+            let id = req
+                .header(&self.header)
+                .filter(|id| self.trust_incoming && is_valid_request_id(id))
+                .map(str::to_string)
+                .unwrap_or_else(|| self.format.generate());
+
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("request_id", id.as_str());
+
+            req.set_header(&self.header, &id);
+            res.header(&self.header, &id).await;
+            req.extensions_mut().insert(RequestId(id));
+
+            next.run(req, res).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HttpError;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Mutex as TokioMutex;
+
+    // Mock Next middleware
+    #[derive(Clone)]
+    struct MockNextMiddleware {
+        seen: Arc<TokioMutex<Option<RequestId>>>,
+        result: Result<(), &'static str>,
+    }
+
+    impl MockNextMiddleware {
+        fn new(result: Result<(), &'static str>) -> Self {
+            Self {
+                seen: Arc::new(TokioMutex::new(None)),
+                result,
+            }
+        }
+    }
+
+    impl Middleware for MockNextMiddleware {
+        fn handle<'a>(
+            &'a self,
+            req: &'a mut Request,
+            _res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                *self.seen.lock().await = req.extensions().get::<RequestId>().cloned();
+                self.result
+                    .map_err(|_| HttpError::NotFound("Not Found".to_string()))
+            })
+        }
+    }
+
+    async fn run(
+        middleware: &RequestIdMiddleware,
+        headers: &str,
+        result: Result<(), &'static str>,
+    ) -> (Request, Response, Option<RequestId>, Result<(), HttpError>) {
+        let mut req = Request::new(
+            &format!("GET /items HTTP/1.1\r\n{}\r\n", headers),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let next_middleware = MockNextMiddleware::new(result);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = middleware.handle(&mut req, &mut res, next).await;
+        let seen = next_middleware.seen.lock().await.clone();
+        (req, res, seen, result)
+    }
+
+    #[tokio::test]
+    async fn test_generates_request_id() {
+        let middleware = RequestIdMiddleware::new();
+        let (req, res, seen, result) = run(&middleware, "", Ok(())).await;
+        assert!(result.is_ok());
+
+        let id = seen.unwrap();
+        assert_eq!(id.as_str().len(), 36);
+        assert_eq!(req.header("X-Request-Id"), Some(id.as_str()));
+        assert_eq!(
+            res.get_header("X-Request-Id").await,
+            Some(id.as_str().to_string())
+        );
+
+        let (_, _, other, _) = run(&middleware, "", Ok(())).await;
+        assert_ne!(other.unwrap(), id);
+    }
+
+    #[tokio::test]
+    async fn test_generates_ulid() {
+        let middleware = RequestIdMiddleware::new()
+            .with_format(RequestIdFormat::Ulid)
+            .with_header("X-Correlation-Id");
+        let (_, res, seen, _) = run(&middleware, "", Ok(())).await;
+        let id = seen.unwrap();
+        assert_eq!(id.as_str().len(), 26);
+        assert_eq!(
+            res.get_header("X-Correlation-Id").await,
+            Some(id.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_accepts_valid_incoming_id() {
+        let middleware = RequestIdMiddleware::new();
+        let (_, res, seen, _) = run(&middleware, "x-request-id: abc-123\r\n", Ok(())).await;
+        assert_eq!(seen.unwrap().as_str(), "abc-123");
+        assert_eq!(
+            res.get_header("X-Request-Id").await,
+            Some("abc-123".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replaces_invalid_or_untrusted_id() {
+        let middleware = RequestIdMiddleware::new();
+        let long = format!("X-Request-Id: {}\r\n", "a".repeat(129));
+        for headers in ["X-Request-Id: a b\"c\r\n", "X-Request-Id: \r\n", &long] {
+            let (req, _, seen, _) = run(&middleware, headers, Ok(())).await;
+            let id = seen.unwrap();
+            assert_eq!(id.as_str().len(), 36);
+            assert_eq!(req.header("X-Request-Id"), Some(id.as_str()));
+        }
+
+        let middleware = RequestIdMiddleware::new().with_trust_incoming(false);
+        let (_, _, seen, _) = run(&middleware, "X-Request-Id: abc\r\n", Ok(())).await;
+        assert_ne!(seen.unwrap().as_str(), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_include_request_id() {
+        let middleware = RequestIdMiddleware::new();
+        let (_, res, _, result) = run(&middleware, "X-Request-Id: abc\r\n", Err("not found")).await;
+        res.error(result.unwrap_err()).await;
+        assert_eq!(res.status().await, Some(404));
+        assert_eq!(
+            res.get_header("X-Request-Id").await,
+            Some("abc".to_string())
+        );
+    }
+}
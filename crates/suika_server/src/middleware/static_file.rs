@@ -1,51 +1,142 @@
+use crate::conditional::{respond, ETag};
+use crate::diagnostics::log_debug;
+use crate::encoding::{negotiate, ContentEncoding};
 use crate::error::HttpError;
+use crate::middleware::directory_listing::{
+    prefers_json, read_entries, render_html, sort_entries, to_context, to_json, SortKey,
+};
 use crate::middleware::{Middleware, MiddlewareFuture, Next};
 use crate::request::Request;
 use crate::response::Response;
-use std::path::Path;
-use suika_mime::get_mime_type;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use suika_mime::get_mime_type_from_path;
+use suika_utils::percent_decode;
 
 async fn handle_static_file_request(
-    path: String,
-    cache_duration: u64,
+    path: &Path,
+    mime_type: &str,
+    encoding: Option<ContentEncoding>,
+    cache_control: &str,
+    content_etags: bool,
+    req: &Request,
     res: &mut Response,
 ) -> Result<(), HttpError> {
-    if Path::new(&path).exists() {
-        if let Err(e) = res.send_file(&path).await {
-            res.set_status(500).await;
-            res.body(format!("Internal Server Error: {}", e)).await;
-            return Err(HttpError::InternalServerError(format!(
-                "Internal Server Error: {}",
-                e
-            )));
-        } else {
-            let mime_type = Path::new(&path)
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(get_mime_type)
-                .unwrap_or_else(|| "application/octet-stream".to_string());
-            res.header("Content-Type", mime_type.as_str()).await;
-
-            res.header(
-                "Cache-Control",
-                &format!("public, max-age={}", cache_duration),
-            )
-            .await;
-
-            res.set_status(200).await;
+    let path = path.to_string_lossy();
+    if content_etags {
+        if let Ok(etag) = ETag::from_file(&path).await {
+            res.etag(&etag).await;
         }
-        Ok(())
-    } else {
-        println!("File not found: {}", path);
-        Err(HttpError::NotFound("File not found".to_string()))
     }
+
+    if let Err(e) = res.send_file_ranged_as(req, &path, mime_type).await {
+        res.set_status(500).await;
+        res.body(format!("Internal Server Error: {}", e)).await;
+        return Err(HttpError::InternalServerError(format!(
+            "Internal Server Error: {}",
+            e
+        )));
+    }
+    res.header("Cache-Control", cache_control).await;
+    if let Some(encoding) = encoding {
+        res.header("Content-Encoding", encoding.name()).await;
+    }
+    Ok(())
+}
+
+/// Responds with a listing of a directory's entries.
+async fn handle_directory_listing(
+    dir: &Path,
+    at_root: bool,
+    show_hidden: bool,
+    template: Option<&str>,
+    req: &Request,
+    res: &mut Response,
+) -> Result<(), HttpError> {
+    let mut entries = read_entries(dir, show_hidden)
+        .await
+        .map_err(|e| HttpError::InternalServerError(format!("Failed to read directory: {}", e)))?;
+    let sort = SortKey::from_query(req.query_param("sort"));
+    let descending = req.query_param("order") == Some("desc");
+    sort_entries(&mut entries, sort, descending);
+
+    res.set_status(200).await;
+    res.header("Cache-Control", "no-cache").await;
+    res.append_header("Vary", "Accept").await;
+    if prefers_json(req.header("Accept")) {
+        res.body_json(to_json(req.original_path(), &entries)).await;
+        return Ok(());
+    }
+
+    let context = to_context(req.original_path(), &entries, sort, descending, at_root);
+    match template {
+        Some(template) => res.render_template(template, &context).await?,
+        None => res.body(render_html(&context)).await,
+    }
+    res.header("Content-Type", "text/html; charset=utf-8").await;
+    Ok(())
+}
+
+/// How requests for dotfiles, such as `.env` or `.git/config`, are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotFiles {
+    /// Dotfiles are served like any other file.
+    Allow,
+    /// Requests for dotfiles are answered with `403 Forbidden`.
+    Deny,
+    /// Dotfiles are treated as if they did not exist.
+    Ignore,
+}
+
+/// The outcome of mapping a request path to the file system.
+enum Resolved {
+    File(PathBuf),
+    Directory(PathBuf, bool),
+    Redirect(String),
+    NotFound,
+    Forbidden,
 }
 
 /// A middleware component for serving static files.
+///
+/// Request paths are percent-decoded and resolved against the canonicalized root
+/// directory. Paths containing `..` segments, and paths that resolve outside the root
+/// through symbolic links, are answered with `403 Forbidden`. Dotfiles are ignored by
+/// default. Directories are served through their `index.html`, and requests for a
+/// directory without a trailing slash are redirected to the URL with one.
+///
+/// Directory listings are opt-in. When enabled, directories without an index file are
+/// answered with a listing of their entries, showing name, size, modification time
+/// and MIME type. The listing is sortable through the `sort` (`name`, `size`,
+/// `modified` or `type`) and `order` (`asc` or `desc`) query parameters, rendered as
+/// HTML or, if the `Accept` header prefers it, as JSON. Dotfiles are only listed when
+/// they are allowed.
+///
+/// Precompressed files can be served in place of the originals. When enabled, a file
+/// such as `app.js` is served from its `app.js.br` or `app.js.gz` sibling if one
+/// exists and the client accepts the encoding, with the `Content-Type` of the original
+/// file, a matching `Content-Encoding` and `Vary: Accept-Encoding`.
+///
+/// Only `GET` and `HEAD` requests are served. Requests for missing files are passed to
+/// the next middleware, or answered with `404 Not Found` when fall-through is disabled.
+///
+/// Files are served with `ETag` and `Last-Modified` validators, and conditional
+/// requests are answered with `304 Not Modified` or `412 Precondition Failed`.
+/// `Range` requests are answered with `206 Partial Content`, reading only the
+/// requested bytes from the file.
 pub struct StaticFileMiddleware {
     url_prefix: String,
     directory: String,
     cache_duration: u64,
+    content_etags: bool,
+    dotfiles: DotFiles,
+    index: Option<String>,
+    redirect: bool,
+    fallthrough: bool,
+    cache_control: HashMap<String, String>,
+    listing: bool,
+    listing_template: Option<String>,
+    precompressed: Vec<ContentEncoding>,
 }
 
 impl StaticFileMiddleware {
@@ -66,13 +157,271 @@ impl StaticFileMiddleware {
     /// ```
     pub fn new(url_prefix: &str, directory: &str, cache_duration: u64) -> Self {
         Self {
-            url_prefix: url_prefix.to_string(),
+            url_prefix: url_prefix.trim_end_matches('/').to_string(),
             directory: directory.to_string(),
             cache_duration,
+            content_etags: false,
+            dotfiles: DotFiles::Ignore,
+            index: Some("index.html".to_string()),
+            redirect: true,
+            fallthrough: true,
+            cache_control: HashMap::new(),
+            listing: false,
+            listing_template: None,
+            precompressed: Vec::new(),
+        }
+    }
+
+    /// Sets whether entity tags are strong tags computed from a hash of the file
+    /// content, rather than weak tags derived from its size and modification time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::StaticFileMiddleware;
+    ///
+    /// let static_file_middleware =
+    ///     StaticFileMiddleware::new("/static", "public", 3600).with_content_etags(true);
+    /// ```
+    pub fn with_content_etags(mut self, content_etags: bool) -> Self {
+        self.content_etags = content_etags;
+        self
+    }
+
+    /// Sets how requests for dotfiles are handled. Defaults to `DotFiles::Ignore`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::{DotFiles, StaticFileMiddleware};
+    ///
+    /// let static_file_middleware =
+    ///     StaticFileMiddleware::new("/static", "public", 3600).with_dotfiles(DotFiles::Deny);
+    /// ```
+    pub fn with_dotfiles(mut self, dotfiles: DotFiles) -> Self {
+        self.dotfiles = dotfiles;
+        self
+    }
+
+    /// Sets the file served for directory requests, or `None` to serve no index.
+    /// Defaults to `index.html`.
+    pub fn with_index(mut self, index: Option<&str>) -> Self {
+        self.index = index.map(str::to_string);
+        self
+    }
+
+    /// Sets whether requests for a directory without a trailing slash are redirected
+    /// with `301 Moved Permanently`. When disabled, the index file is served directly.
+    pub fn with_redirect(mut self, redirect: bool) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// Sets whether requests for missing files are passed to the next middleware.
+    /// When disabled, they are answered with `404 Not Found`.
+    pub fn with_fallthrough(mut self, fallthrough: bool) -> Self {
+        self.fallthrough = fallthrough;
+        self
+    }
+
+    /// Sets the `Cache-Control` header for files with the given extension, overriding
+    /// the `public, max-age` value derived from the cache duration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::StaticFileMiddleware;
+    ///
+    /// let static_file_middleware = StaticFileMiddleware::new("/static", "public", 3600)
+    ///     .with_cache_control("html", "no-cache")
+    ///     .with_cache_control("woff2", "public, max-age=31536000, immutable");
+    /// ```
+    pub fn with_cache_control(mut self, extension: &str, cache_control: &str) -> Self {
+        self.cache_control.insert(
+            extension.trim_start_matches('.').to_ascii_lowercase(),
+            cache_control.to_string(),
+        );
+        self
+    }
+
+    /// Sets whether directories without an index file are answered with a listing of
+    /// their entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::StaticFileMiddleware;
+    ///
+    /// let static_file_middleware = StaticFileMiddleware::new("/artifacts", "artifacts", 0)
+    ///     .with_index(None)
+    ///     .with_directory_listing(true);
+    /// ```
+    pub fn with_directory_listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// Sets the template used to render HTML directory listings instead of the
+    /// built-in page.
+    ///
+    /// The template is looked up in the server's template engine and rendered with
+    /// `path`, `parent` (absent at the root), `sort`, `order` and `entries`, a list of
+    /// objects with `name`, `href`, `is_dir`, `size`, `modified` and `mime_type`.
+    /// Since templates do not escape output, these values are HTML-escaped already.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::middleware::StaticFileMiddleware;
+    ///
+    /// let static_file_middleware = StaticFileMiddleware::new("/artifacts", "artifacts", 0)
+    ///     .with_directory_listing(true)
+    ///     .with_directory_listing_template("listing.html");
+    /// ```
+    pub fn with_directory_listing_template(mut self, template: &str) -> Self {
+        self.listing_template = Some(template.to_string());
+        self
+    }
+
+    /// Sets the encodings of precompressed siblings to look for, in order of
+    /// preference. Brotli files end in `.br` and gzip files in `.gz`; deflate has no
+    /// conventional extension and is ignored. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::encoding::ContentEncoding;
+    /// use suika_server::middleware::StaticFileMiddleware;
+    ///
+    /// let static_file_middleware = StaticFileMiddleware::new("/static", "dist", 3600)
+    ///     .with_precompressed(&[ContentEncoding::Brotli, ContentEncoding::Gzip]);
+    /// ```
+    pub fn with_precompressed(mut self, encodings: &[ContentEncoding]) -> Self {
+        self.precompressed = encodings
+            .iter()
+            .copied()
+            .filter(|encoding| encoding.extension().is_some())
+            .collect();
+        self
+    }
+
+    /// Finds the precompressed sibling of a file that best matches `Accept-Encoding`.
+    async fn precompressed_variant(
+        &self,
+        path: &Path,
+        accept_encoding: Option<&str>,
+    ) -> Option<(PathBuf, ContentEncoding)> {
+        let accept_encoding = accept_encoding?;
+        let root = tokio::fs::canonicalize(&self.directory).await.ok()?;
+
+        let mut available = Vec::new();
+        for encoding in &self.precompressed {
+            let mut sibling = path.as_os_str().to_owned();
+            sibling.push(format!(".{}", encoding.extension()?));
+            if let Ok(Some(sibling)) = confine(&root, PathBuf::from(sibling)).await {
+                if sibling.is_file() {
+                    available.push((sibling, *encoding));
+                }
+            }
+        }
+
+        let encodings: Vec<ContentEncoding> = available.iter().map(|(_, e)| *e).collect();
+        let encoding = negotiate(accept_encoding, &encodings)?;
+        available.into_iter().find(|(_, e)| *e == encoding)
+    }
+
+    /// Returns the `Cache-Control` header value for a file.
+    fn cache_control_for(&self, path: &Path) -> String {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.cache_control.get(&ext.to_ascii_lowercase()))
+            .cloned()
+            .unwrap_or_else(|| format!("public, max-age={}", self.cache_duration))
+    }
+
+    /// Maps the part of a request path after the URL prefix to a file below the root.
+    async fn resolve(&self, relative: &str, request_path: &str) -> Resolved {
+        let decoded = match percent_decode(relative) {
+            Ok(decoded) => decoded,
+            Err(_) => return Resolved::NotFound,
+        };
+
+        let mut segments = Vec::new();
+        for segment in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if segment == ".." || segment.contains(['\\', '\0']) {
+                return Resolved::Forbidden;
+            }
+            if !matches!(
+                Path::new(segment).components().next(),
+                Some(Component::Normal(_))
+            ) {
+                return Resolved::Forbidden;
+            }
+            if segment.starts_with('.') {
+                match self.dotfiles {
+                    DotFiles::Allow => {}
+                    DotFiles::Deny => return Resolved::Forbidden,
+                    DotFiles::Ignore => return Resolved::NotFound,
+                }
+            }
+            segments.push(segment);
+        }
+
+        let root = match tokio::fs::canonicalize(&self.directory).await {
+            Ok(root) => root,
+            Err(_) => return Resolved::NotFound,
+        };
+        let path = match confine(&root, segments.iter().collect()).await {
+            Ok(Some(path)) => path,
+            Ok(None) => return Resolved::NotFound,
+            Err(resolved) => return resolved,
+        };
+
+        let metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(_) => return Resolved::NotFound,
+        };
+        if metadata.is_file() {
+            return if decoded.ends_with('/') {
+                Resolved::NotFound
+            } else {
+                Resolved::File(path)
+            };
+        }
+
+        if !metadata.is_dir() || (self.index.is_none() && !self.listing) {
+            return Resolved::NotFound;
+        }
+        if self.redirect && !request_path.ends_with('/') {
+            return Resolved::Redirect(format!("{}/", request_path));
+        }
+        if let Some(index) = &self.index {
+            match confine(&root, path.join(index)).await {
+                Ok(Some(index)) if index.is_file() => return Resolved::File(index),
+                Ok(_) => {}
+                Err(resolved) => return resolved,
+            }
+        }
+        if self.listing {
+            Resolved::Directory(path, segments.is_empty())
+        } else {
+            Resolved::NotFound
         }
     }
 }
 
+/// Canonicalizes `path` relative to `root` and checks that it does not escape it.
+///
+/// Returns `Ok(None)` if the path does not exist and `Err(Resolved::Forbidden)` if it
+/// resolves outside the root.
+async fn confine(root: &Path, path: PathBuf) -> Result<Option<PathBuf>, Resolved> {
+    match tokio::fs::canonicalize(root.join(path)).await {
+        Ok(path) if path.starts_with(root) => Ok(Some(path)),
+        Ok(_) => Err(Resolved::Forbidden),
+        Err(_) => Ok(None),
+    }
+}
+
 impl Middleware for StaticFileMiddleware {
     /// Handles an incoming HTTP request by serving a static file if the request path matches the URL prefix.
     ///
@@ -91,20 +440,69 @@ impl Middleware for StaticFileMiddleware {
         res: &'a mut Response,
         mut next: Next<'a>,
     ) -> MiddlewareFuture<'a> {
-        let url_prefix = self.url_prefix.clone();
-        let directory = self.directory.clone();
-        let cache_duration = self.cache_duration;
-
         Box::pin(async move {
-            let path = if let Some(stripped_path) = req.path().strip_prefix(&url_prefix) {
-                format!("{}{}", directory, stripped_path)
-            } else {
-                return next.run(req, res).await;
+            let relative = match req.path().strip_prefix(&self.url_prefix) {
+                Some(relative)
+                    if (relative.is_empty() || relative.starts_with('/'))
+                        && matches!(req.method(), "GET" | "HEAD") =>
+                {
+                    relative.to_string()
+                }
+                _ => return next.run(req, res).await,
             };
 
-            match handle_static_file_request(path, cache_duration, res).await {
-                Ok(_) => Ok(()),
-                Err(_) => next.run(req, res).await,
+            match self.resolve(&relative, req.original_path()).await {
+                Resolved::File(path) => {
+                    let cache_control = self.cache_control_for(&path);
+                    let mime_type = get_mime_type_from_path(&path.to_string_lossy());
+                    let variant = self
+                        .precompressed_variant(&path, req.header("Accept-Encoding"))
+                        .await;
+                    if !self.precompressed.is_empty() {
+                        res.append_header("Vary", "Accept-Encoding").await;
+                    }
+                    let (path, encoding) = match variant {
+                        Some((sibling, encoding)) => (sibling, Some(encoding)),
+                        None => (path, None),
+                    };
+                    handle_static_file_request(
+                        &path,
+                        &mime_type,
+                        encoding,
+                        &cache_control,
+                        self.content_etags,
+                        req,
+                        res,
+                    )
+                    .await?;
+                    respond(req, res).await
+                }
+                Resolved::Directory(path, at_root) => {
+                    handle_directory_listing(
+                        &path,
+                        at_root,
+                        self.dotfiles == DotFiles::Allow,
+                        self.listing_template.as_deref(),
+                        req,
+                        res,
+                    )
+                    .await
+                }
+                Resolved::Redirect(location) => {
+                    res.set_status(301).await;
+                    res.header("Location", &location).await;
+                    res.body(format!("Redirecting to {}", location)).await;
+                    Ok(())
+                }
+                Resolved::Forbidden => Err(HttpError::Forbidden("Forbidden".to_string())),
+                Resolved::NotFound => {
+                    log_debug!(path = %req.path(), "Static file not found");
+                    if self.fallthrough {
+                        next.run(req, res).await
+                    } else {
+                        Err(HttpError::NotFound("File not found".to_string()))
+                    }
+                }
             }
         })
     }
@@ -155,7 +553,11 @@ mod tests {
     #[tokio::test]
     async fn test_static_file_middleware_serves_file() {
         // Create a temporary file to act as a static file
-        let mut tempfile = Builder::new().suffix(".txt").tempfile().unwrap();
+        let mut tempfile = Builder::new()
+            .prefix("static")
+            .suffix(".txt")
+            .tempfile()
+            .unwrap();
         tempfile.write_all(b"fake file data").unwrap();
         let file_dir = tempfile
             .path()
@@ -194,8 +596,8 @@ mod tests {
             Some(&"text/plain".to_string())
         );
         assert_eq!(
-            inner.body(),
-            &Some(Body::Binary(b"fake file data".to_vec()))
+            inner.body().clone().unwrap().into_bytes().await.unwrap(),
+            b"fake file data"
         );
 
         let next_called = *next_middleware.called.lock().await;
@@ -226,4 +628,459 @@ mod tests {
         let next_called = *next_middleware.called.lock().await;
         assert!(next_called);
     }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_revalidates() {
+        let mut tempfile = Builder::new()
+            .prefix("static")
+            .suffix(".css")
+            .tempfile()
+            .unwrap();
+        tempfile.write_all(b"body { color: red }").unwrap();
+        let file_dir = tempfile
+            .path()
+            .parent()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let file_name = tempfile.path().file_name().unwrap().to_str().unwrap();
+
+        let static_file_middleware =
+            StaticFileMiddleware::new("/static", &file_dir, 3600).with_content_etags(true);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockNextMiddleware::new())];
+
+        let serve = |headers: String| {
+            let static_file_middleware = &static_file_middleware;
+            let next = Next::new(middleware_stack.as_slice());
+            async move {
+                let mut req = Request::new(
+                    &format!("GET /static/{} HTTP/1.1\r\n{}\r\n", file_name, headers),
+                    Arc::new(Mutex::new(HashMap::new())),
+                )
+                .unwrap();
+                let mut res = Response::new(None);
+                let result = static_file_middleware
+                    .handle(&mut req, &mut res, next)
+                    .await;
+                (result, res)
+            }
+        };
+
+        let (result, res) = serve(String::new()).await;
+        assert!(result.is_ok());
+        let etag = res.get_header("ETag").await.unwrap();
+        assert_eq!(etag, ETag::from_content(b"body { color: red }").to_string());
+        let last_modified = res.get_header("Last-Modified").await.unwrap();
+
+        let (_, res) = serve(format!("If-None-Match: {}\r\n", etag)).await;
+        assert_eq!(res.status().await, Some(304));
+        assert_eq!(res.get_inner().await.body(), &None);
+
+        let (_, res) = serve(format!("If-Modified-Since: {}\r\n", last_modified)).await;
+        assert_eq!(res.status().await, Some(304));
+
+        let (result, res) = serve("If-Match: \"stale\"\r\n".to_string()).await;
+        assert!(matches!(result, Err(HttpError::PreconditionFailed(_))));
+        res.error(result.unwrap_err()).await;
+        let mut output = Vec::new();
+        res.send(&mut output).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 412 "));
+        assert!(!output.contains("Content-Length"));
+        assert!(!output.contains("Content-Type"));
+        assert!(output.ends_with("\r\n\r\nPrecondition failed"));
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_serves_ranges() {
+        let mut tempfile = Builder::new()
+            .prefix("static")
+            .suffix(".txt")
+            .tempfile()
+            .unwrap();
+        tempfile.write_all(b"0123456789").unwrap();
+        let file_dir = tempfile.path().parent().unwrap().to_str().unwrap();
+        let file_name = tempfile.path().file_name().unwrap().to_str().unwrap();
+
+        let mut req = Request::new(
+            &format!(
+                "GET /static/{} HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n",
+                file_name
+            ),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+
+        let static_file_middleware =
+            StaticFileMiddleware::new("/static", file_dir, 3600).with_content_etags(true);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockNextMiddleware::new())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        static_file_middleware
+            .handle(&mut req, &mut res, next)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().await, Some(206));
+        assert_eq!(
+            res.get_header("Content-Range").await,
+            Some("bytes 2-5/10".to_string())
+        );
+        assert_eq!(
+            res.get_header("ETag").await,
+            Some(ETag::from_content(b"0123456789").to_string())
+        );
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("public, max-age=3600".to_string())
+        );
+    }
+
+    /// Creates a site with `public/index.html`, `public/docs/index.html`,
+    /// `public/.env` and a `secret.txt` next to the root.
+    fn site() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        std::fs::create_dir_all(public.join("docs")).unwrap();
+        std::fs::write(public.join("index.html"), "home").unwrap();
+        std::fs::write(public.join("docs").join("index.html"), "docs").unwrap();
+        std::fs::write(public.join("app.js"), "app").unwrap();
+        std::fs::write(public.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    async fn serve(
+        static_file_middleware: &StaticFileMiddleware,
+        path: &str,
+    ) -> (Result<(), HttpError>, Response, bool) {
+        let mut req = Request::new(
+            &format!("GET {} HTTP/1.1\r\n\r\n", path),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let next_middleware = MockNextMiddleware::new();
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(next_middleware.clone())];
+        let next = Next::new(middleware_stack.as_slice());
+
+        let result = static_file_middleware
+            .handle(&mut req, &mut res, next)
+            .await;
+        let called = *next_middleware.called.lock().await;
+        (result, res, called)
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_confines_paths() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let static_file_middleware =
+            StaticFileMiddleware::new("/static", root.to_str().unwrap(), 0);
+
+        for path in [
+            "/static/../secret.txt",
+            "/static/docs/../../secret.txt",
+            "/static/%2e%2e/secret.txt",
+            "/static/%2E%2E%2Fsecret.txt",
+            "/static/..%5csecret.txt",
+        ] {
+            let (result, _, called) = serve(&static_file_middleware, path).await;
+            assert!(matches!(result, Err(HttpError::Forbidden(_))), "{}", path);
+            assert!(!called);
+        }
+
+        let (_, _, called) = serve(&static_file_middleware, "/staticapp.js").await;
+        assert!(called);
+
+        let (result, res, _) = serve(&static_file_middleware, "/static/docs/./../app.js").await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert_eq!(res.status().await, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_static_file_middleware_rejects_symlinks_outside_root() {
+        let dir = site();
+        let root = dir.path().join("public");
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("link.txt")).unwrap();
+        let static_file_middleware =
+            StaticFileMiddleware::new("/static", root.to_str().unwrap(), 0);
+
+        let (result, _, _) = serve(&static_file_middleware, "/static/link.txt").await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_dotfiles() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let root = root.to_str().unwrap();
+
+        let (result, _, called) = serve(
+            &StaticFileMiddleware::new("/static", root, 0),
+            "/static/.env",
+        )
+        .await;
+        assert!(result.is_ok());
+        assert!(called);
+
+        let deny = StaticFileMiddleware::new("/static", root, 0).with_dotfiles(DotFiles::Deny);
+        let (result, _, called) = serve(&deny, "/static/.env").await;
+        assert!(matches!(result, Err(HttpError::Forbidden(_))));
+        assert!(!called);
+
+        let allow = StaticFileMiddleware::new("/static", root, 0).with_dotfiles(DotFiles::Allow);
+        let (result, res, _) = serve(&allow, "/static/.env").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"SECRET=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_serves_index_files() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let root = root.to_str().unwrap();
+        let static_file_middleware = StaticFileMiddleware::new("/static", root, 0);
+
+        let (_, res, _) = serve(&static_file_middleware, "/static/").await;
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"home"
+        );
+
+        let (_, res, _) = serve(&static_file_middleware, "/static/docs").await;
+        assert_eq!(res.status().await, Some(301));
+        assert_eq!(
+            res.get_header("Location").await,
+            Some("/static/docs/".to_string())
+        );
+
+        let (_, res, _) = serve(&static_file_middleware, "/static").await;
+        assert_eq!(
+            res.get_header("Location").await,
+            Some("/static/".to_string())
+        );
+
+        let no_redirect = StaticFileMiddleware::new("/static", root, 0).with_redirect(false);
+        let (_, res, _) = serve(&no_redirect, "/static/docs").await;
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"docs"
+        );
+
+        let no_index = StaticFileMiddleware::new("/static", root, 0).with_index(None);
+        let (_, _, called) = serve(&no_index, "/static/docs/").await;
+        assert!(called);
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_fallthrough_and_cache_control() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let root = root.to_str().unwrap();
+
+        let strict = StaticFileMiddleware::new("/static", root, 60)
+            .with_fallthrough(false)
+            .with_cache_control(".JS", "no-cache");
+        let (result, _, called) = serve(&strict, "/static/missing.js").await;
+        assert!(matches!(result, Err(HttpError::NotFound(_))));
+        assert!(!called);
+
+        let (_, res, _) = serve(&strict, "/static/app.js").await;
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("no-cache".to_string())
+        );
+
+        let (_, res, _) = serve(&strict, "/static/index.html").await;
+        assert_eq!(
+            res.get_header("Cache-Control").await,
+            Some("public, max-age=60".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_lists_directories() {
+        let dir = site();
+        let root = dir.path().join("public");
+        std::fs::write(root.join("docs").join("b.txt"), "bbbbbb").unwrap();
+        std::fs::write(root.join("docs").join("a.css"), "a").unwrap();
+        std::fs::write(root.join("docs").join(".hidden"), "").unwrap();
+        std::fs::create_dir(root.join("docs").join("img")).unwrap();
+        let root = root.to_str().unwrap();
+
+        let (_, res, called) = serve(
+            &StaticFileMiddleware::new("/static", root, 0),
+            "/static/docs/",
+        )
+        .await;
+        assert!(!called);
+        assert_eq!(
+            res.take_body().await.unwrap().into_bytes().await.unwrap(),
+            b"docs"
+        );
+
+        let listing = StaticFileMiddleware::new("/static", root, 0)
+            .with_index(None)
+            .with_directory_listing(true);
+        let (result, res, _) = serve(&listing, "/static/docs/").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            res.get_header("Content-Type").await,
+            Some("text/html; charset=utf-8".to_string())
+        );
+        let html = match res.get_inner().await.body() {
+            Some(Body::Text(html)) => html.clone(),
+            body => panic!("Expected HTML body, got {:?}", body),
+        };
+        assert!(html.contains("<a href=\"/static/docs/a.css\">a.css</a>"));
+        assert!(html.contains("<a href=\"/static/docs/img/\">img/</a>"));
+        assert!(html.contains("<a href=\"/static/\">../</a>"));
+        assert!(!html.contains(".hidden"));
+        assert!(html.find("img/").unwrap() < html.find("a.css").unwrap());
+
+        let mut req = Request::new(
+            "GET /static/docs/?sort=size&order=desc HTTP/1.1\r\nAccept: application/json\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockNextMiddleware::new())];
+        let listing = listing.with_dotfiles(DotFiles::Allow);
+        listing
+            .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+            .await
+            .unwrap();
+        let json = match res.get_inner().await.body() {
+            Some(Body::Text(json)) => suika_json::parse_json(json).unwrap(),
+            body => panic!("Expected JSON body, got {:?}", body),
+        };
+        assert_eq!(
+            json.get("path").and_then(|p| p.as_str()),
+            Some("/static/docs/")
+        );
+        let entries = json.get("entries").and_then(|e| e.as_array()).unwrap();
+        let names: Vec<&str> = entries
+            .iter()
+            .filter_map(|e| e.get("name").and_then(|n| n.as_str()))
+            .collect();
+        assert_eq!(names, ["img", "b.txt", "index.html", "a.css", ".hidden"]);
+        assert_eq!(
+            entries[1].get("mime_type").and_then(|m| m.as_str()),
+            Some("text/plain")
+        );
+        assert_eq!(entries[1].get("size").and_then(|s| s.as_f64()), Some(6.0));
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_renders_listing_template() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let mut template_engine = suika_templates::TemplateEngine::new();
+        template_engine.add_template(
+            "listing.html",
+            "<ul><% for entry in entries %><li><%= entry.name %></li><% endfor %></ul>",
+        );
+
+        let mut req = Request::new(
+            "GET /static/docs/ HTTP/1.1\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(Some(Arc::new(template_engine)));
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+            vec![Arc::new(MockNextMiddleware::new())];
+        let listing = StaticFileMiddleware::new("/static", root.to_str().unwrap(), 0)
+            .with_index(Some("missing.html"))
+            .with_directory_listing(true)
+            .with_directory_listing_template("listing.html");
+        listing
+            .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.get_inner().await.body(),
+            &Some(Body::Text("<ul><li>index.html</li></ul>".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_static_file_middleware_serves_precompressed_files() {
+        let dir = site();
+        let root = dir.path().join("public");
+        let script = b"console.log('precompressed');";
+        std::fs::write(root.join("app.js"), script).unwrap();
+        std::fs::write(
+            root.join("app.js.br"),
+            ContentEncoding::Brotli.encode(script).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("app.js.gz"),
+            ContentEncoding::Gzip.encode(script).unwrap(),
+        )
+        .unwrap();
+        let static_file_middleware =
+            StaticFileMiddleware::new("/static", root.to_str().unwrap(), 0)
+                .with_precompressed(&[ContentEncoding::Brotli, ContentEncoding::Gzip]);
+
+        let serve = |accept_encoding: &'static str| {
+            let static_file_middleware = &static_file_middleware;
+            async move {
+                let mut req = Request::new(
+                    &format!(
+                        "GET /static/app.js HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+                        accept_encoding
+                    ),
+                    Arc::new(Mutex::new(HashMap::new())),
+                )
+                .unwrap();
+                let mut res = Response::new(None);
+                let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> =
+                    vec![Arc::new(MockNextMiddleware::new())];
+                static_file_middleware
+                    .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+                    .await
+                    .unwrap();
+                res
+            }
+        };
+
+        for (accept_encoding, encoding) in [
+            ("gzip, br", Some(ContentEncoding::Brotli)),
+            ("gzip", Some(ContentEncoding::Gzip)),
+            ("identity", None),
+        ] {
+            let res = serve(accept_encoding).await;
+            assert_eq!(
+                res.get_header("Content-Type").await,
+                Some("application/javascript".to_string())
+            );
+            assert_eq!(
+                res.get_header("Vary").await,
+                Some("Accept-Encoding".to_string())
+            );
+            assert_eq!(
+                res.get_header("Content-Encoding").await,
+                encoding.map(|e| e.name().to_string())
+            );
+            let body = res.take_body().await.unwrap().into_bytes().await.unwrap();
+            let decoded = match encoding {
+                Some(encoding) => encoding.decode(&body, 1024).unwrap(),
+                None => body,
+            };
+            assert_eq!(decoded, script);
+        }
+    }
 }
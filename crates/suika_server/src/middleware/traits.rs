@@ -1,6 +1,7 @@
 use crate::error::HttpError;
 use crate::request::Request;
 use crate::response::Response;
+use crate::state::StateKey;
 use futures::future::BoxFuture;
 use std::sync::Arc;
 
@@ -28,6 +29,14 @@ pub trait Middleware: Send + Sync {
         res: &'a mut Response,
         next: Next<'a>,
     ) -> MiddlewareFuture<'a>;
+
+    /// Returns the application state this middleware reads with `Request::state`.
+    ///
+    /// The server checks that all of it has been registered with `Server::with_state`
+    /// before it starts, so missing state fails at startup rather than on a request.
+    fn required_state(&self) -> Vec<StateKey> {
+        Vec::new()
+    }
 }
 
 /// Represents the next middleware in the stack.
@@ -50,7 +59,7 @@ impl<'a> Next<'a> {
     /// use suika_server::middleware::{Next, LoggerMiddleware, Middleware};
     /// use std::sync::Arc;
     ///
-    /// let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![Arc::new(LoggerMiddleware)];
+    /// let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![Arc::new(LoggerMiddleware::new())];
     /// let next = Next::new(middleware_stack.as_slice());
     /// ```
     pub fn new(stack: &'a [Arc<dyn Middleware + Send + Sync>]) -> Self {
@@ -0,0 +1,400 @@
+use crate::request::Request;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A block of IP addresses in CIDR notation, such as `10.0.0.0/8` or `2001:db8::/32`.
+///
+/// A plain address is treated as a block containing only that address. IPv4-mapped
+/// IPv6 addresses match IPv4 blocks.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::proxy::Cidr;
+///
+/// let private: Cidr = "10.0.0.0/8".parse().unwrap();
+/// assert!(private.contains("10.1.2.3".parse().unwrap()));
+/// assert!(!private.contains("192.168.0.1".parse().unwrap()));
+///
+/// let localhost: Cidr = "::1".parse().unwrap();
+/// assert!(localhost.contains("::1".parse().unwrap()));
+///
+/// assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Creates a block from a network address and prefix length.
+    ///
+    /// Host bits of `network` are cleared. Returns an error if `prefix_len` is longer
+    /// than the address.
+    pub fn new(network: IpAddr, prefix_len: u8) -> Result<Self, String> {
+        let max_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(format!(
+                "Invalid prefix length /{} for {}",
+                prefix_len, network
+            ));
+        }
+
+        let network = match network {
+            IpAddr::V4(addr) => IpAddr::V4((u32::from(addr) & mask_v4(prefix_len)).into()),
+            IpAddr::V6(addr) => IpAddr::V6((u128::from(addr) & mask_v6(prefix_len)).into()),
+        };
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+
+    /// Returns `true` if `addr` is inside the block.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+            addr => addr,
+        };
+        match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                u32::from(addr) & mask_v4(self.prefix_len) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                u128::from(addr) & mask_v6(self.prefix_len) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the IPv4 netmask for a prefix length.
+fn mask_v4(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+/// Returns the IPv6 netmask for a prefix length.
+fn mask_v6(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => (
+                addr,
+                Some(
+                    len.parse::<u8>()
+                        .map_err(|_| format!("Invalid prefix length in '{}'", s))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid IP address in '{}'", s))?;
+        let prefix_len = prefix_len.unwrap_or(match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        });
+        Cidr::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// A single hop reported by a forwarding header.
+#[derive(Debug, Default)]
+struct Hop {
+    addr: Option<IpAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// The set of reverse proxies whose forwarding headers are trusted.
+///
+/// When the peer that connected to the server is a trusted proxy, the client IP, scheme
+/// and host are resolved from the `Forwarded` header (RFC 7239) or, if it is absent,
+/// from `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host`. The address
+/// chain is walked from the nearest hop outwards and stops at the first address that is
+/// not a trusted proxy. Headers sent by untrusted peers are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::proxy::TrustedProxies;
+/// use suika_server::request::Request;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let proxies = TrustedProxies::new(&["10.0.0.0/8"]).unwrap();
+///
+/// let mut req = Request::new(
+///     "GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.7, 10.0.0.5\r\nX-Forwarded-Proto: https\r\n\r\n",
+///     Arc::new(Mutex::new(HashMap::new())),
+/// ).unwrap();
+/// req.set_peer_addr("10.0.0.1:40000".parse().unwrap());
+///
+/// proxies.apply(&mut req);
+/// assert_eq!(req.client_ip(), Some("203.0.113.7".parse().unwrap()));
+/// assert_eq!(req.scheme(), "https");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    proxies: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Creates a set of trusted proxies from addresses or CIDR blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxies` - The trusted addresses, e.g. `["127.0.0.1", "10.0.0.0/8"]`.
+    pub fn new(proxies: &[&str]) -> Result<Self, String> {
+        let proxies = proxies
+            .iter()
+            .map(|proxy| proxy.parse())
+            .collect::<Result<Vec<Cidr>, String>>()?;
+        Ok(Self { proxies })
+    }
+
+    /// Returns `true` if `addr` belongs to a trusted proxy.
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.proxies.iter().any(|cidr| cidr.contains(addr))
+    }
+
+    /// Resolves the client IP, scheme and host of a request from its forwarding headers.
+    ///
+    /// Does nothing unless the request's peer address is a trusted proxy.
+    pub fn apply(&self, req: &mut Request) {
+        let peer = match req.peer_addr() {
+            Some(peer) if self.is_trusted(peer.ip()) => peer.ip(),
+            _ => return,
+        };
+
+        let hops = match req.header("Forwarded") {
+            Some(forwarded) => parse_forwarded(forwarded),
+            None => parse_x_forwarded(req),
+        };
+
+        let mut client = peer;
+        let mut client_hop = None;
+        for hop in hops.iter().rev() {
+            match hop.addr {
+                Some(addr) => {
+                    client = addr;
+                    client_hop = Some(hop);
+                    if !self.is_trusted(addr) {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        req.set_client_ip(client);
+        if let Some(hop) = client_hop {
+            if let Some(proto) = &hop.proto {
+                req.set_scheme(proto);
+            }
+            if let Some(host) = &hop.host {
+                req.set_host(host);
+            }
+        }
+    }
+}
+
+/// Parses the elements of a `Forwarded` header.
+fn parse_forwarded(value: &str) -> Vec<Hop> {
+    value
+        .split(',')
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in element.split(';') {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.addr = parse_node(value),
+                    "proto" => hop.proto = parse_proto(value),
+                    "host" if !value.is_empty() => hop.host = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect()
+}
+
+/// Builds hops from the `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host`
+/// headers.
+///
+/// Protocol and host lists are matched to addresses by position when they have the
+/// same length; otherwise their last value applies to every hop.
+fn parse_x_forwarded(req: &Request) -> Vec<Hop> {
+    let list = |name: &str| -> Vec<String> {
+        req.header(name)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let addrs = list("X-Forwarded-For");
+    let protos = list("X-Forwarded-Proto");
+    let hosts = list("X-Forwarded-Host");
+
+    let pick = |values: &[String], index: usize| -> Option<String> {
+        if values.len() == addrs.len() {
+            values.get(index).cloned()
+        } else {
+            values.last().cloned()
+        }
+    };
+
+    addrs
+        .iter()
+        .enumerate()
+        .map(|(index, addr)| Hop {
+            addr: parse_node(addr),
+            proto: pick(&protos, index).and_then(|proto| parse_proto(&proto)),
+            host: pick(&hosts, index).filter(|host| !host.is_empty()),
+        })
+        .collect()
+}
+
+/// Parses a forwarded node such as `192.0.2.1`, `192.0.2.1:8080` or `[2001:db8::1]:80`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(addr) = node.parse() {
+        return Some(addr);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']').and_then(|(addr, _)| addr.parse().ok());
+    }
+    node.rsplit_once(':')
+        .and_then(|(addr, _)| addr.parse().ok())
+}
+
+/// Accepts only the `http` and `https` schemes.
+fn parse_proto(proto: &str) -> Option<String> {
+    let proto = proto.to_ascii_lowercase();
+    matches!(proto.as_str(), "http" | "https").then_some(proto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn request(headers: &str, peer: &str) -> Request {
+        let mut req = Request::new(
+            &format!("GET / HTTP/1.1\r\nHost: internal:8080\r\n{}\r\n", headers),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        req.set_peer_addr(peer.parse().unwrap());
+        req
+    }
+
+    fn ip(addr: &str) -> Option<IpAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr: Cidr = "192.168.1.77/24".parse().unwrap();
+        assert_eq!(cidr.to_string(), "192.168.1.0/24");
+        assert!(cidr.contains("192.168.1.255".parse().unwrap()));
+        assert!(!cidr.contains("192.168.2.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:192.168.1.5".parse().unwrap()));
+
+        let v6: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::1".parse().unwrap()));
+        assert!(!v6.contains("10.0.0.1".parse().unwrap()));
+
+        let all: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("8.8.8.8".parse().unwrap()));
+
+        assert!("not-an-ip".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/abc".parse::<Cidr>().is_err());
+        assert!("::/129".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_headers() {
+        let proxies = TrustedProxies::new(&["10.0.0.0/8"]).unwrap();
+        let mut req = request(
+            "X-Forwarded-For: 1.2.3.4\r\nX-Forwarded-Proto: https\r\n",
+            "198.51.100.9:5000",
+        );
+        proxies.apply(&mut req);
+
+        assert_eq!(req.client_ip(), ip("198.51.100.9"));
+        assert_eq!(req.scheme(), "http");
+        assert_eq!(req.host(), Some("internal:8080"));
+    }
+
+    #[test]
+    fn test_x_forwarded_chain() {
+        let proxies = TrustedProxies::new(&["10.0.0.0/8", "127.0.0.1"]).unwrap();
+
+        // The left-most entry is spoofed by the client and must not be used.
+        let mut req = request(
+            "X-Forwarded-For: 6.6.6.6, 203.0.113.7, 10.0.0.5\r\nX-Forwarded-Proto: https\r\nX-Forwarded-Host: example.com\r\n",
+            "127.0.0.1:40000",
+        );
+        proxies.apply(&mut req);
+
+        assert_eq!(req.client_ip(), ip("203.0.113.7"));
+        assert_eq!(req.scheme(), "https");
+        assert_eq!(req.host(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let proxies = TrustedProxies::new(&["10.0.0.0/8"]).unwrap();
+        let mut req = request(
+            "Forwarded: for=\"[2001:db8:cafe::17]:4711\";proto=https;host=example.com, for=10.0.0.3\r\nX-Forwarded-For: 1.2.3.4\r\n",
+            "10.0.0.1:40000",
+        );
+        proxies.apply(&mut req);
+
+        assert_eq!(req.client_ip(), ip("2001:db8:cafe::17"));
+        assert_eq!(req.scheme(), "https");
+        assert_eq!(req.host(), Some("example.com"));
+
+        // An obfuscated identifier stops the walk at the last known hop.
+        let mut req = request("Forwarded: for=_hidden, for=10.0.0.3\r\n", "10.0.0.1:40000");
+        proxies.apply(&mut req);
+        assert_eq!(req.client_ip(), ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn test_trusted_peer_without_headers() {
+        let proxies = TrustedProxies::new(&["10.0.0.0/8"]).unwrap();
+        let mut req = request("", "10.0.0.1:40000");
+        proxies.apply(&mut req);
+
+        assert_eq!(req.client_ip(), ip("10.0.0.1"));
+        assert_eq!(req.scheme(), "http");
+
+        assert!(TrustedProxies::new(&["10.0.0.0/8", "bogus"]).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The signature that starts a PROXY protocol v1 preamble.
+const V1_SIGNATURE: &[u8] = b"PROXY ";
+
+/// The maximum length of a PROXY protocol v1 preamble, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// The signature that starts a PROXY protocol v2 preamble.
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The length of the fixed part of a PROXY protocol v2 preamble.
+const V2_HEADER_LEN: usize = 16;
+
+/// How a listener treats HAProxy PROXY protocol preambles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocolMode {
+    /// Use a v1 or v2 preamble if the connection starts with one.
+    Accept,
+    /// Reject connections that do not start with a v1 or v2 preamble.
+    Require,
+}
+
+/// The addresses carried by a PROXY protocol preamble.
+///
+/// Both addresses are `None` for `UNKNOWN` (v1) and `LOCAL` (v2) preambles, and for
+/// address families other than TCP/UDP over IPv4 or IPv6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProxyHeader {
+    /// The address of the client that connected to the proxy.
+    pub source: Option<SocketAddr>,
+    /// The address the client connected to.
+    pub destination: Option<SocketAddr>,
+}
+
+/// Represents the errors that can occur while reading a PROXY protocol preamble.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProxyProtocolError {
+    /// More bytes are needed to parse the preamble.
+    Incomplete,
+    /// The connection did not start with a preamble but one is required.
+    Missing,
+    /// The preamble is malformed.
+    Invalid(String),
+    /// Reading from the connection failed or it closed before the preamble was complete.
+    Io(String),
+}
+
+impl fmt::Display for ProxyProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyProtocolError::Incomplete => write!(f, "Incomplete PROXY protocol preamble"),
+            ProxyProtocolError::Missing => write!(f, "Missing PROXY protocol preamble"),
+            ProxyProtocolError::Invalid(msg) => {
+                write!(f, "Invalid PROXY protocol preamble: {}", msg)
+            }
+            ProxyProtocolError::Io(msg) => {
+                write!(f, "Failed to read PROXY protocol preamble: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProxyProtocolError {}
+
+/// Parses a PROXY protocol v1 or v2 preamble at the start of `buf`.
+///
+/// Returns the header and the number of bytes it occupies, `Ok(None)` if `buf` does not
+/// start with a preamble, or `ProxyProtocolError::Incomplete` if more bytes are needed
+/// to decide.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::proxy_protocol::{parse, ProxyProtocolError};
+///
+/// let data = b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n";
+/// let (header, len) = parse(data).unwrap().unwrap();
+/// assert_eq!(header.source, Some("203.0.113.7:56324".parse().unwrap()));
+/// assert_eq!(&data[len..], b"GET / HTTP/1.1\r\n\r\n");
+///
+/// assert_eq!(parse(b"GET / HTTP/1.1\r\n\r\n"), Ok(None));
+/// assert_eq!(parse(b"PROXY TCP4"), Err(ProxyProtocolError::Incomplete));
+/// ```
+pub fn parse(buf: &[u8]) -> Result<Option<(ProxyHeader, usize)>, ProxyProtocolError> {
+    if starts_with_signature(buf, V1_SIGNATURE)? {
+        parse_v1(buf).map(Some)
+    } else if starts_with_signature(buf, V2_SIGNATURE)? {
+        parse_v2(buf).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns whether `buf` starts with `signature`, or `Incomplete` if `buf` is a proper
+/// prefix of it.
+fn starts_with_signature(buf: &[u8], signature: &[u8]) -> Result<bool, ProxyProtocolError> {
+    if buf.len() < signature.len() {
+        if signature.starts_with(buf) {
+            Err(ProxyProtocolError::Incomplete)
+        } else {
+            Ok(false)
+        }
+    } else {
+        Ok(buf.starts_with(signature))
+    }
+}
+
+/// Parses a text (v1) preamble.
+fn parse_v1(buf: &[u8]) -> Result<(ProxyHeader, usize), ProxyProtocolError> {
+    let invalid = |msg: &str| ProxyProtocolError::Invalid(msg.to_string());
+
+    let end = match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) if end + 2 <= V1_MAX_LEN => end,
+        Some(_) => return Err(invalid("v1 preamble is too long")),
+        None if buf.len() >= V1_MAX_LEN => return Err(invalid("v1 preamble is too long")),
+        None => return Err(ProxyProtocolError::Incomplete),
+    };
+    let line = std::str::from_utf8(&buf[..end]).map_err(|_| invalid("v1 preamble is not ASCII"))?;
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    let header = match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => ProxyHeader::default(),
+        ["PROXY", family @ ("TCP4" | "TCP6"), src, dst, src_port, dst_port] => {
+            let parse_ip = |addr: &str| -> Result<IpAddr, ProxyProtocolError> {
+                let ip: IpAddr = addr.parse().map_err(|_| invalid("invalid address"))?;
+                if ip.is_ipv4() != (*family == "TCP4") {
+                    return Err(invalid("address does not match the protocol family"));
+                }
+                Ok(ip)
+            };
+            let parse_port = |port: &str| -> Result<u16, ProxyProtocolError> {
+                if port.len() > 1 && port.starts_with('0') {
+                    return Err(invalid("port has leading zeros"));
+                }
+                port.parse().map_err(|_| invalid("invalid port"))
+            };
+            ProxyHeader {
+                source: Some(SocketAddr::new(parse_ip(src)?, parse_port(src_port)?)),
+                destination: Some(SocketAddr::new(parse_ip(dst)?, parse_port(dst_port)?)),
+            }
+        }
+        _ => return Err(invalid("malformed v1 preamble")),
+    };
+
+    Ok((header, end + 2))
+}
+
+/// Parses a binary (v2) preamble.
+fn parse_v2(buf: &[u8]) -> Result<(ProxyHeader, usize), ProxyProtocolError> {
+    let invalid = |msg: &str| ProxyProtocolError::Invalid(msg.to_string());
+
+    if buf.len() < V2_HEADER_LEN {
+        return Err(ProxyProtocolError::Incomplete);
+    }
+
+    let version = buf[12] >> 4;
+    let command = buf[12] & 0x0f;
+    let family = buf[13];
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+
+    if version != 2 {
+        return Err(invalid("unsupported version"));
+    }
+    if command > 1 {
+        return Err(invalid("unsupported command"));
+    }
+    if buf.len() < V2_HEADER_LEN + len {
+        return Err(ProxyProtocolError::Incomplete);
+    }
+    let addresses = &buf[V2_HEADER_LEN..V2_HEADER_LEN + len];
+
+    // LOCAL connections (health checks from the proxy itself) keep the socket addresses.
+    let header = if command == 0 {
+        ProxyHeader::default()
+    } else {
+        match family >> 4 {
+            0x1 => {
+                if addresses.len() < 12 {
+                    return Err(invalid("IPv4 address block is too short"));
+                }
+                let ip = |at: usize| {
+                    IpAddr::V4(Ipv4Addr::new(
+                        addresses[at],
+                        addresses[at + 1],
+                        addresses[at + 2],
+                        addresses[at + 3],
+                    ))
+                };
+                let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+                ProxyHeader {
+                    source: Some(SocketAddr::new(ip(0), port(8))),
+                    destination: Some(SocketAddr::new(ip(4), port(10))),
+                }
+            }
+            0x2 => {
+                if addresses.len() < 36 {
+                    return Err(invalid("IPv6 address block is too short"));
+                }
+                let ip = |at: usize| {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&addresses[at..at + 16]);
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                };
+                let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+                ProxyHeader {
+                    source: Some(SocketAddr::new(ip(0), port(32))),
+                    destination: Some(SocketAddr::new(ip(16), port(34))),
+                }
+            }
+            0x0 | 0x3 => ProxyHeader::default(),
+            _ => return Err(invalid("unsupported address family")),
+        }
+    };
+
+    Ok((header, V2_HEADER_LEN + len))
+}
+
+/// Reads a PROXY protocol preamble from the start of a connection.
+///
+/// Bytes read past the preamble are left in `buf`, which must be empty on entry, so that
+/// they can be parsed as the start of the HTTP request.
+///
+/// # Arguments
+///
+/// * `stream` - The accepted connection.
+/// * `mode` - Whether the preamble is optional or required.
+/// * `buf` - Receives any bytes read after the preamble.
+pub async fn read_preamble<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    mode: ProxyProtocolMode,
+    buf: &mut Vec<u8>,
+) -> Result<Option<ProxyHeader>, ProxyProtocolError> {
+    let mut chunk = [0u8; 1024];
+    loop {
+        match parse(buf) {
+            Ok(Some((header, len))) => {
+                buf.drain(..len);
+                return Ok(Some(header));
+            }
+            Ok(None) if mode == ProxyProtocolMode::Require => {
+                return Err(ProxyProtocolError::Missing)
+            }
+            Ok(None) => return Ok(None),
+            Err(ProxyProtocolError::Incomplete) => {}
+            Err(e) => return Err(e),
+        }
+
+        let size = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| ProxyProtocolError::Io(e.to_string()))?;
+        if size == 0 {
+            return if buf.is_empty() && mode == ProxyProtocolMode::Accept {
+                Ok(None)
+            } else {
+                Err(ProxyProtocolError::Io(
+                    "connection closed before the preamble was complete".to_string(),
+                ))
+            };
+        }
+        buf.extend_from_slice(&chunk[..size]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut data = V2_SIGNATURE.to_vec();
+        data.push(0x20 | command);
+        data.push(family);
+        data.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        data.extend_from_slice(addresses);
+        data
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let (header, len) = parse(b"PROXY TCP6 2001:db8::1 ::1 4000 8080\r\nrest")
+            .unwrap()
+            .unwrap();
+        assert_eq!(header.source, Some("[2001:db8::1]:4000".parse().unwrap()));
+        assert_eq!(header.destination, Some("[::1]:8080".parse().unwrap()));
+        assert_eq!(len, 38);
+
+        let (header, _) = parse(b"PROXY UNKNOWN\r\n").unwrap().unwrap();
+        assert_eq!(header, ProxyHeader::default());
+    }
+
+    #[test]
+    fn test_parse_v1_invalid() {
+        for data in [
+            &b"PROXY TCP4 1.2.3.4 5.6.7.8 1 2 3\r\n"[..],
+            b"PROXY TCP4 ::1 5.6.7.8 1 2\r\n",
+            b"PROXY TCP4 1.2.3.4 5.6.7.8 99999 2\r\n",
+            b"PROXY TCP4 1.2.3.4 5.6.7.8 01 2\r\n",
+            b"PROXY UDP4 1.2.3.4 5.6.7.8 1 2\r\n",
+            b"PROXY  TCP4 1.2.3.4 5.6.7.8 1 2\r\n",
+        ] {
+            assert!(
+                matches!(parse(data), Err(ProxyProtocolError::Invalid(_))),
+                "{}",
+                String::from_utf8_lossy(data)
+            );
+        }
+
+        let long = [b"PROXY ".as_slice(), &[b'A'; 120]].concat();
+        assert!(matches!(parse(&long), Err(ProxyProtocolError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut addresses = vec![203, 0, 113, 7, 10, 0, 0, 1];
+        addresses.extend_from_slice(&56324u16.to_be_bytes());
+        addresses.extend_from_slice(&443u16.to_be_bytes());
+        // A trailing TLV is skipped.
+        addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]);
+        let mut data = v2(1, 0x11, &addresses);
+        data.extend_from_slice(b"GET /");
+
+        let (header, len) = parse(&data).unwrap().unwrap();
+        assert_eq!(header.source, Some("203.0.113.7:56324".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().unwrap()));
+        assert_eq!(&data[len..], b"GET /");
+
+        let mut addresses = vec![0u8; 36];
+        addresses[15] = 1;
+        addresses[31] = 2;
+        addresses[32..34].copy_from_slice(&1234u16.to_be_bytes());
+        addresses[34..36].copy_from_slice(&80u16.to_be_bytes());
+        let (header, _) = parse(&v2(1, 0x21, &addresses)).unwrap().unwrap();
+        assert_eq!(header.source, Some("[::1]:1234".parse().unwrap()));
+        assert_eq!(header.destination, Some("[::2]:80".parse().unwrap()));
+
+        let (header, _) = parse(&v2(0, 0x00, &[])).unwrap().unwrap();
+        assert_eq!(header, ProxyHeader::default());
+    }
+
+    #[test]
+    fn test_parse_v2_invalid_and_incomplete() {
+        let data = v2(1, 0x11, &[1, 2, 3, 4, 5, 6, 7, 8, 0, 1, 0, 2]);
+        assert_eq!(parse(&data[..5]), Err(ProxyProtocolError::Incomplete));
+        assert_eq!(parse(&data[..20]), Err(ProxyProtocolError::Incomplete));
+
+        let mut bad_version = data.clone();
+        bad_version[12] = 0x11;
+        assert!(matches!(
+            parse(&bad_version),
+            Err(ProxyProtocolError::Invalid(_))
+        ));
+
+        assert!(matches!(
+            parse(&v2(1, 0x11, &[1, 2, 3])),
+            Err(ProxyProtocolError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&v2(2, 0x11, &[])),
+            Err(ProxyProtocolError::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_preamble_modes() {
+        let mut stream: &[u8] = b"PROXY TCP4 1.2.3.4 5.6.7.8 1000 80\r\nGET / HTTP/1.1\r\n\r\n";
+        let mut buf = Vec::new();
+        let header = read_preamble(&mut stream, ProxyProtocolMode::Require, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(
+            header.unwrap().source,
+            Some("1.2.3.4:1000".parse().unwrap())
+        );
+        assert_eq!(buf, b"GET / HTTP/1.1\r\n\r\n");
+
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\n\r\n";
+        let mut buf = Vec::new();
+        let header = read_preamble(&mut stream, ProxyProtocolMode::Accept, &mut buf)
+            .await
+            .unwrap();
+        assert!(header.is_none());
+        assert_eq!(buf, b"GET / HTTP/1.1\r\n\r\n");
+
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\n\r\n";
+        let mut buf = Vec::new();
+        assert_eq!(
+            read_preamble(&mut stream, ProxyProtocolMode::Require, &mut buf).await,
+            Err(ProxyProtocolError::Missing)
+        );
+
+        let mut stream: &[u8] = b"PROXY TCP4 1.2.3.4";
+        let mut buf = Vec::new();
+        assert!(matches!(
+            read_preamble(&mut stream, ProxyProtocolMode::Accept, &mut buf).await,
+            Err(ProxyProtocolError::Io(_))
+        ));
+    }
+}
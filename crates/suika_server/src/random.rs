@@ -0,0 +1,104 @@
+/// Fills a buffer of the given length with cryptographically secure random bytes.
+///
+/// # Panics
+///
+/// Panics if the operating system's random number generator is unavailable.
+pub(crate) fn random_bytes(len: usize) -> Vec<u8> {
+    let mut buffer = vec![0u8; len];
+    getrandom::getrandom(&mut buffer).expect("Failed to obtain random bytes");
+    buffer
+}
+
+/// Generates a random token of `len` bytes encoded as lowercase hex.
+pub(crate) fn random_hex(len: usize) -> String {
+    random_bytes(len)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The Crockford base32 alphabet used by ULIDs.
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a random (version 4) UUID in its hyphenated lowercase form.
+pub(crate) fn uuid_v4() -> String {
+    let mut bytes = random_bytes(16);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Generates a ULID: a 48-bit millisecond timestamp followed by 80 random bits,
+/// encoded as 26 Crockford base32 characters, so IDs sort by creation time.
+pub(crate) fn ulid() -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    let value = random_bytes(10)
+        .iter()
+        .fold(u128::from(millis & 0xffff_ffff_ffff), |acc, byte| {
+            (acc << 8) | u128::from(*byte)
+        });
+    (0..26)
+        .map(|i| CROCKFORD_BASE32[((value >> (125 - 5 * i)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Compares two byte slices in constant time with respect to their contents.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_hex() {
+        let token = random_hex(16);
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, random_hex(16));
+    }
+
+    #[test]
+    fn test_uuid_v4() {
+        let uuid = uuid_v4();
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(groups[3].as_bytes()[0], b'8' | b'9' | b'a' | b'b'));
+        assert_ne!(uuid, uuid_v4());
+    }
+
+    #[test]
+    fn test_ulid() {
+        let first = ulid();
+        assert_eq!(first.len(), 26);
+        assert!(first.bytes().all(|b| CROCKFORD_BASE32.contains(&b)));
+        assert!(first.as_bytes()[0] <= b'7');
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(ulid()[..10] > first[..10]);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"tok"));
+    }
+}
@@ -0,0 +1,203 @@
+/// The maximum number of ranges honoured in a single `Range` header.
+const MAX_RANGES: usize = 32;
+
+/// An inclusive range of byte offsets within a representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    /// The offset of the first byte.
+    pub start: u64,
+    /// The offset of the last byte.
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Returns the number of bytes in the range.
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Formats the range as a `Content-Range` header value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::range::ByteRange;
+    ///
+    /// let range = ByteRange { start: 0, end: 499 };
+    /// assert_eq!(range.content_range(1000), "bytes 0-499/1000");
+    /// ```
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, complete_length)
+    }
+}
+
+/// The result of interpreting a `Range` header against a representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ranges {
+    /// The header is malformed, uses another unit or asks for too many ranges, and
+    /// must be ignored: the full representation is sent.
+    Ignore,
+    /// The ranges to send, sorted and with overlapping ranges merged.
+    Satisfiable(Vec<ByteRange>),
+    /// None of the ranges overlap the representation (`416 Range Not Satisfiable`).
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header for a representation of `length` bytes.
+///
+/// Supports `first-last`, `first-` and `-suffix` byte ranges.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::range::{parse_range, ByteRange, Ranges};
+///
+/// assert_eq!(
+///     parse_range("bytes=0-99, -100", 1000),
+///     Ranges::Satisfiable(vec![
+///         ByteRange { start: 0, end: 99 },
+///         ByteRange { start: 900, end: 999 },
+///     ])
+/// );
+/// assert_eq!(parse_range("bytes=2000-", 1000), Ranges::Unsatisfiable);
+/// assert_eq!(parse_range("items=0-1", 1000), Ranges::Ignore);
+/// ```
+pub fn parse_range(header: &str, length: u64) -> Ranges {
+    let header = header.trim();
+    let specs = match header.split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return Ranges::Ignore,
+    };
+
+    let mut ranges = Vec::new();
+    let mut count = 0;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        count += 1;
+        if count > MAX_RANGES {
+            return Ranges::Ignore;
+        }
+
+        let (first, last) = match spec.split_once('-') {
+            Some(parts) => parts,
+            None => return Ranges::Ignore,
+        };
+        let (first, last) = (first.trim(), last.trim());
+
+        if first.is_empty() {
+            let suffix: u64 = match last.parse() {
+                Ok(suffix) => suffix,
+                Err(_) => return Ranges::Ignore,
+            };
+            if suffix > 0 && length > 0 {
+                ranges.push(ByteRange {
+                    start: length - suffix.min(length),
+                    end: length - 1,
+                });
+            }
+            continue;
+        }
+
+        let start: u64 = match first.parse() {
+            Ok(start) => start,
+            Err(_) => return Ranges::Ignore,
+        };
+        let end = if last.is_empty() {
+            None
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => Some(end),
+                _ => return Ranges::Ignore,
+            }
+        };
+        if start < length {
+            ranges.push(ByteRange {
+                start,
+                end: end.unwrap_or(u64::MAX).min(length - 1),
+            });
+        }
+    }
+
+    if count == 0 {
+        return Ranges::Ignore;
+    }
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    Ranges::Satisfiable(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn test_single_ranges() {
+        assert_eq!(
+            parse_range("bytes=0-499", 1000),
+            Ranges::Satisfiable(vec![range(0, 499)])
+        );
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            Ranges::Satisfiable(vec![range(500, 999)])
+        );
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            Ranges::Satisfiable(vec![range(900, 999)])
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 1000),
+            Ranges::Satisfiable(vec![range(0, 999)])
+        );
+        assert_eq!(range(10, 19).length(), 10);
+    }
+
+    #[test]
+    fn test_multiple_ranges_are_merged() {
+        assert_eq!(
+            parse_range("bytes=500-599, 0-99, 50-149, 150-199", 1000),
+            Ranges::Satisfiable(vec![range(0, 199), range(500, 599)])
+        );
+        assert_eq!(
+            parse_range("bytes=0-9, 5000-6000", 1000),
+            Ranges::Satisfiable(vec![range(0, 9)])
+        );
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), Ranges::Unsatisfiable);
+    }
+
+    #[test]
+    fn test_ignored_ranges() {
+        assert_eq!(parse_range("bytes=", 1000), Ranges::Ignore);
+        assert_eq!(parse_range("bytes=5-1", 1000), Ranges::Ignore);
+        assert_eq!(parse_range("bytes=a-b", 1000), Ranges::Ignore);
+        assert_eq!(parse_range("bytes 0-1", 1000), Ranges::Ignore);
+        let many = (0..40)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(
+            parse_range(&format!("bytes={}", many), 1000),
+            Ranges::Ignore
+        );
+    }
+}
@@ -1,5 +1,10 @@
+use crate::auth::Principal;
+use crate::extensions::Extensions;
+use crate::jwt::Claims;
+use crate::state::AppState;
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{Arc, Mutex};
 use suika_json::JsonValue;
 use suika_utils::parse_query_string;
@@ -8,14 +13,25 @@ use suika_utils::parse_query_string;
 #[derive(Debug, Clone)]
 pub struct Request {
     method: String,
+    target: String,
+    version: String,
     path: String,
     original_path: String,
     headers: HashMap<String, String>,
     query_params: HashMap<String, String>,
     body: Option<String>,
+    raw_body: Vec<u8>,
     json_body: Option<JsonValue>,
     form_data: Option<HashMap<String, String>>,
+    cookies: HashMap<String, String>,
     params: HashMap<String, String>,
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    client_ip: Option<IpAddr>,
+    scheme: Option<String>,
+    host: Option<String>,
+    extensions: Extensions,
+    state: Option<Arc<AppState>>,
     modules: Arc<Mutex<HashMap<String, Arc<dyn std::any::Any + Send + Sync>>>>,
 }
 
@@ -67,6 +83,8 @@ impl Request {
         }
         let method = request_line_parts[0].to_string();
         let path_with_query = request_line_parts[1].to_string();
+        let target = path_with_query.clone();
+        let version = request_line_parts[2].to_string();
 
         let (path, query_params) = if let Some(query_start) = path_with_query.find('?') {
             let path = path_with_query[..query_start].to_string();
@@ -107,49 +125,80 @@ impl Request {
             body_content.truncate(body_content.len() - 2);
         }
 
-        let json_body = if headers
-            .get("Content-Type")
-            .map(|v| v == "application/json")
-            .unwrap_or(false)
-        {
-            match suika_json::parse_json(&body_content) {
-                Ok(json) => Some(json),
-                Err(_) => None,
-            }
-        } else {
-            None
-        };
-
-        let form_data = if headers
-            .get("Content-Type")
-            .map(|v| v == "application/x-www-form-urlencoded")
-            .unwrap_or(false)
-        {
-            let mut form_data = parse_query_string(&body_content);
-            form_data.iter_mut().for_each(|(_, v)| {
-                *v = v.trim_end_matches('\0').to_string();
-            });
-            Some(form_data)
-        } else {
-            None
-        };
+        let cookies = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("Cookie"))
+            .map(|(_, v)| parse_cookies(v))
+            .unwrap_or_default();
 
-        Ok(Request {
+        let mut request = Request {
             method,
+            target,
+            version,
             path: path.clone(),
             original_path: path,
             headers,
             query_params,
-            body: if !body_content.is_empty() {
-                Some(body_content)
-            } else {
-                None
-            },
-            json_body,
-            form_data,
+            body: None,
+            raw_body: Vec::new(),
+            json_body: None,
+            form_data: None,
+            cookies,
             params: HashMap::new(),
+            peer_addr: None,
+            local_addr: None,
+            client_ip: None,
+            scheme: None,
+            host: None,
+            extensions: Extensions::new(),
+            state: None,
             modules,
-        })
+        };
+        request.set_body_bytes(body_content.into_bytes());
+        Ok(request)
+    }
+
+    /// Creates a new `Request` from the raw bytes read from a connection.
+    ///
+    /// Unlike `Request::new`, the body is kept byte for byte, so binary and compressed
+    /// bodies survive intact.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The raw HTTP request, including the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `IoResult` containing an error if the request head is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::sync::{Arc,Mutex};
+    /// use std::collections::HashMap;
+    ///
+    /// let request = Request::from_bytes(
+    ///     b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n\x1f\x8b\x00\xff",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(request.path(), "/upload");
+    /// assert_eq!(request.body_bytes(), &[0x1f, 0x8b, 0x00, 0xff]);
+    /// ```
+    pub fn from_bytes(
+        data: &[u8],
+        modules: Arc<Mutex<HashMap<String, Arc<dyn std::any::Any + Send + Sync>>>>,
+    ) -> IoResult<Request> {
+        match data.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => {
+                let head = String::from_utf8_lossy(&data[..end + 4]);
+                let mut request = Request::new(&head, modules)?;
+                request.set_body_bytes(data[end + 4..].to_vec());
+                Ok(request)
+            }
+            None => Request::new(&String::from_utf8_lossy(data), modules),
+        }
     }
 
     /// Retrieves a module from the request context by name.
@@ -195,6 +244,59 @@ impl Request {
         modules.get(name)?.clone().downcast::<T>().ok()
     }
 
+    /// Sets the application state shared by all requests.
+    ///
+    /// This is called by the server with the state registered through
+    /// `Server::with_state`.
+    pub fn set_state(&mut self, state: Arc<AppState>) {
+        self.state = Some(state);
+    }
+
+    /// Returns the application state of type `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no state of type `T` was registered with `Server::with_state`. Declare
+    /// the state a router needs with `Router::requires_state` so the server reports it
+    /// at startup instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use suika_server::state::AppState;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Config {
+    ///     site_name: String,
+    /// }
+    ///
+    /// let mut state = AppState::new();
+    /// state.insert(Config { site_name: "Suika".to_string() });
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    /// request.set_state(Arc::new(state));
+    ///
+    /// assert_eq!(request.state::<Config>().site_name, "Suika");
+    /// ```
+    pub fn state<T: Send + Sync + 'static>(&self) -> &T {
+        self.try_state().unwrap_or_else(|| {
+            panic!(
+                "No application state of type `{}`; register it with `Server::with_state`",
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
+    /// Returns the application state of type `T`, or `None` if it was not registered.
+    pub fn try_state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.as_ref()?.get()
+    }
+
     /// Returns the HTTP method of the request.
     ///
     /// # Examples
@@ -254,8 +356,37 @@ impl Request {
         &self.original_path
     }
 
+    /// Returns the request target as sent in the request line, including the query
+    /// string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::sync::{Arc,Mutex};
+    /// use std::collections::HashMap;
+    ///
+    /// let request = Request::new(
+    ///     "GET /path?name=value HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(request.target(), "/path?name=value");
+    /// assert_eq!(request.version(), "HTTP/1.1");
+    /// ```
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the HTTP version from the request line, such as `HTTP/1.1`.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
     /// Returns the value of the specified header.
     ///
+    /// Header names are matched case-insensitively, with an exact match taking precedence.
+    ///
     /// # Arguments
     ///
     /// * `key` - A string slice that holds the header name.
@@ -273,9 +404,43 @@ impl Request {
     /// ).unwrap();
     ///
     /// assert_eq!(request.header("Host"), Some("example.com"));
+    /// assert_eq!(request.header("host"), Some("example.com"));
     /// ```
     pub fn header(&self, key: &str) -> Option<&str> {
-        self.headers.get(key).map(|s| s.as_str())
+        self.headers
+            .get(key)
+            .or_else(|| {
+                self.headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(|(_, v)| v)
+            })
+            .map(|s| s.as_str())
+    }
+
+    /// Returns the value of the specified cookie, as sent in the `Cookie` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A string slice that holds the cookie name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::sync::{Arc,Mutex};
+    /// use std::collections::HashMap;
+    ///
+    /// let request = Request::new(
+    ///     "GET /path HTTP/1.1\r\nCookie: session=abc; theme=dark\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(request.cookie("theme"), Some("dark"));
+    /// assert_eq!(request.cookie("missing"), None);
+    /// ```
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(|s| s.as_str())
     }
 
     /// Returns the value of the specified query parameter.
@@ -322,6 +487,58 @@ impl Request {
         self.body.as_deref()
     }
 
+    /// Returns the raw bytes of the request body.
+    pub fn body_bytes(&self) -> &[u8] {
+        &self.raw_body
+    }
+
+    /// Replaces the request body, re-parsing JSON and form data according to the
+    /// `Content-Type` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - The new raw body.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::sync::{Arc,Mutex};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut request = Request::new(
+    ///     "POST /path HTTP/1.1\r\nContent-Type: application/json\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// request.set_body_bytes(b"{\"key\":\"value\"}".to_vec());
+    /// assert_eq!(request.body(), Some("{\"key\":\"value\"}"));
+    /// assert!(request.json_body().is_some());
+    /// ```
+    pub fn set_body_bytes(&mut self, body: Vec<u8>) {
+        let text = String::from_utf8_lossy(&body).into_owned();
+        let content_type = self.headers.get("Content-Type").map(|v| v.as_str());
+
+        self.json_body = if content_type == Some("application/json") {
+            suika_json::parse_json(&text).ok()
+        } else {
+            None
+        };
+
+        self.form_data = if content_type == Some("application/x-www-form-urlencoded") {
+            let mut form_data = parse_query_string(&text);
+            form_data.iter_mut().for_each(|(_, v)| {
+                *v = v.trim_end_matches('\0').to_string();
+            });
+            Some(form_data)
+        } else {
+            None
+        };
+
+        self.body = if !text.is_empty() { Some(text) } else { None };
+        self.raw_body = body;
+    }
+
     /// Sets the JSON body of the request.
     ///
     /// # Arguments
@@ -440,6 +657,228 @@ impl Request {
         self.params.get(key).map(|s| s.as_str())
     }
 
+    /// Attaches an authenticated principal to the request.
+    ///
+    /// This is normally called by authentication middleware. The principal is stored in
+    /// the request's extensions.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - The authenticated principal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::auth::Principal;
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert!(request.principal().is_none());
+    ///
+    /// request.set_principal(Principal::new("alice"));
+    /// assert_eq!(request.principal().map(|p| p.id()), Some("alice"));
+    /// ```
+    pub fn set_principal(&mut self, principal: Principal) {
+        self.extensions.insert(principal);
+    }
+
+    /// Returns the authenticated principal of the request, if any.
+    pub fn principal(&self) -> Option<&Principal> {
+        self.extensions.get::<Principal>()
+    }
+
+    /// Attaches the verified claims of a JSON Web Token to the request.
+    ///
+    /// This is normally called by `JwtMiddleware`. The claims are stored in the
+    /// request's extensions as `Claims`.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - The verified token claims.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::json;
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert!(request.claims().is_none());
+    ///
+    /// request.set_claims(json!({ "sub" => "alice" }));
+    /// assert_eq!(request.claims().and_then(|c| c.get("sub")).and_then(|s| s.as_str()), Some("alice"));
+    /// ```
+    pub fn set_claims(&mut self, claims: JsonValue) {
+        self.extensions.insert(Claims(claims));
+    }
+
+    /// Returns the verified JSON Web Token claims of the request, if any.
+    pub fn claims(&self) -> Option<&JsonValue> {
+        self.extensions.get::<Claims>().map(|claims| &claims.0)
+    }
+
+    /// Returns the request-scoped extensions set by middleware.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// #[derive(Clone)]
+    /// struct TenantId(u32);
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// request.extensions_mut().insert(TenantId(7));
+    /// assert_eq!(request.extensions().get::<TenantId>().map(|t| t.0), Some(7));
+    /// ```
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the request-scoped extensions for modification.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Sets the address of the peer that sent the request.
+    ///
+    /// This is called by the server with the address returned when the connection was
+    /// accepted, or with the source address of a PROXY protocol preamble.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_addr` - The socket address of the remote peer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert!(request.peer_addr().is_none());
+    ///
+    /// request.set_peer_addr("127.0.0.1:50000".parse().unwrap());
+    /// assert_eq!(request.peer_addr().map(|addr| addr.port()), Some(50000));
+    /// ```
+    pub fn set_peer_addr(&mut self, peer_addr: SocketAddr) {
+        self.peer_addr = Some(peer_addr);
+    }
+
+    /// Returns the address of the peer that sent the request, if known.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Sets the local address the peer connected to.
+    ///
+    /// This is called by the server with the listening socket's address, or with the
+    /// destination address of a PROXY protocol preamble.
+    pub fn set_local_addr(&mut self, local_addr: SocketAddr) {
+        self.local_addr = Some(local_addr);
+    }
+
+    /// Returns the local address the peer connected to, if known.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Overrides the client IP address, e.g. with one resolved from forwarding headers.
+    ///
+    /// This is normally called by `TrustedProxies::apply`.
+    pub fn set_client_ip(&mut self, client_ip: IpAddr) {
+        self.client_ip = Some(client_ip);
+    }
+
+    /// Returns the IP address of the client.
+    ///
+    /// This is the address resolved through trusted proxies if any, otherwise the IP of
+    /// the peer that connected to the server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// request.set_peer_addr("10.0.0.1:50000".parse().unwrap());
+    /// assert_eq!(request.client_ip(), Some("10.0.0.1".parse().unwrap()));
+    ///
+    /// request.set_client_ip("203.0.113.7".parse().unwrap());
+    /// assert_eq!(request.client_ip(), Some("203.0.113.7".parse().unwrap()));
+    /// ```
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+            .or_else(|| self.peer_addr.map(|addr| addr.ip()))
+    }
+
+    /// Overrides the scheme the client used to reach the server.
+    pub fn set_scheme(&mut self, scheme: &str) {
+        self.scheme = Some(scheme.to_string());
+    }
+
+    /// Returns the scheme the client used to reach the server, `"http"` by default.
+    pub fn scheme(&self) -> &str {
+        self.scheme.as_deref().unwrap_or("http")
+    }
+
+    /// Overrides the host the client requested.
+    pub fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    /// Returns the host the client requested, falling back to the `Host` header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\nHost: localhost:8080\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(request.host(), Some("localhost:8080"));
+    ///
+    /// request.set_host("example.com");
+    /// assert_eq!(request.host(), Some("example.com"));
+    /// ```
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref().or_else(|| self.header("Host"))
+    }
+
     /// Returns all headers of the request.
     ///
     /// # Examples
@@ -460,6 +899,39 @@ impl Request {
         &self.headers
     }
 
+    /// Sets a request header, replacing any existing value regardless of case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::request::Request;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc,Mutex};
+    ///
+    /// let mut request = Request::new(
+    ///     "GET /path HTTP/1.1\r\ncontent-length: 10\r\n\r\n",
+    ///     Arc::new(Mutex::new(HashMap::new())),
+    /// ).unwrap();
+    ///
+    /// request.set_header("Content-Length", "42");
+    /// assert_eq!(request.header("content-length"), Some("42"));
+    /// assert_eq!(request.headers().len(), 1);
+    /// ```
+    pub fn set_header(&mut self, key: &str, value: &str) {
+        self.remove_header(key);
+        self.headers.insert(key.to_string(), value.to_string());
+    }
+
+    /// Removes a request header regardless of case, returning its previous value.
+    pub fn remove_header(&mut self, key: &str) -> Option<String> {
+        let name = self
+            .headers
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(key))
+            .cloned()?;
+        self.headers.remove(&name)
+    }
+
     /// Returns all query parameters of the request.
     ///
     /// # Examples
@@ -506,6 +978,21 @@ impl Request {
     }
 }
 
+/// Parses the value of a `Cookie` header into name/value pairs.
+fn parse_cookies(header: &str) -> HashMap<String, String> {
+    header
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some((name.to_string(), value.trim().trim_matches('"').to_string()))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -563,6 +1050,33 @@ mod tests {
         assert_eq!(form_data.get("key"), Some(&"value".to_string()));
     }
 
+    #[test]
+    fn test_header_case_insensitive() {
+        let request = Request::new(
+            "GET /path HTTP/1.1\r\ncontent-type: text/plain\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+
+        assert_eq!(request.header("Content-Type"), Some("text/plain"));
+        assert_eq!(request.header("CONTENT-TYPE"), Some("text/plain"));
+        assert_eq!(request.header("Accept"), None);
+    }
+
+    #[test]
+    fn test_cookies() {
+        let request = Request::new(
+            "GET /path HTTP/1.1\r\nCookie: a=1; b=\"two\";malformed; c=\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+
+        assert_eq!(request.cookie("a"), Some("1"));
+        assert_eq!(request.cookie("b"), Some("two"));
+        assert_eq!(request.cookie("c"), Some(""));
+        assert_eq!(request.cookie("malformed"), None);
+    }
+
     #[test]
     fn test_set_json_body() {
         let mut request = Request::new(
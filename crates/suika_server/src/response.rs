@@ -1,20 +1,33 @@
+use crate::conditional::{if_range_matches, ETag};
 use crate::error::HttpError;
-use std::collections::HashMap;
-use std::io::Result as IoResult;
+use crate::extensions::Extensions;
+use crate::http_date::{format_http_date, parse_http_date};
+use crate::random::random_hex;
+use crate::range::{parse_range, Ranges};
+use crate::request::Request;
+use crate::sendfile;
+use futures::{Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{Result as IoResult, SeekFrom};
 use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::SystemTime;
 use suika_json::JsonValue;
 use suika_mime::get_mime_type_from_path;
 use suika_templates::context::Context;
 use suika_templates::TemplateEngine;
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 
 /// Represents an HTTP response.
 #[derive(Debug)]
 pub struct Response {
     inner: Arc<Mutex<ResponseInner>>,
+    extensions: Extensions,
 }
 
 /// Represents the inner state of the HTTP response.
@@ -24,6 +37,7 @@ pub struct ResponseInner {
     headers: HashMap<String, String>,
     body: Option<Body>,
     template_engine: Option<Arc<TemplateEngine>>, // Add template engine field
+    template_globals: HashMap<String, JsonValue>,
 }
 
 impl ResponseInner {
@@ -48,6 +62,148 @@ impl ResponseInner {
 pub enum Body {
     Text(String),
     Binary(Vec<u8>),
+    Stream(StreamBody),
+    File(FileBody),
+}
+
+impl Body {
+    /// Collects the body into bytes, reading streamed and file bodies to the end.
+    ///
+    /// Streamed and file bodies that have already been taken yield no bytes.
+    pub async fn into_bytes(self) -> IoResult<Vec<u8>> {
+        let mut chunks = match self {
+            Body::Text(text) => return Ok(text.into_bytes()),
+            Body::Binary(bytes) => return Ok(bytes),
+            Body::Stream(body) => body.take(),
+            Body::File(body) => body.take_stream(),
+        };
+        let mut bytes = Vec::new();
+        if let Some(chunks) = &mut chunks {
+            while let Some(chunk) = chunks.next().await {
+                bytes.extend(chunk?);
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// A boxed stream of body chunks.
+pub type ByteStream = Pin<Box<dyn Stream<Item = IoResult<Vec<u8>>> + Send>>;
+
+/// A response body produced incrementally by a stream of chunks.
+///
+/// The stream can only be consumed once; clones share the same stream. Unless a
+/// `Content-Length` header is set, streamed bodies are sent with chunked transfer encoding.
+#[derive(Clone)]
+pub struct StreamBody {
+    stream: Arc<std::sync::Mutex<Option<ByteStream>>>,
+}
+
+impl StreamBody {
+    /// Creates a new `StreamBody` from a stream of chunks.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = IoResult<Vec<u8>>> + Send + 'static,
+    {
+        StreamBody {
+            stream: Arc::new(std::sync::Mutex::new(Some(Box::pin(stream)))),
+        }
+    }
+
+    /// Takes the underlying stream, leaving the body empty.
+    ///
+    /// Returns `None` if the stream has already been taken.
+    pub fn take(&self) -> Option<ByteStream> {
+        self.stream.lock().unwrap().take()
+    }
+}
+
+impl fmt::Debug for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamBody")
+    }
+}
+
+impl PartialEq for StreamBody {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.stream, &other.stream)
+    }
+}
+
+/// A response body sent from a region of an open file.
+///
+/// When the server writes the response to a TCP socket on Linux, the region is copied
+/// by the kernel with `sendfile`, so the file content never passes through user space.
+/// Elsewhere it is read and written in chunks. Like a `StreamBody`, the file can only
+/// be taken once; clones share the same file.
+#[derive(Clone)]
+pub struct FileBody {
+    file: Arc<std::sync::Mutex<Option<std::fs::File>>>,
+    offset: u64,
+    length: u64,
+}
+
+impl FileBody {
+    /// Creates a new `FileBody` sending `length` bytes of `file` starting at `offset`.
+    pub fn new(file: std::fs::File, offset: u64, length: u64) -> Self {
+        FileBody {
+            file: Arc::new(std::sync::Mutex::new(Some(file))),
+            offset,
+            length,
+        }
+    }
+
+    /// Returns the offset of the region in the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the length of the region, in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns `true` if the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Takes the underlying file, leaving the body empty.
+    ///
+    /// Returns `None` if the file has already been taken.
+    pub fn take(&self) -> Option<std::fs::File> {
+        self.file.lock().unwrap().take()
+    }
+
+    /// Takes the underlying file as a stream of chunks read from the region.
+    ///
+    /// Returns `None` if the file has already been taken.
+    pub fn take_stream(&self) -> Option<ByteStream> {
+        self.take().map(|file| {
+            file_segments(
+                File::from_std(file),
+                vec![Segment::File {
+                    start: self.offset,
+                    remaining: self.length,
+                }],
+            )
+        })
+    }
+}
+
+impl fmt::Debug for FileBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileBody")
+            .field("offset", &self.offset)
+            .field("length", &self.length)
+            .finish()
+    }
+}
+
+impl PartialEq for FileBody {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file)
+    }
 }
 
 impl Response {
@@ -59,10 +215,39 @@ impl Response {
                 headers: HashMap::new(),
                 body: None,
                 template_engine,
+                template_globals: HashMap::new(),
             })),
+            extensions: Extensions::new(),
         }
     }
 
+    /// Returns the response-scoped extensions set by middleware and handlers.
+    ///
+    /// Unlike the status, headers and body, extensions are not shared between clones
+    /// of a response; each clone gets a copy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::response::Response;
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone)]
+    /// struct HandlerTime(Duration);
+    ///
+    /// let mut res = Response::new(None);
+    /// res.extensions_mut().insert(HandlerTime(Duration::from_millis(3)));
+    /// assert!(res.extensions().get::<HandlerTime>().is_some());
+    /// ```
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the response-scoped extensions for modification.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Sets the status code of the response.
     pub async fn set_status(&self, code: u16) {
         let mut inner = self.inner.lock().await;
@@ -81,6 +266,51 @@ impl Response {
         inner.headers.insert(key.to_string(), value.to_string());
     }
 
+    /// Appends a value to a comma-separated list header such as `Vary`, unless the list
+    /// already contains it.
+    pub async fn append_header(&self, key: &str, value: &str) {
+        let mut inner = self.inner.lock().await;
+        match inner.headers.get_mut(key) {
+            Some(existing) => {
+                if !existing
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(value))
+                {
+                    existing.push_str(", ");
+                    existing.push_str(value);
+                }
+            }
+            None => {
+                inner.headers.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    /// Gets the value of a response header.
+    pub async fn get_header(&self, key: &str) -> Option<String> {
+        let inner = self.inner.lock().await;
+        inner.headers.get(key).cloned()
+    }
+
+    /// Sets the `ETag` header of the response.
+    ///
+    /// Combined with `conditional::respond` or the `ConditionalMiddleware`, this lets
+    /// clients revalidate cached copies with `If-None-Match`.
+    pub async fn etag(&self, etag: &ETag) {
+        self.header("ETag", &etag.to_string()).await;
+    }
+
+    /// Sets the `Last-Modified` header of the response.
+    pub async fn last_modified(&self, time: SystemTime) {
+        self.header("Last-Modified", &format_http_date(time)).await;
+    }
+
+    /// Removes a header from the response, returning its previous value.
+    pub async fn remove_header(&self, key: &str) -> Option<String> {
+        let mut inner = self.inner.lock().await;
+        inner.headers.remove(key)
+    }
+
     /// Sets the body of the response to a text string.
     pub async fn body(&self, body: String) {
         let mut inner = self.inner.lock().await;
@@ -102,6 +332,35 @@ impl Response {
         inner.body = Some(Body::Binary(body));
     }
 
+    /// Sets the body of the response to a stream of chunks.
+    pub async fn body_stream<S>(&self, stream: S)
+    where
+        S: Stream<Item = IoResult<Vec<u8>>> + Send + 'static,
+    {
+        let mut inner = self.inner.lock().await;
+        inner.body = Some(Body::Stream(StreamBody::new(stream)));
+    }
+
+    /// Sets the body of the response to `length` bytes of a file starting at `offset`.
+    ///
+    /// See `FileBody` for how the file is sent.
+    pub async fn body_file(&self, file: std::fs::File, offset: u64, length: u64) {
+        let mut inner = self.inner.lock().await;
+        inner.body = Some(Body::File(FileBody::new(file, offset, length)));
+    }
+
+    /// Replaces the body of the response.
+    pub async fn set_body(&self, body: Option<Body>) {
+        let mut inner = self.inner.lock().await;
+        inner.body = body;
+    }
+
+    /// Takes the body out of the response, leaving it empty.
+    pub async fn take_body(&self) -> Option<Body> {
+        let mut inner = self.inner.lock().await;
+        inner.body.take()
+    }
+
     /// Sets the response to an HTTP error.
     pub async fn error(&self, http_error: HttpError) {
         let mut inner = self.inner.lock().await;
@@ -113,67 +372,296 @@ impl Response {
     /// Sends the response over a stream.
     pub async fn send(&self, stream: &mut (impl AsyncWriteExt + Unpin)) -> IoResult<()> {
         let inner = self.inner.lock().await;
-        let status_code = inner.status_code.unwrap_or(200);
-        let status_text = match status_code {
-            200 => "OK",
-            404 => "Not Found",
-            500 => "Internal Server Error",
-            _ => "Unknown Status",
-        };
-        let status_line = format!("HTTP/1.1 {} {}\r\n", status_code, status_text);
-
-        stream.write_all(status_line.as_bytes()).await?;
+        let chunked = write_head(&inner, stream).await?;
 
-        for (key, value) in &inner.headers {
-            let header_line = format!("{}: {}\r\n", key, value);
-            stream.write_all(header_line.as_bytes()).await?;
+        match &inner.body {
+            Some(Body::File(body)) => {
+                if let Some(file) = body.take() {
+                    sendfile::copy_buffered(file, body.offset(), body.len(), stream).await?;
+                }
+            }
+            Some(body) => write_body(body, chunked, stream).await?,
+            None => {}
         }
 
-        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        Ok(())
+    }
 
-        if let Some(ref body) = inner.body {
-            match body {
-                Body::Text(ref text) => {
-                    stream.write_all(text.as_bytes()).await?;
-                }
-                Body::Binary(ref binary) => {
-                    stream.write_all(binary).await?;
+    /// Sends the response over a TCP stream.
+    ///
+    /// Behaves like `send`, except that file bodies are copied to the socket by the
+    /// kernel where the platform supports it.
+    pub async fn send_tcp(&self, stream: &mut TcpStream) -> IoResult<()> {
+        let inner = self.inner.lock().await;
+        let chunked = write_head(&inner, stream).await?;
+
+        match &inner.body {
+            Some(Body::File(body)) => {
+                if let Some(file) = body.take() {
+                    sendfile::copy_to_socket(file, body.offset(), body.len(), stream).await?;
                 }
             }
+            Some(body) => write_body(body, chunked, stream).await?,
+            None => {}
         }
 
         stream.flush().await?;
         Ok(())
     }
 
+    /// Sends only the status line and headers of the response, as the answer to a
+    /// `HEAD` request.
+    ///
+    /// The headers, including `Content-Length`, are the ones a `GET` would be answered
+    /// with, but the body is never read or sent.
+    pub async fn send_head(&self, stream: &mut (impl AsyncWriteExt + Unpin)) -> IoResult<()> {
+        let inner = self.inner.lock().await;
+        write_head(&inner, stream).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
     /// Sends a file as the response body.
+    ///
+    /// Sets `Content-Type`, `Content-Length`, and `ETag` and `Last-Modified` validators
+    /// derived from the file's size and modification time.
     pub async fn send_file(&self, file_path: &str) -> Result<(), HttpError> {
-        let path = Path::new(file_path);
+        let (file, metadata) = open_file(file_path).await?;
+        let length = metadata.len();
 
-        if !path.exists() {
-            return Err(HttpError::NotFound("File not found".to_string()));
+        if let Ok(modified) = metadata.modified() {
+            self.etag(&ETag::from_metadata(length, modified)).await;
+            self.last_modified(modified).await;
         }
 
-        let mut file = File::open(path)
-            .await
-            .map_err(|e| HttpError::InternalServerError(format!("Failed to open file: {}", e)))?;
-
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .await
-            .map_err(|e| HttpError::InternalServerError(format!("Failed to read file: {}", e)))?;
-
         let mime_type = get_mime_type_from_path(file_path);
         self.header("Content-Type", mime_type.as_ref()).await;
-        self.header("Content-Length", &buffer.len().to_string())
-            .await;
+        self.header("Content-Length", &length.to_string()).await;
 
         self.set_status(200).await;
-        self.body_bytes(buffer).await;
+        self.body_file(file.into_std().await, 0, length).await;
 
         Ok(())
     }
 
+    /// Sends a file as an attachment, prompting browsers to save it as `filename`.
+    ///
+    /// Behaves like `send_file` and sets `Content-Disposition: attachment`. Names that
+    /// are not plain ASCII are sent as an RFC 5987 `filename*` parameter, with an ASCII
+    /// `filename` fallback for older clients.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// res.download("reports/2024-q1.pdf", "Quarterly report.pdf").await?;
+    /// ```
+    pub async fn download(&self, file_path: &str, filename: &str) -> Result<(), HttpError> {
+        self.send_file(file_path).await?;
+        self.header("Content-Disposition", &content_disposition(filename))
+            .await;
+        Ok(())
+    }
+
+    /// Sends a file as the response body, honouring the request's `Range` header.
+    ///
+    /// Behaves like `send_file` and additionally advertises `Accept-Ranges: bytes`. For
+    /// `GET` requests with a satisfiable `Range` header, the requested bytes are read from
+    /// the file at their offsets and sent as `206 Partial Content`, using a
+    /// `multipart/byteranges` body when several ranges are requested. Unsatisfiable ranges
+    /// result in `416 Range Not Satisfiable`. An `If-Range` header that does not match the
+    /// file's validators causes the whole file to be sent.
+    ///
+    /// An `ETag` already set on the response, such as one computed from the file
+    /// content, is kept and used to evaluate `If-Range`.
+    pub async fn send_file_ranged(&self, req: &Request, file_path: &str) -> Result<(), HttpError> {
+        let mime_type = get_mime_type_from_path(file_path);
+        self.send_file_ranged_as(req, file_path, &mime_type).await
+    }
+
+    /// Sends a file like `send_file_ranged`, using the given `Content-Type` instead of
+    /// one derived from the file's extension.
+    ///
+    /// This is useful for precompressed files such as `app.js.br`, which are sent with
+    /// the type of the original file.
+    pub async fn send_file_ranged_as(
+        &self,
+        req: &Request,
+        file_path: &str,
+        mime_type: &str,
+    ) -> Result<(), HttpError> {
+        let (file, metadata) = open_file(file_path).await?;
+        let length = metadata.len();
+        let modified = metadata.modified().ok();
+
+        let etag = match self.get_header("ETag").await.and_then(|v| ETag::parse(&v)) {
+            Some(etag) => Some(etag),
+            None => modified.map(|modified| ETag::from_metadata(length, modified)),
+        };
+        if let Some(etag) = &etag {
+            self.etag(etag).await;
+        }
+        if let Some(modified) = modified {
+            self.last_modified(modified).await;
+        }
+
+        match self
+            .apply_ranges(req, length, mime_type, etag.as_ref(), modified)
+            .await
+        {
+            RangedBody::Full => {
+                self.header("Content-Length", &length.to_string()).await;
+                self.set_status(200).await;
+                self.body_file(file.into_std().await, 0, length).await;
+            }
+            RangedBody::Unsatisfiable => {}
+            RangedBody::Partial(segments) => match segments.as_slice() {
+                [Segment::File { start, remaining }] => {
+                    let (start, remaining) = (*start, *remaining);
+                    self.body_file(file.into_std().await, start, remaining)
+                        .await;
+                }
+                _ => self.body_stream(file_segments(file, segments)).await,
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Sends in-memory data as the response body, honouring the request's `Range`
+    /// header like `send_file_ranged`.
+    ///
+    /// Validators used to evaluate `If-Range` must be set on the response with `etag`
+    /// and `last_modified` before calling this method.
+    pub async fn send_bytes_ranged(&self, req: &Request, data: &[u8], mime_type: &str) {
+        let etag = self.get_header("ETag").await.and_then(|v| ETag::parse(&v));
+        let modified = self
+            .get_header("Last-Modified")
+            .await
+            .and_then(|v| parse_http_date(&v));
+
+        match self
+            .apply_ranges(req, data.len() as u64, mime_type, etag.as_ref(), modified)
+            .await
+        {
+            RangedBody::Full => {
+                self.header("Content-Length", &data.len().to_string()).await;
+                self.set_status(200).await;
+                self.body_bytes(data.to_vec()).await;
+            }
+            RangedBody::Unsatisfiable => {}
+            RangedBody::Partial(segments) => {
+                let mut body = Vec::new();
+                for segment in segments {
+                    match segment {
+                        Segment::Bytes(bytes) => body.extend(bytes),
+                        Segment::File { start, remaining } => {
+                            body.extend_from_slice(
+                                &data[start as usize..(start + remaining) as usize],
+                            );
+                        }
+                    }
+                }
+                self.body_bytes(body).await;
+            }
+        }
+    }
+
+    /// Sets `Content-Type` and `Accept-Ranges`, and applies the request's `Range`
+    /// header to a representation of `length` bytes.
+    ///
+    /// For partial responses the status, `Content-Range` or multipart `Content-Type`
+    /// and `Content-Length` are set, and the segments making up the body are returned.
+    async fn apply_ranges(
+        &self,
+        req: &Request,
+        length: u64,
+        mime_type: &str,
+        etag: Option<&ETag>,
+        modified: Option<SystemTime>,
+    ) -> RangedBody {
+        self.header("Content-Type", mime_type).await;
+        self.header("Accept-Ranges", "bytes").await;
+
+        let ranges = match req.header("Range") {
+            Some(range) if req.method() == "GET" && if_range_matches(req, etag, modified) => {
+                parse_range(range, length)
+            }
+            _ => Ranges::Ignore,
+        };
+
+        match ranges {
+            Ranges::Ignore => RangedBody::Full,
+            Ranges::Unsatisfiable => {
+                self.header("Content-Range", &format!("bytes */{}", length))
+                    .await;
+                self.remove_header("Content-Length").await;
+                self.set_status(416).await;
+                self.body("Range Not Satisfiable".to_string()).await;
+                RangedBody::Unsatisfiable
+            }
+            Ranges::Satisfiable(ranges) if ranges.len() == 1 => {
+                let range = ranges[0];
+                self.header("Content-Range", &range.content_range(length))
+                    .await;
+                self.header("Content-Length", &range.length().to_string())
+                    .await;
+                self.set_status(206).await;
+                RangedBody::Partial(vec![Segment::File {
+                    start: range.start,
+                    remaining: range.length(),
+                }])
+            }
+            Ranges::Satisfiable(ranges) => {
+                let boundary = random_hex(16);
+                let mut segments = Vec::with_capacity(ranges.len() * 2 + 1);
+                let mut content_length = 0;
+                for range in &ranges {
+                    let part_head = format!(
+                        "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                        boundary,
+                        mime_type,
+                        range.content_range(length)
+                    );
+                    content_length += part_head.len() as u64 + range.length();
+                    segments.push(Segment::Bytes(part_head.into_bytes()));
+                    segments.push(Segment::File {
+                        start: range.start,
+                        remaining: range.length(),
+                    });
+                }
+                let closing = format!("\r\n--{}--\r\n", boundary);
+                content_length += closing.len() as u64;
+                segments.push(Segment::Bytes(closing.into_bytes()));
+
+                self.header(
+                    "Content-Type",
+                    &format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .await;
+                self.header("Content-Length", &content_length.to_string())
+                    .await;
+                self.set_status(206).await;
+                RangedBody::Partial(segments)
+            }
+        }
+    }
+
+    /// Sets a value that is made available to every template rendered for this response.
+    ///
+    /// Middleware uses this to expose per-request values such as CSRF tokens to templates.
+    /// Values inserted into the context passed to `render_template` take precedence.
+    pub async fn set_template_global<T: Into<JsonValue>>(&self, key: &str, value: T) {
+        let mut inner = self.inner.lock().await;
+        inner.template_globals.insert(key.to_string(), value.into());
+    }
+
+    /// Gets a template global previously set on the response.
+    pub async fn template_global(&self, key: &str) -> Option<JsonValue> {
+        let inner = self.inner.lock().await;
+        inner.template_globals.get(key).cloned()
+    }
+
     /// Renders a template using the template engine and sets it as the response body.
     pub async fn render_template(
         &self,
@@ -182,6 +670,13 @@ impl Response {
     ) -> Result<(), HttpError> {
         let inner = self.inner.lock().await;
         if let Some(template_engine) = &inner.template_engine {
+            let mut context = context.clone();
+            for (key, value) in &inner.template_globals {
+                if context.get(key).is_none() {
+                    context.insert(key, value.clone());
+                }
+            }
+
             let rendered = template_engine
                 .render(template_name, &context)
                 .map_err(|e| {
@@ -203,10 +698,240 @@ impl Response {
     }
 }
 
+/// Opens a regular file for sending, returning it with its metadata.
+async fn open_file(file_path: &str) -> Result<(File, std::fs::Metadata), HttpError> {
+    let path = Path::new(file_path);
+
+    if !path.is_file() {
+        return Err(HttpError::NotFound("File not found".to_string()));
+    }
+
+    let file = File::open(path)
+        .await
+        .map_err(|e| HttpError::InternalServerError(format!("Failed to open file: {}", e)))?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|e| HttpError::InternalServerError(format!("Failed to read file: {}", e)))?;
+    Ok((file, metadata))
+}
+
+/// Writes the status line and headers of a response, returning whether the body is
+/// sent with chunked transfer encoding.
+async fn write_head(
+    inner: &ResponseInner,
+    stream: &mut (impl AsyncWriteExt + Unpin),
+) -> IoResult<bool> {
+    let status_code = inner.status_code.unwrap_or(200);
+    let status_line = format!("HTTP/1.1 {} {}\r\n", status_code, status_text(status_code));
+
+    stream.write_all(status_line.as_bytes()).await?;
+
+    for (key, value) in &inner.headers {
+        let header_line = format!("{}: {}\r\n", key, value);
+        stream.write_all(header_line.as_bytes()).await?;
+    }
+
+    let has_length = inner.headers.contains_key("Content-Length");
+    let chunked = matches!(inner.body, Some(Body::Stream(_))) && !has_length;
+    if chunked {
+        stream.write_all(b"Transfer-Encoding: chunked\r\n").await?;
+    }
+    if let Some(Body::File(body)) = &inner.body {
+        if !has_length {
+            let header_line = format!("Content-Length: {}\r\n", body.len());
+            stream.write_all(header_line.as_bytes()).await?;
+        }
+    }
+
+    stream.write_all(b"\r\n").await?;
+    Ok(chunked)
+}
+
+/// Writes an in-memory or streamed body.
+async fn write_body(
+    body: &Body,
+    chunked: bool,
+    stream: &mut (impl AsyncWriteExt + Unpin),
+) -> IoResult<()> {
+    match body {
+        Body::Text(ref text) => {
+            stream.write_all(text.as_bytes()).await?;
+        }
+        Body::Binary(ref binary) => {
+            stream.write_all(binary).await?;
+        }
+        Body::Stream(ref body) => {
+            if let Some(mut chunks) = body.take() {
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk?;
+                    if chunk.is_empty() {
+                        continue;
+                    }
+                    if chunked {
+                        stream
+                            .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                            .await?;
+                        stream.write_all(&chunk).await?;
+                        stream.write_all(b"\r\n").await?;
+                    } else {
+                        stream.write_all(&chunk).await?;
+                    }
+                }
+            }
+            if chunked {
+                stream.write_all(b"0\r\n\r\n").await?;
+            }
+        }
+        Body::File(body) => {
+            if let Some(file) = body.take() {
+                sendfile::copy_buffered(file, body.offset(), body.len(), stream).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns a `Content-Disposition: attachment` value for a file name, following
+/// RFC 6266 with RFC 5987 encoding for names that are not plain ASCII.
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' && c != '%' => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        return format!("attachment; filename=\"{}\"", filename);
+    }
+
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+/// How a representation is sent after applying a `Range` header.
+enum RangedBody {
+    Full,
+    Unsatisfiable,
+    Partial(Vec<Segment>),
+}
+
+/// A part of a partial body: literal bytes or a region of the representation.
+enum Segment {
+    Bytes(Vec<u8>),
+    File { start: u64, remaining: u64 },
+}
+
+/// The size of the chunks read from a file for a streamed body.
+const FILE_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Streams a sequence of segments, reading file regions in chunks from their offsets.
+fn file_segments(file: File, segments: Vec<Segment>) -> ByteStream {
+    let state = (file, VecDeque::from(segments));
+    Box::pin(futures::stream::unfold(
+        state,
+        |(mut file, mut segments)| async move {
+            let chunk = match segments.pop_front()? {
+                Segment::Bytes(bytes) => Ok(bytes),
+                Segment::File { start, remaining } => {
+                    let mut buffer = vec![0; remaining.min(FILE_CHUNK_SIZE) as usize];
+                    let read = async {
+                        file.seek(SeekFrom::Start(start)).await?;
+                        file.read_exact(&mut buffer).await
+                    };
+                    match read.await {
+                        Ok(_) => {
+                            let read = buffer.len() as u64;
+                            if read < remaining {
+                                segments.push_front(Segment::File {
+                                    start: start + read,
+                                    remaining: remaining - read,
+                                });
+                            }
+                            Ok(buffer)
+                        }
+                        Err(e) => {
+                            segments.clear();
+                            Err(e)
+                        }
+                    }
+                }
+            };
+            Some((chunk, (file, segments)))
+        },
+    ))
+}
+
+/// Returns the reason phrase for an HTTP status code.
+fn status_text(status_code: u16) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Unknown Status",
+    }
+}
+
 impl Clone for Response {
     fn clone(&self) -> Self {
         Response {
             inner: Arc::clone(&self.inner),
+            extensions: self.extensions.clone(),
         }
     }
 }
@@ -289,6 +1014,19 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_append_header() {
+        let response = Response::new(None);
+        response.append_header("Vary", "Origin").await;
+        response.append_header("Vary", "Accept-Encoding").await;
+        response.append_header("Vary", "origin").await;
+        assert_eq!(
+            response.get_header("Vary").await,
+            Some("Origin, Accept-Encoding".to_string())
+        );
+        assert_eq!(response.get_header("Missing").await, None);
+    }
+
     #[tokio::test]
     async fn test_body() {
         let response = Response::new(None);
@@ -345,6 +1083,66 @@ mod tests {
         assert!(response_string.contains("Hello, world!"));
     }
 
+    #[tokio::test]
+    async fn test_send_stream() {
+        let response = Response::new(None);
+        response.set_status(200).await;
+        response
+            .body_stream(futures::stream::iter(vec![
+                Ok(b"Hello, ".to_vec()),
+                Ok(Vec::new()),
+                Ok(b"world!".to_vec()),
+            ]))
+            .await;
+
+        let mut mock_stream = MockStream::new();
+        response.send(&mut mock_stream).await.unwrap();
+
+        let data = mock_stream.get_data().await;
+        let response_string = String::from_utf8(data).unwrap();
+        assert!(response_string.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(response_string.ends_with("\r\n\r\n7\r\nHello, \r\n6\r\nworld!\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_send_head() {
+        let file_path = "test_send_head.txt";
+        let mut file = File::create(file_path).await.unwrap();
+        file.write_all(&[b'a'; 5000]).await.unwrap();
+
+        let response = Response::new(None);
+        response.send_file(file_path).await.unwrap();
+
+        let mut mock_stream = MockStream::new();
+        response.send_head(&mut mock_stream).await.unwrap();
+
+        let data = mock_stream.get_data().await;
+        let response_string = String::from_utf8(data).unwrap();
+        assert!(response_string.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response_string.contains("Content-Length: 5000\r\n"));
+        assert!(response_string.ends_with("\r\n\r\n"));
+        assert!(!response_string.contains("aaaa"));
+
+        tokio::fs::remove_file(file_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_take_body() {
+        let response = Response::new(None);
+        response.body("Hello".to_string()).await;
+        assert_eq!(
+            response.take_body().await,
+            Some(Body::Text("Hello".to_string()))
+        );
+        assert_eq!(response.take_body().await, None);
+
+        response.set_body(Some(Body::Binary(vec![1]))).await;
+        assert_eq!(
+            response.get_inner().await.body(),
+            &Some(Body::Binary(vec![1]))
+        );
+    }
+
     #[tokio::test]
     async fn test_send_file() {
         // Create a temporary file for testing
@@ -361,16 +1159,174 @@ mod tests {
             Some(&"text/plain".to_string())
         );
         assert_eq!(inner.headers.get("Content-Length"), Some(&"12".to_string()));
-        if let Some(Body::Binary(ref bytes)) = inner.body {
-            assert_eq!(bytes, &b"File content"[..]);
-        } else {
-            panic!("Expected body to be Some(Body::Binary)");
-        }
+        assert!(inner.headers.get("ETag").unwrap().starts_with("W/\"c-"));
+        assert!(inner.headers.contains_key("Last-Modified"));
+        let body = inner.body.clone().unwrap();
+        assert!(matches!(body, Body::File(ref file) if file.offset() == 0 && file.len() == 12));
+        assert_eq!(body.into_bytes().await.unwrap(), b"File content");
 
         // Clean up the temporary file
         tokio::fs::remove_file(file_path).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_download() {
+        let file = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+        std::fs::write(file.path(), "a,b\n1,2\n").unwrap();
+
+        let response = Response::new(None);
+        response
+            .download(file.path().to_str().unwrap(), "report.csv")
+            .await
+            .unwrap();
+        assert_eq!(
+            response.get_header("Content-Disposition").await,
+            Some("attachment; filename=\"report.csv\"".to_string())
+        );
+        assert_eq!(
+            response.get_header("Content-Type").await,
+            Some("text/csv".to_string())
+        );
+
+        let mut stream = MockStream::new();
+        response.send(&mut stream).await.unwrap();
+        let data = String::from_utf8(stream.get_data().await).unwrap();
+        assert!(data.contains("Content-Length: 8\r\n"));
+        assert!(data.ends_with("\r\n\r\na,b\n1,2\n"));
+
+        let missing = Response::new(None);
+        assert!(matches!(
+            missing.download("missing.csv", "report.csv").await,
+            Err(HttpError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("plain name.txt"),
+            "attachment; filename=\"plain name.txt\""
+        );
+        assert_eq!(
+            content_disposition("résumé 2024.pdf"),
+            "attachment; filename=\"r_sum_ 2024.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%202024.pdf"
+        );
+        assert_eq!(
+            content_disposition("say \"hi\"\r\n.txt"),
+            "attachment; filename=\"say _hi___.txt\"; filename*=UTF-8''say%20%22hi%22%0D%0A.txt"
+        );
+        assert_eq!(
+            content_disposition("100%.txt"),
+            "attachment; filename=\"100_.txt\"; filename*=UTF-8''100%25.txt"
+        );
+    }
+
+    async fn send_ranged(file_path: &str, headers: &str) -> (Response, Vec<u8>) {
+        let req = Request::new(
+            &format!("GET /file HTTP/1.1\r\n{}\r\n", headers),
+            Arc::new(std::sync::Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let response = Response::new(None);
+        response.send_file_ranged(&req, file_path).await.unwrap();
+
+        let body = match response.take_body().await {
+            Some(body) => body.into_bytes().await.unwrap(),
+            None => Vec::new(),
+        };
+        (response, body)
+    }
+
+    #[tokio::test]
+    async fn test_send_file_ranged() {
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let file = tempfile::Builder::new().suffix(".bin").tempfile().unwrap();
+        std::fs::write(file.path(), &content).unwrap();
+        let file_path = file.path().to_str().unwrap();
+
+        let (response, body) = send_ranged(file_path, "").await;
+        assert_eq!(response.status().await, Some(200));
+        assert_eq!(
+            response.get_header("Accept-Ranges").await,
+            Some("bytes".to_string())
+        );
+        assert_eq!(body, content);
+
+        let (response, body) = send_ranged(file_path, "Range: bytes=1000-150999\r\n").await;
+        assert_eq!(response.status().await, Some(206));
+        assert_eq!(
+            response.get_header("Content-Range").await,
+            Some("bytes 1000-150999/200000".to_string())
+        );
+        assert_eq!(
+            response.get_header("Content-Length").await,
+            Some("150000".to_string())
+        );
+        assert_eq!(body, &content[1000..151000]);
+
+        let (response, body) = send_ranged(file_path, "Range: bytes=-10\r\n").await;
+        assert_eq!(response.status().await, Some(206));
+        assert_eq!(body, &content[199_990..]);
+    }
+
+    #[tokio::test]
+    async fn test_send_file_multiple_ranges() {
+        let file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        std::fs::write(file.path(), b"0123456789abcdefghij").unwrap();
+        let file_path = file.path().to_str().unwrap();
+
+        let (response, body) = send_ranged(file_path, "Range: bytes=0-1, 10-11\r\n").await;
+        assert_eq!(response.status().await, Some(206));
+        let content_type = response.get_header("Content-Type").await.unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        let expected = format!(
+            "\r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+             \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 10-11/20\r\n\r\nab\
+             \r\n--{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(String::from_utf8(body).unwrap(), expected);
+        assert_eq!(
+            response.get_header("Content-Length").await,
+            Some(expected.len().to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_file_unsatisfiable_and_if_range() {
+        let file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        std::fs::write(file.path(), b"0123456789").unwrap();
+        let file_path = file.path().to_str().unwrap();
+
+        let (response, _) = send_ranged(file_path, "Range: bytes=10-\r\n").await;
+        assert_eq!(response.status().await, Some(416));
+        assert_eq!(
+            response.get_header("Content-Range").await,
+            Some("bytes */10".to_string())
+        );
+
+        let (response, body) =
+            send_ranged(file_path, "Range: bytes=0-1\r\nIf-Range: \"stale\"\r\n").await;
+        assert_eq!(response.status().await, Some(200));
+        assert_eq!(body, b"0123456789");
+
+        let response = Response::new(None);
+        response.etag(&ETag::strong("v1")).await;
+        let req = Request::new(
+            "GET / HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"v1\"\r\n\r\n",
+            Arc::new(std::sync::Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        response.send_file_ranged(&req, file_path).await.unwrap();
+        assert_eq!(response.status().await, Some(206));
+        assert_eq!(
+            response.get_header("ETag").await,
+            Some("\"v1\"".to_string())
+        );
+    }
+
     #[tokio::test]
     async fn test_render_template() {
         let mut template_engine = TemplateEngine::new();
@@ -398,6 +1354,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_render_template_with_globals() {
+        let mut template_engine = TemplateEngine::new();
+        template_engine.add_template("form.html", "<%= csrf_token %>:<%= name %>");
+
+        let response = Response::new(Some(Arc::new(template_engine)));
+        response.set_template_global("csrf_token", "abc").await;
+        response.set_template_global("name", "global").await;
+
+        let mut context = Context::new();
+        context.insert("name", "local");
+
+        response
+            .render_template("form.html", &context)
+            .await
+            .unwrap();
+
+        let inner = response.inner.lock().await;
+        assert_eq!(inner.body, Some(Body::Text("abc:local".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_send_status_text() {
+        let response = Response::new(None);
+        response.set_status(403).await;
+
+        let mut mock_stream = MockStream::new();
+        response.send(&mut mock_stream).await.unwrap();
+
+        let data = mock_stream.get_data().await;
+        let response_string = String::from_utf8(data).unwrap();
+        assert!(response_string.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+
     #[tokio::test]
     async fn test_body_json() {
         let response = Response::new(None);
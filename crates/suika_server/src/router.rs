@@ -1,19 +1,56 @@
 use crate::error::HttpError;
+use crate::guard::Guard;
 use crate::middleware::{Middleware, MiddlewareFuture, Next};
 use crate::request::Request;
 use crate::response::Response;
+use crate::state::StateKey;
 use regex::Regex;
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
 
+/// A type-erased route handler.
+pub type RouteHandler =
+    Arc<dyn for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a> + Send + Sync>;
+
 /// Represents a route in the router.
 pub struct Route {
     pub method: Option<String>,
     pub pattern: Regex,
-    pub handler: Arc<
-        dyn for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a> + Send + Sync,
-    >,
+    pub handler: RouteHandler,
+    pub guards: Vec<Guard>,
+}
+
+impl Route {
+    /// Adds a guard that must allow the request before the handler runs.
+    ///
+    /// Guards run after the router middleware, so they can inspect the principal set
+    /// by authentication middleware. A rejected request fails with `HttpError::Forbidden`.
+    ///
+    /// # Arguments
+    ///
+    /// * `guard` - A `Guard` or a closure over the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::guard::{require_permission, require_role};
+    /// use suika_server::router::Router;
+    ///
+    /// let mut router = Router::new("/");
+    /// router
+    ///     .delete("/todos/(?<id>\\d+)$", |_req, res| {
+    ///         Box::pin(async move {
+    ///             res.set_status(204).await;
+    ///             Ok(())
+    ///         })
+    ///     })
+    ///     .guard(require_role("admin").or(require_permission("todos:delete")));
+    /// ```
+    pub fn guard<G: Into<Guard>>(&mut self, guard: G) -> &mut Self {
+        self.guards.push(guard.into());
+        self
+    }
 }
 
 /// A router for handling HTTP requests and routing them to appropriate handlers.
@@ -98,6 +135,9 @@ pub struct Router {
     pub base_path: String,
     pub routes: Vec<Route>,
     pub sub_routers: Vec<Router>,
+    pub middleware: Vec<Arc<dyn Middleware + Send + Sync>>,
+    pub guards: Vec<Guard>,
+    pub required_state: Vec<StateKey>,
 }
 
 impl Router {
@@ -119,9 +159,87 @@ impl Router {
             base_path: base_path.to_string(),
             routes: Vec::new(),
             sub_routers: Vec::new(),
+            middleware: Vec::new(),
+            guards: Vec::new(),
+            required_state: Vec::new(),
         }
     }
 
+    /// Adds middleware that runs only for requests matched by this router's routes,
+    /// including the routes of mounted sub-routers.
+    ///
+    /// Router middleware runs after the route has been matched and its parameters set,
+    /// and before the route handler. Middleware of a parent router runs before that of
+    /// its sub-routers.
+    ///
+    /// # Arguments
+    ///
+    /// * `mw` - An Arc containing the middleware to add.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::router::Router;
+    /// use suika_server::middleware::LoggerMiddleware;
+    /// use std::sync::Arc;
+    ///
+    /// let mut router = Router::new("/api");
+    /// router.use_middleware(Arc::new(LoggerMiddleware::new()));
+    /// ```
+    pub fn use_middleware(&mut self, mw: Arc<dyn Middleware + Send + Sync>) {
+        self.middleware.push(mw);
+    }
+
+    /// Adds a guard that applies to every route of this router, including the routes of
+    /// mounted sub-routers.
+    ///
+    /// Router guards are checked before route guards, after all router middleware has run.
+    ///
+    /// # Arguments
+    ///
+    /// * `guard` - A `Guard` or a closure over the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::guard::require_role;
+    /// use suika_server::router::Router;
+    ///
+    /// let mut admin = Router::new("/admin");
+    /// admin.guard(require_role("admin"));
+    /// ```
+    pub fn guard<G: Into<Guard>>(&mut self, guard: G) {
+        self.guards.push(guard.into());
+    }
+
+    /// Declares application state of type `T` that this router's handlers read with
+    /// `Request::state`.
+    ///
+    /// The server refuses to start unless the state has been registered with
+    /// `Server::with_state`, so a missing store is reported at startup instead of when a
+    /// route is first requested.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::router::Router;
+    ///
+    /// struct TodoStore;
+    ///
+    /// let mut router = Router::new("/");
+    /// router.requires_state::<TodoStore>();
+    /// router.get("/todos$", |req, res| {
+    ///     Box::pin(async move {
+    ///         let _store = req.state::<TodoStore>();
+    ///         res.set_status(200).await;
+    ///         Ok(())
+    ///     })
+    /// });
+    /// ```
+    pub fn requires_state<T: Send + Sync + 'static>(&mut self) {
+        self.required_state.push(StateKey::of::<T>());
+    }
+
     /// Adds a GET route to the router
     ///
     /// # Arguments
@@ -148,14 +266,14 @@ impl Router {
     ///     })
     /// });
     /// ```
-    pub fn get<F>(&mut self, pattern: &str, handler: F)
+    pub fn get<F>(&mut self, pattern: &str, handler: F) -> &mut Route
     where
         F: for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a>
             + Send
             + Sync
             + 'static,
     {
-        self.add_route(Some("GET"), pattern, handler);
+        self.add_route(Some("GET"), pattern, handler)
     }
 
     /// Adds a PUT route to the router
@@ -184,14 +302,14 @@ impl Router {
     ///     })
     /// });
     /// ```
-    pub fn put<F>(&mut self, pattern: &str, handler: F)
+    pub fn put<F>(&mut self, pattern: &str, handler: F) -> &mut Route
     where
         F: for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a>
             + Send
             + Sync
             + 'static,
     {
-        self.add_route(Some("PUT"), pattern, handler);
+        self.add_route(Some("PUT"), pattern, handler)
     }
 
     /// Adds a POST route to the router
@@ -220,14 +338,14 @@ impl Router {
     ///     })
     /// });
     /// ```
-    pub fn post<F>(&mut self, pattern: &str, handler: F)
+    pub fn post<F>(&mut self, pattern: &str, handler: F) -> &mut Route
     where
         F: for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a>
             + Send
             + Sync
             + 'static,
     {
-        self.add_route(Some("POST"), pattern, handler);
+        self.add_route(Some("POST"), pattern, handler)
     }
 
     /// Adds a DELETE route to the router
@@ -256,14 +374,14 @@ impl Router {
     ///     })
     /// });
     /// ```
-    pub fn delete<F>(&mut self, pattern: &str, handler: F)
+    pub fn delete<F>(&mut self, pattern: &str, handler: F) -> &mut Route
     where
         F: for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a>
             + Send
             + Sync
             + 'static,
     {
-        self.add_route(Some("DELETE"), pattern, handler);
+        self.add_route(Some("DELETE"), pattern, handler)
     }
 
     /// Adds a route to the router.
@@ -293,7 +411,7 @@ impl Router {
     ///     })
     /// });
     /// ```
-    pub fn add_route<F>(&mut self, method: Option<&str>, pattern: &str, handler: F)
+    pub fn add_route<F>(&mut self, method: Option<&str>, pattern: &str, handler: F) -> &mut Route
     where
         F: for<'a> Fn(&'a mut Request, &'a mut Response) -> MiddlewareFuture<'a>
             + Send
@@ -307,7 +425,9 @@ impl Router {
             method: method.map(|m| m.to_string()),
             pattern: rgx,
             handler: Arc::new(handler),
+            guards: Vec::new(),
         });
+        self.routes.last_mut().expect("Route was just added")
     }
 
     /// Mounts a sub-router onto this router.
@@ -344,6 +464,8 @@ impl Router {
     ///
     /// * `req` - A mutable reference to the incoming request.
     /// * `res` - A mutable reference to the response to be sent.
+    /// * `inherited` - Middleware inherited from parent routers.
+    /// * `inherited_guards` - Guards inherited from parent routers.
     ///
     /// # Returns
     ///
@@ -352,8 +474,15 @@ impl Router {
         &'a self,
         req: &'a mut Request,
         res: &'a mut Response,
+        inherited: &'a [Arc<dyn Middleware + Send + Sync>],
+        inherited_guards: &'a [Guard],
     ) -> Pin<Box<dyn futures::Future<Output = Result<bool, HttpError>> + Send + 'a>> {
         Box::pin(async move {
+            let mut stack = inherited.to_vec();
+            stack.extend(self.middleware.iter().cloned());
+            let mut guards = inherited_guards.to_vec();
+            guards.extend(self.guards.iter().cloned());
+
             for route in &self.routes {
                 if let Some(ref route_method) = route.method {
                     if route_method.to_uppercase() != req.method().to_uppercase() {
@@ -370,16 +499,22 @@ impl Router {
 
                     req.set_params(params);
 
-                    if let Err(e) = (route.handler)(req, res).await {
+                    guards.extend(route.guards.iter().cloned());
+                    stack.push(Arc::new(RouteEndpoint {
+                        handler: Arc::clone(&route.handler),
+                        guards,
+                    }));
+                    let result = Next::new(&stack).run(req, res).await;
+
+                    if let Err(e) = result {
                         res.error(e).await;
-                        return Ok(true);
                     }
                     return Ok(true);
                 }
             }
 
             for subr in &self.sub_routers {
-                if subr.handle_internal(req, res).await? {
+                if subr.handle_internal(req, res, &stack, &guards).await? {
                     return Ok(true);
                 }
             }
@@ -388,6 +523,28 @@ impl Router {
     }
 }
 
+/// Terminates a router middleware chain by checking the route's guards and invoking
+/// its handler.
+struct RouteEndpoint {
+    handler: RouteHandler,
+    guards: Vec<Guard>,
+}
+
+impl Middleware for RouteEndpoint {
+    fn handle<'a>(
+        &'a self,
+        req: &'a mut Request,
+        res: &'a mut Response,
+        _next: Next<'a>,
+    ) -> MiddlewareFuture<'a> {
+        if self.guards.iter().all(|guard| guard.check(req)) {
+            (self.handler)(req, res)
+        } else {
+            Box::pin(async { Err(HttpError::Forbidden("Access denied".to_string())) })
+        }
+    }
+}
+
 impl Middleware for Router {
     /// Handles an incoming HTTP request by routing it to the appropriate handler.
     ///
@@ -483,7 +640,7 @@ impl Middleware for Router {
         mut next: Next<'a>,
     ) -> MiddlewareFuture<'a> {
         Box::pin(async move {
-            let matched_route = self.handle_internal(req, res).await;
+            let matched_route = self.handle_internal(req, res, &[], &[]).await;
             if let Err(e) = matched_route {
                 res.error(e).await;
             } else if !matched_route.unwrap_or(false) {
@@ -492,6 +649,18 @@ impl Middleware for Router {
             Ok(())
         })
     }
+
+    /// Returns the state required by this router, its middleware and its sub-routers.
+    fn required_state(&self) -> Vec<StateKey> {
+        let mut required = self.required_state.clone();
+        for mw in &self.middleware {
+            required.extend(mw.required_state());
+        }
+        for sub_router in &self.sub_routers {
+            required.extend(sub_router.required_state());
+        }
+        required
+    }
 }
 
 #[cfg(test)]
@@ -680,4 +849,189 @@ mod tests {
         let next_called = *next_middleware.called.lock().await;
         assert!(!next_called);
     }
+
+    #[tokio::test]
+    async fn test_router_middleware_applies_to_sub_routers() {
+        use crate::auth::Principal;
+        use crate::middleware::AuthMiddleware;
+
+        let mut main_router = Router::new("/");
+        let mut api = Router::new("/api");
+        let mut public = Router::new("/public");
+
+        api.use_middleware(Arc::new(AuthMiddleware::bearer("api", |token: &str| {
+            (token == "secret").then(|| Principal::new("bot"))
+        })));
+        api.get("/whoami$", |req, res| {
+            Box::pin(async move {
+                let id = req.principal().map(|p| p.id().to_string());
+                res.set_status(200).await;
+                res.body(id.unwrap_or_default()).await;
+                Ok(())
+            })
+        });
+        public.get("/hello$", |_req, res| {
+            Box::pin(async move {
+                res.set_status(200).await;
+                res.body("hello".to_string()).await;
+                Ok(())
+            })
+        });
+
+        main_router.mount(api);
+        main_router.mount(public);
+
+        let cases = [
+            ("GET /api/whoami HTTP/1.1\r\n\r\n", 401, None),
+            (
+                "GET /api/whoami HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n",
+                200,
+                Some("bot"),
+            ),
+            ("GET /public/hello HTTP/1.1\r\n\r\n", 200, Some("hello")),
+        ];
+
+        for (request, status, body) in cases {
+            let mut req = Request::new(request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+            let mut res = Response::new(None);
+            let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![];
+
+            main_router
+                .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+                .await
+                .unwrap();
+
+            let inner = res.get_inner().await;
+            assert_eq!(inner.status_code(), Some(status), "{}", request);
+            if let Some(body) = body {
+                assert_eq!(inner.body(), &Some(Body::Text(body.to_string())));
+            } else {
+                assert!(inner.headers().contains_key("WWW-Authenticate"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router_guards() {
+        use crate::auth::Principal;
+        use crate::guard::{require_permission, require_role};
+        use crate::middleware::AuthMiddleware;
+
+        let mut main_router = Router::new("/");
+        let mut admin = Router::new("/admin");
+
+        main_router.use_middleware(Arc::new(AuthMiddleware::bearer(
+            "app",
+            |token: &str| match token {
+                "admin" => Some(Principal::new("alice").with_roles(vec!["admin"])),
+                "writer" => Some(Principal::new("bob").with_permissions(vec!["todos:write"])),
+                _ => Some(Principal::new("carol")),
+            },
+        )));
+        main_router
+            .post("/todos$", |_req, res| {
+                Box::pin(async move {
+                    res.set_status(201).await;
+                    Ok(())
+                })
+            })
+            .guard(require_role("admin").or(require_permission("todos:write")));
+
+        admin.guard(require_role("admin"));
+        admin.get("/stats$", |_req, res| {
+            Box::pin(async move {
+                res.set_status(200).await;
+                Ok(())
+            })
+        });
+        main_router.mount(admin);
+
+        let cases = [
+            ("POST /todos", "admin", 201),
+            ("POST /todos", "writer", 201),
+            ("POST /todos", "reader", 403),
+            ("GET /admin/stats", "admin", 200),
+            ("GET /admin/stats", "writer", 403),
+        ];
+
+        for (request_line, token, status) in cases {
+            let request = format!(
+                "{} HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n",
+                request_line, token
+            );
+            let mut req = Request::new(&request, Arc::new(Mutex::new(HashMap::new()))).unwrap();
+            let mut res = Response::new(None);
+            let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![];
+
+            main_router
+                .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+                .await
+                .unwrap();
+
+            let inner = res.get_inner().await;
+            assert_eq!(
+                inner.status_code(),
+                Some(status),
+                "{} as {}",
+                request_line,
+                token
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extensions_reach_handlers() {
+        #[derive(Clone)]
+        struct Tenant(String);
+
+        #[derive(Clone, Debug, PartialEq)]
+        struct CacheHit(bool);
+
+        struct TenantMiddleware;
+
+        impl Middleware for TenantMiddleware {
+            fn handle<'a>(
+                &'a self,
+                req: &'a mut Request,
+                res: &'a mut Response,
+                mut next: Next<'a>,
+            ) -> MiddlewareFuture<'a> {
+                Box::pin(async move {
+                    let tenant = req.header("X-Tenant").unwrap_or("default").to_string();
+                    req.extensions_mut().insert(Tenant(tenant));
+                    next.run(req, res).await
+                })
+            }
+        }
+
+        let mut router = Router::new("/");
+        router.use_middleware(Arc::new(TenantMiddleware));
+        router.get("/home$", |req, res| {
+            Box::pin(async move {
+                let tenant = req.extensions().get::<Tenant>().unwrap().0.clone();
+                res.extensions_mut().insert(CacheHit(false));
+                res.set_status(200).await;
+                res.body(format!("Hello {}", tenant)).await;
+                Ok(())
+            })
+        });
+
+        let mut req = Request::new(
+            "GET /home HTTP/1.1\r\nX-Tenant: acme\r\n\r\n",
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .unwrap();
+        let mut res = Response::new(None);
+        let middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>> = vec![];
+
+        router
+            .handle(&mut req, &mut res, Next::new(middleware_stack.as_slice()))
+            .await
+            .unwrap();
+
+        let inner = res.get_inner().await;
+        assert_eq!(inner.body(), &Some(Body::Text("Hello acme".to_string())));
+        assert_eq!(res.extensions().get::<CacheHit>(), Some(&CacheHit(false)));
+        assert!(req.extensions().get::<Tenant>().is_some());
+    }
 }
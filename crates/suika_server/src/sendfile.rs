@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result as IoResult, SeekFrom};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite};
+use tokio::net::TcpStream;
+
+/// Copies `length` bytes of `file` starting at `offset` to a TCP stream.
+///
+/// On Linux the data is copied by the kernel with `sendfile(2)`. Files that do not
+/// support it, and other platforms, fall back to `copy_buffered`.
+pub(crate) async fn copy_to_socket(
+    file: File,
+    offset: u64,
+    length: u64,
+    stream: &mut TcpStream,
+) -> IoResult<()> {
+    #[cfg(target_os = "linux")]
+    {
+        if linux::sendfile(&file, offset, length, stream).await? {
+            return Ok(());
+        }
+    }
+    copy_buffered(file, offset, length, stream).await
+}
+
+/// Copies `length` bytes of `file` starting at `offset` to a writer, reading the file
+/// in chunks.
+pub(crate) async fn copy_buffered(
+    file: File,
+    offset: u64,
+    length: u64,
+    stream: &mut (impl AsyncWrite + Unpin),
+) -> IoResult<()> {
+    let mut file = tokio::fs::File::from_std(file);
+    file.seek(SeekFrom::Start(offset)).await?;
+    let copied = tokio::io::copy(&mut file.take(length), stream).await?;
+    if copied < length {
+        return Err(file_truncated());
+    }
+    Ok(())
+}
+
+/// The error for a file that ended before the region being sent.
+fn file_truncated() -> Error {
+    Error::new(
+        ErrorKind::UnexpectedEof,
+        "File ended before the response body",
+    )
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::file_truncated;
+    use std::fs::File;
+    use std::io::{Error, ErrorKind, Result as IoResult};
+    use std::os::unix::io::AsRawFd;
+    use tokio::io::Interest;
+    use tokio::net::TcpStream;
+
+    /// The largest number of bytes passed to a single `sendfile` call.
+    const MAX_CHUNK: u64 = 1 << 30;
+
+    /// Sends a file region with `sendfile(2)`, waiting for the socket to become
+    /// writable whenever its buffer is full.
+    ///
+    /// Returns `false` without sending anything if the file does not support
+    /// `sendfile`, such as files on some network or virtual file systems.
+    pub(super) async fn sendfile(
+        file: &File,
+        offset: u64,
+        length: u64,
+        stream: &TcpStream,
+    ) -> IoResult<bool> {
+        let (socket_fd, file_fd) = (stream.as_raw_fd(), file.as_raw_fd());
+        let mut offset = offset as libc::off_t;
+        let mut sent = 0;
+
+        while sent < length {
+            let count = (length - sent).min(MAX_CHUNK) as usize;
+            stream.writable().await?;
+            let result = stream.try_io(Interest::WRITABLE, || {
+                // SAFETY: both descriptors are open for the duration of the call, and
+                // `offset` is a valid pointer that the kernel advances past the bytes sent.
+                match unsafe { libc::sendfile(socket_fd, file_fd, &mut offset, count) } {
+                    -1 => Err(Error::last_os_error()),
+                    n => Ok(n as u64),
+                }
+            });
+            match result {
+                Ok(0) => return Err(file_truncated()),
+                Ok(n) => sent += n,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+                Err(e)
+                    if sent == 0
+                        && matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) =>
+                {
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn content() -> Vec<u8> {
+        (0..3_000_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn temp_file(content: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn test_copy_to_socket() {
+        let content = content();
+        let file = temp_file(&content);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let reader = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let region = 1_000_003..content.len() as u64 - 17;
+        copy_to_socket(
+            file.reopen().unwrap(),
+            region.start,
+            region.end - region.start,
+            &mut stream,
+        )
+        .await
+        .unwrap();
+        drop(stream);
+
+        let received = reader.await.unwrap();
+        assert_eq!(
+            received,
+            &content[region.start as usize..region.end as usize]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_buffered() {
+        let content = content();
+        let file = temp_file(&content);
+
+        let mut written = Vec::new();
+        copy_buffered(file.reopen().unwrap(), 10, 100_000, &mut written)
+            .await
+            .unwrap();
+        assert_eq!(written, &content[10..100_010]);
+
+        let mut written = Vec::new();
+        let result = copy_buffered(
+            file.reopen().unwrap(),
+            content.len() as u64 - 5,
+            10,
+            &mut written,
+        )
+        .await;
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,6 +1,10 @@
+use crate::diagnostics::{log_debug, log_error, log_info, log_warn};
 use crate::middleware::{Middleware, Next};
+use crate::proxy::TrustedProxies;
+use crate::proxy_protocol::{self, ProxyProtocolMode};
 use crate::request::Request;
 use crate::response::Response;
+use crate::state::{AppState, StateKey};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -16,6 +20,9 @@ pub struct Server {
     middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>>,
     template_engine: Option<TemplateEngine>,
     modules: Arc<Mutex<HashMap<String, Arc<dyn std::any::Any + Send + Sync>>>>,
+    state: Arc<AppState>,
+    trusted_proxies: Option<TrustedProxies>,
+    proxy_protocol: Option<ProxyProtocolMode>,
     shutdown_signal: Option<Arc<Mutex<Option<oneshot::Sender<()>>>>>,
 }
 
@@ -39,6 +46,9 @@ impl Server {
             middleware_stack: Vec::new(),
             template_engine: None,
             modules: Arc::new(Mutex::new(HashMap::new())),
+            state: Arc::new(AppState::new()),
+            trusted_proxies: None,
+            proxy_protocol: None,
             shutdown_signal: None,
         }
     }
@@ -101,6 +111,9 @@ impl Server {
 
     /// Adds a module to the server.
     ///
+    /// Modules are looked up by name and type with `Request::module`, which locks the
+    /// module map on every lookup. Prefer `with_state` for new code.
+    ///
     /// # Arguments
     ///
     /// * `name` - The name of the module.
@@ -121,12 +134,145 @@ impl Server {
         modules.insert(name.to_string(), Arc::new(module));
     }
 
+    /// Registers application state that handlers read with `Request::state::<T>()`.
+    ///
+    /// State is keyed by its type and shared by all requests without locking. It cannot
+    /// be changed once the server is running, so values that change at runtime manage
+    /// their own synchronization.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state to share.
+    ///
+    /// # Panics
+    ///
+    /// Panics if state of the same type is already registered, or if the server has
+    /// been started.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::router::Router;
+    /// use suika_server::server::Server;
+    /// use std::sync::Mutex;
+    ///
+    /// struct TodoStore {
+    ///     todos: Mutex<Vec<String>>,
+    /// }
+    ///
+    /// let mut router = Router::new("/");
+    /// router.requires_state::<TodoStore>();
+    /// router.get("/todos$", |req, res| {
+    ///     Box::pin(async move {
+    ///         let count = req.state::<TodoStore>().todos.lock().unwrap().len();
+    ///         res.set_status(200).await;
+    ///         res.body(format!("{} todos", count)).await;
+    ///         Ok(())
+    ///     })
+    /// });
+    ///
+    /// let mut server = Server::new("127.0.0.1:8080");
+    /// server.with_state(TodoStore { todos: Mutex::new(Vec::new()) });
+    /// server.use_middleware(std::sync::Arc::new(router));
+    /// assert!(server.missing_state().is_empty());
+    /// ```
+    pub fn with_state<T: Send + Sync + 'static>(&mut self, state: T) {
+        let values = Arc::get_mut(&mut self.state)
+            .expect("Application state cannot be changed after the server has started");
+        if values.insert(state).is_some() {
+            panic!(
+                "Application state of type `{}` is registered twice",
+                std::any::type_name::<T>()
+            );
+        }
+    }
+
+    /// Returns the type names of state required by the middleware stack, through
+    /// `Middleware::required_state` or `Router::requires_state`, that has not been
+    /// registered with `with_state`.
+    pub fn missing_state(&self) -> Vec<&'static str> {
+        let required: Vec<StateKey> = self
+            .middleware_stack
+            .iter()
+            .flat_map(|mw| mw.required_state())
+            .collect();
+        self.state.missing(&required)
+    }
+
+    /// Trusts forwarding headers sent by the given reverse proxies.
+    ///
+    /// The client IP, scheme and host of each request are then resolved from the
+    /// `Forwarded` or `X-Forwarded-*` headers when the connecting peer is a trusted proxy.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxies` - The trusted proxies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::proxy::TrustedProxies;
+    /// use suika_server::server::Server;
+    ///
+    /// let mut server = Server::new("127.0.0.1:8080");
+    /// server.use_trusted_proxies(TrustedProxies::new(&["127.0.0.1", "10.0.0.0/8"]).unwrap());
+    /// ```
+    pub fn use_trusted_proxies(&mut self, proxies: TrustedProxies) {
+        self.trusted_proxies = Some(proxies);
+    }
+
+    /// Reads HAProxy PROXY protocol (v1 or v2) preambles from incoming connections.
+    ///
+    /// The source and destination addresses of a preamble replace the socket's peer and
+    /// local addresses on the request. Connections with a malformed preamble, or without
+    /// one in `ProxyProtocolMode::Require` mode, are closed before any request is read.
+    ///
+    /// When trusted proxies are configured with `use_trusted_proxies`, preambles are only
+    /// honored from connections whose peer is a trusted proxy: in `Accept` mode the
+    /// addresses sent by other peers are ignored, and in `Require` mode other peers are
+    /// disconnected. `Accept` mode requires trusted proxies, since any client could
+    /// otherwise spoof its address; in `Require` mode without them, every peer is treated
+    /// as a proxy, so the listener must only be reachable by the proxies.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Whether the preamble is optional or required.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_server::proxy_protocol::ProxyProtocolMode;
+    /// use suika_server::server::Server;
+    ///
+    /// let mut server = Server::new("127.0.0.1:8080");
+    /// server.use_proxy_protocol(ProxyProtocolMode::Require);
+    /// ```
+    ///
+    /// ```
+    /// use suika_server::proxy::TrustedProxies;
+    /// use suika_server::proxy_protocol::ProxyProtocolMode;
+    /// use suika_server::server::Server;
+    ///
+    /// let mut server = Server::new("127.0.0.1:8080");
+    /// server.use_trusted_proxies(TrustedProxies::new(&["10.0.0.0/8"]).unwrap());
+    /// server.use_proxy_protocol(ProxyProtocolMode::Accept);
+    /// ```
+    pub fn use_proxy_protocol(&mut self, mode: ProxyProtocolMode) {
+        self.proxy_protocol = Some(mode);
+    }
+
     /// Runs the server. If an existing runtime handle is provided, it is used to run the server.
     ///
     /// # Arguments
     ///
     /// * `existing_runtime` - An optional reference to an existing tokio runtime handle.
     ///
+    /// # Panics
+    ///
+    /// Panics before binding the address if state required by the middleware stack has
+    /// not been registered with `with_state`, or if `ProxyProtocolMode::Accept` is used
+    /// without trusted proxies.
+    ///
     /// # Examples
     ///
     /// ```rust,ignore
@@ -139,6 +285,20 @@ impl Server {
     /// }
     /// ```
     pub fn run(&mut self, existing_runtime: Option<&Handle>) {
+        let missing = self.missing_state();
+        if !missing.is_empty() {
+            panic!(
+                "Missing application state: {}; register it with `Server::with_state`",
+                missing.join(", ")
+            );
+        }
+        if self.proxy_protocol == Some(ProxyProtocolMode::Accept) && self.trusted_proxies.is_none()
+        {
+            panic!(
+                "ProxyProtocolMode::Accept requires trusted proxies; configure them with `Server::use_trusted_proxies`"
+            );
+        }
+
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
         self.shutdown_signal = Some(Arc::new(Mutex::new(Some(shutdown_tx))));
 
@@ -146,6 +306,9 @@ impl Server {
         let middleware_stack = self.middleware_stack.clone();
         let template_engine = self.template_engine.clone();
         let modules = Arc::clone(&self.modules);
+        let state = Arc::clone(&self.state);
+        let trusted_proxies = self.trusted_proxies.clone().map(Arc::new);
+        let proxy_protocol = self.proxy_protocol;
 
         if let Some(handle) = existing_runtime {
             handle.spawn(async move {
@@ -154,6 +317,9 @@ impl Server {
                     middleware_stack,
                     template_engine,
                     modules,
+                    state,
+                    trusted_proxies,
+                    proxy_protocol,
                     shutdown_rx,
                 )
                 .await;
@@ -175,6 +341,9 @@ impl Server {
                     middleware_stack,
                     template_engine,
                     modules,
+                    state,
+                    trusted_proxies,
+                    proxy_protocol,
                     shutdown_rx,
                 )
                 .await;
@@ -190,56 +359,129 @@ impl Server {
     /// * `middleware_stack` - A vector of middleware to be applied to each request.
     /// * `template_engine` - An optional template engine for rendering responses.
     /// * `modules` - A collection of modules that can be used by the server.
+    /// * `state` - The application state shared by all requests.
+    /// * `trusted_proxies` - Proxies whose forwarding headers are trusted.
+    /// * `proxy_protocol` - Whether connections start with a PROXY protocol preamble.
     /// * `shutdown_rx` - A receiver for the shutdown signal.
+    #[allow(clippy::too_many_arguments)]
     async fn run_server(
         address: String,
         middleware_stack: Vec<Arc<dyn Middleware + Send + Sync>>,
         template_engine: Option<TemplateEngine>,
         modules: Arc<Mutex<HashMap<String, Arc<dyn std::any::Any + Send + Sync>>>>,
+        state: Arc<AppState>,
+        trusted_proxies: Option<Arc<TrustedProxies>>,
+        proxy_protocol: Option<ProxyProtocolMode>,
         mut shutdown_rx: oneshot::Receiver<()>,
     ) {
         let listener = TcpListener::bind(&address)
             .await
             .expect("Failed to bind address");
 
-        println!("Server running on {}", address);
+        log_info!(address = %address, "Server running");
 
         loop {
             tokio::select! {
                 _ = &mut shutdown_rx => {
-                    println!("Server shutting down");
+                    log_info!("Server shutting down");
                     break;
                 }
-                Ok((mut stream, _)) = listener.accept() => {
+                Ok((mut stream, peer_addr)) = listener.accept() => {
                     let mw_stack = middleware_stack.clone();
                     let tmpl_engine = template_engine.clone().map(Arc::new);
                     let modules = Arc::clone(&modules);
+                    let state = Arc::clone(&state);
+                    let trusted_proxies = trusted_proxies.clone();
 
                     tokio::spawn(async move {
-                        let mut buffer = [0; 1024];
-                        if let Ok(size) = stream.read(&mut buffer).await {
-                            if size > 0 {
-                                let request_str = String::from_utf8_lossy(&buffer[..size]);
-                                let mut req = Request::new(&request_str, Arc::clone(&modules)).unwrap();
-                                let mut res = Response::new(tmpl_engine.clone());
+                        let mut data = Vec::new();
+                        let mut peer_addr = peer_addr;
+                        let mut local_addr = stream.local_addr().ok();
+
+                        if let Some(mode) = proxy_protocol {
+                            let trusted = trusted_proxies
+                                .as_ref()
+                                .is_none_or(|proxies| proxies.is_trusted(peer_addr.ip()));
+                            if !trusted && mode == ProxyProtocolMode::Require {
+                                log_warn!(peer = %peer_addr, "Rejected connection from untrusted proxy");
+                                return;
+                            }
+                            match proxy_protocol::read_preamble(&mut stream, mode, &mut data).await {
+                                Ok(Some(header)) if trusted => {
+                                    peer_addr = header.source.unwrap_or(peer_addr);
+                                    local_addr = header.destination.or(local_addr);
+                                }
+                                Ok(Some(_)) => {
+                                    log_warn!(peer = %peer_addr, "Ignored PROXY protocol preamble from untrusted peer");
+                                }
+                                Ok(None) => {}
+                                Err(e) => {
+                                    log_warn!(peer = %peer_addr, error = %e, "Rejected connection");
+                                    return;
+                                }
+                            }
+                        }
+
+                        if let Err(e) = read_request(&mut stream, &mut data).await {
+                            if let ReadError::Io(error) = &e {
+                                log_debug!(peer = %peer_addr, error = %error, "Incomplete request");
+                            } else if let Some((status, message)) = e.response() {
+                                log_debug!(peer = %peer_addr, status = %status, "Rejected oversized request");
+                                let res = Response::new(None);
+                                res.set_status(status).await;
+                                res.header("Connection", "close").await;
+                                res.body(message.to_string()).await;
+                                let _ = res.send_tcp(&mut stream).await;
+                            }
+                            return;
+                        }
+
+                        if !data.is_empty() {
+                            let mut req = match Request::from_bytes(&data, Arc::clone(&modules)) {
+                                Ok(req) => req,
+                                Err(_) => return,
+                            };
+                            req.set_state(state);
+                            req.set_peer_addr(peer_addr);
+                            if let Some(local_addr) = local_addr {
+                                req.set_local_addr(local_addr);
+                            }
+                            if let Some(proxies) = &trusted_proxies {
+                                proxies.apply(&mut req);
+                            }
+                            let mut res = Response::new(tmpl_engine.clone());
 
+                            #[cfg(feature = "tracing")]
+                            let span = crate::diagnostics::request_span(&req);
+                            let handled = async {
                                 let mut next = Next::new(&mw_stack);
                                 if let Err(e) = next.run(&mut req, &mut res).await {
+                                    log_debug!(error = %e, "Request failed");
                                     res.error(e).await;
                                 }
+                            };
+                            #[cfg(feature = "tracing")]
+                            let handled = tracing::Instrument::instrument(handled, span);
+                            handled.await;
 
-                                let status = res.status().await;
-                                if status == None {
-                                    res.set_status(404).await;
-                                    res.body("404 Not Found".to_string()).await;
-                                }
+                            let status = res.status().await;
+                            if status.is_none() {
+                                res.set_status(404).await;
+                                res.body("404 Not Found".to_string()).await;
+                            }
 
-                                res.send(&mut stream).await.unwrap();
+                            let sent = if req.method() == "HEAD" {
+                                res.send_head(&mut stream).await
+                            } else {
+                                res.send_tcp(&mut stream).await
+                            };
+                            if let Err(e) = sent {
+                                log_debug!(peer = %peer_addr, error = %e, "Failed to send response");
                             }
                         }
                     });
                 }
-                else => eprintln!("Failed to accept connection"),
+                else => log_error!("Failed to accept connection"),
             }
         }
     }
@@ -276,6 +518,87 @@ impl Server {
     }
 }
 
+/// The maximum size of a request head, in bytes.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// The maximum size of a request body read from a connection, in bytes.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The reasons a request could not be read completely.
+#[derive(Debug)]
+enum ReadError {
+    /// The head exceeded `MAX_HEAD_SIZE` without ending.
+    HeadTooLarge,
+    /// The `Content-Length` exceeded `MAX_BODY_SIZE`.
+    BodyTooLarge,
+    /// The connection failed or closed before the request was complete.
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(error: std::io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl ReadError {
+    /// Returns the status and message to answer an oversized request with, if any.
+    fn response(&self) -> Option<(u16, &'static str)> {
+        match self {
+            ReadError::HeadTooLarge => Some((431, "Request Header Fields Too Large")),
+            ReadError::BodyTooLarge => Some((413, "Payload Too Large")),
+            ReadError::Io(_) => None,
+        }
+    }
+}
+
+/// Reads a request into `data`, which may already hold bytes read from the stream,
+/// until the head is complete and the body announced by `Content-Length` has arrived.
+///
+/// Leaves `data` empty if the peer closes the connection before sending anything.
+/// Requests exceeding the size limits, and requests cut short by the peer, are
+/// errors, so a partial request is never handled.
+async fn read_request(
+    stream: &mut (impl AsyncReadExt + Unpin),
+    data: &mut Vec<u8>,
+) -> Result<(), ReadError> {
+    let mut buffer = [0; 8192];
+    loop {
+        match data.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) if end > MAX_HEAD_SIZE => return Err(ReadError::HeadTooLarge),
+            Some(end) => {
+                let head = String::from_utf8_lossy(&data[..end]);
+                let content_length = head
+                    .split("\r\n")
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("Content-Length"))
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if content_length > MAX_BODY_SIZE {
+                    return Err(ReadError::BodyTooLarge);
+                }
+                if data.len() >= end + 4 + content_length {
+                    return Ok(());
+                }
+            }
+            None if data.len() > MAX_HEAD_SIZE => return Err(ReadError::HeadTooLarge),
+            None => {}
+        }
+
+        let size = stream.read(&mut buffer).await?;
+        if size == 0 {
+            if data.is_empty() {
+                return Ok(());
+            }
+            return Err(ReadError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Connection closed before the request was complete",
+            )));
+        }
+        data.extend_from_slice(&buffer[..size]);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -374,4 +697,395 @@ mod tests {
 
         server.stop();
     }
+
+    // Middleware that echoes the resolved client address
+    struct ClientEchoMiddleware;
+
+    impl Middleware for ClientEchoMiddleware {
+        fn handle<'a>(
+            &'a self,
+            req: &'a mut Request,
+            res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                let client = format!(
+                    "{} {}",
+                    req.client_ip().map(|ip| ip.to_string()).unwrap_or_default(),
+                    req.scheme()
+                );
+                res.set_status(200).await;
+                res.body(client).await;
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_resolves_client_through_trusted_proxy() {
+        let address = "127.0.0.1:8083";
+        let mut server = Server::new(address);
+        server.use_trusted_proxies(TrustedProxies::new(&["127.0.0.1"]).unwrap());
+        server.use_middleware(Arc::new(ClientEchoMiddleware));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.7\r\nX-Forwarded-Proto: https\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut buffer = [0; 1024];
+        let size = stream.read(&mut buffer).await.unwrap();
+        let response_str = String::from_utf8_lossy(&buffer[..size]);
+
+        assert!(
+            response_str.contains("203.0.113.7 https"),
+            "Response: {}",
+            response_str
+        );
+
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_server_proxy_protocol() {
+        let address = "127.0.0.1:8084";
+        let mut server = Server::new(address);
+        server.use_proxy_protocol(ProxyProtocolMode::Require);
+        server.use_middleware(Arc::new(ClientEchoMiddleware));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        // v1 preamble followed by the request in the same write.
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.contains("203.0.113.7 http"),
+            "Response: {}",
+            response
+        );
+
+        // v2 preamble written separately from the request.
+        let mut preamble = b"\r\n\r\n\0\r\nQUIT\n\x21\x21\x00\x24".to_vec();
+        preamble.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        preamble.extend_from_slice(&[0; 11]);
+        preamble.push(1);
+        preamble.extend_from_slice(&[0; 15]);
+        preamble.push(1);
+        preamble.extend_from_slice(&[0x10, 0x00, 0x00, 0x50]);
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(&preamble).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.contains("2001:db8::1 http"),
+            "Response: {}",
+            response
+        );
+
+        // Connections without a preamble are closed without a response.
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(response.is_empty());
+
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_server_proxy_protocol_requires_trusted_peer() {
+        let address = "127.0.0.1:8090";
+        let mut server = Server::new(address);
+        server.use_trusted_proxies(TrustedProxies::new(&["10.0.0.0/8"]).unwrap());
+        server.use_proxy_protocol(ProxyProtocolMode::Accept);
+        server.use_middleware(Arc::new(ClientEchoMiddleware));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        // The test client is not a trusted proxy, so its preamble cannot spoof an address.
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.contains("127.0.0.1 http"),
+            "Response: {}",
+            response
+        );
+
+        server.stop();
+
+        let address = "127.0.0.1:8091";
+        let mut server = Server::new(address);
+        server.use_trusted_proxies(TrustedProxies::new(&["10.0.0.0/8"]).unwrap());
+        server.use_proxy_protocol(ProxyProtocolMode::Require);
+        server.use_middleware(Arc::new(ClientEchoMiddleware));
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        assert!(response.is_empty());
+
+        server.stop();
+    }
+
+    #[test]
+    #[should_panic(expected = "requires trusted proxies")]
+    fn test_proxy_protocol_accept_requires_trusted_proxies() {
+        let mut server = Server::new("127.0.0.1:8092");
+        server.use_proxy_protocol(ProxyProtocolMode::Accept);
+        server.run(None);
+    }
+
+    // Middleware that echoes the length of the request body
+    struct BodyLengthMiddleware;
+
+    impl Middleware for BodyLengthMiddleware {
+        fn handle<'a>(
+            &'a self,
+            req: &'a mut Request,
+            res: &'a mut Response,
+            _next: Next<'a>,
+        ) -> MiddlewareFuture<'a> {
+            Box::pin(async move {
+                res.set_status(200).await;
+                res.body(format!("length={}", req.body_bytes().len())).await;
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_reads_compressed_body() {
+        let address = "127.0.0.1:8085";
+        let mut server = Server::new(address);
+        server.use_middleware(Arc::new(crate::middleware::DecompressionMiddleware::new()));
+        server.use_middleware(Arc::new(BodyLengthMiddleware));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        // Pseudo-random data so the compressed body spans several reads.
+        let mut state = 0x2545_f491_u32;
+        let data: Vec<u8> = (0..50_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let body = crate::encoding::ContentEncoding::Gzip
+            .encode(&data)
+            .unwrap();
+        assert!(body.len() > 8192);
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let head = format!(
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        for chunk in body.chunks(4096) {
+            stream.write_all(chunk).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.contains("length=50000"), "Response: {}", response);
+
+        server.stop();
+    }
+
+    struct Greeting(&'static str);
+
+    fn greeting_router() -> crate::router::Router {
+        let mut router = crate::router::Router::new("/");
+        let mut api = crate::router::Router::new("/api");
+        api.requires_state::<Greeting>();
+        api.get("/hello$", |req, res| {
+            Box::pin(async move {
+                res.set_status(200).await;
+                res.body(req.state::<Greeting>().0.to_string()).await;
+                Ok(())
+            })
+        });
+        router.mount(api);
+        router
+    }
+
+    #[tokio::test]
+    async fn test_server_shares_state() {
+        let address = "127.0.0.1:8086";
+        let mut server = Server::new(address);
+        server.with_state(Greeting("Hello from state"));
+        server.use_middleware(Arc::new(greeting_router()));
+        assert!(server.missing_state().is_empty());
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /api/hello HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.contains("Hello from state"),
+            "Response: {}",
+            response
+        );
+
+        server.stop();
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing application state")]
+    fn test_missing_state_fails_at_startup() {
+        let mut server = Server::new("127.0.0.1:8087");
+        server.use_middleware(Arc::new(greeting_router()));
+        assert_eq!(
+            server.missing_state(),
+            vec![std::any::type_name::<Greeting>()]
+        );
+        server.run(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    fn test_duplicate_state_fails() {
+        let mut server = Server::new("127.0.0.1:8088");
+        server.with_state(Greeting("first"));
+        server.with_state(Greeting("second"));
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_oversized_requests() {
+        let address = "127.0.0.1:8089";
+        let mut server = Server::new(address);
+        let mock_middleware = MockMiddleware::new();
+        server.use_middleware(Arc::new(mock_middleware.clone()));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let head = format!(
+            "POST /upload HTTP/1.1\r\nContent-Length: {}\r\n\r\npartial",
+            MAX_BODY_SIZE + 1
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 413"),
+            "Response: {}",
+            response
+        );
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let head = format!(
+            "GET / HTTP/1.1\r\nX-Padding: {}\r\n",
+            "a".repeat(MAX_HEAD_SIZE)
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 431"),
+            "Response: {}",
+            response
+        );
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.is_empty(), "Response: {}", response);
+
+        assert!(!*mock_middleware.called.lock().await);
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_server_sends_no_body_for_head() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("video.bin"), [b'a'; 5000]).unwrap();
+
+        let address = "127.0.0.1:8093";
+        let mut server = Server::new(address);
+        server.use_middleware(Arc::new(crate::middleware::StaticFileMiddleware::new(
+            "/static",
+            dir.path().to_str().unwrap(),
+            3600,
+        )));
+
+        let runtime_handle = tokio::runtime::Handle::current();
+        server.run(Some(&runtime_handle));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"HEAD /static/video.bin HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 200"),
+            "Response: {}",
+            response
+        );
+        assert!(response.contains("Content-Length: 5000\r\n"));
+        assert!(response.ends_with("\r\n\r\n"), "Response: {}", response);
+
+        server.stop();
+    }
 }
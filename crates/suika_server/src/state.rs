@@ -0,0 +1,131 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Identifies a type of application state, for declaring the state a router or
+/// middleware needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateKey {
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl StateKey {
+    /// Returns the key of state of type `T`.
+    pub fn of<T: Send + Sync + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// Returns the name of the state's type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+/// Application state shared by all requests, keyed by type.
+///
+/// State is registered with `Server::with_state` before the server starts and is
+/// read-only afterwards, so handlers read it through `Request::state` without
+/// locking. Values that change at runtime manage their own synchronization, such as
+/// a store wrapping a `Mutex` or a connection pool.
+///
+/// # Examples
+///
+/// ```
+/// use suika_server::state::AppState;
+///
+/// struct Config {
+///     site_name: String,
+/// }
+///
+/// let mut state = AppState::new();
+/// state.insert(Config { site_name: "Suika".to_string() });
+///
+/// assert_eq!(state.get::<Config>().map(|c| c.site_name.as_str()), Some("Suika"));
+/// assert!(state.get::<String>().is_none());
+/// ```
+#[derive(Default)]
+pub struct AppState {
+    values: HashMap<TypeId, (&'static str, Box<dyn Any + Send + Sync>)>,
+}
+
+impl AppState {
+    /// Creates an empty `AppState`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type, if any.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), (type_name::<T>(), Box::new(value)))
+            .and_then(|(_, previous)| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns a reference to the value of type `T`, if any.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|(_, value)| value.downcast_ref())
+    }
+
+    /// Returns `true` if state with the given key is present.
+    pub fn contains(&self, key: &StateKey) -> bool {
+        self.values.contains_key(&key.type_id)
+    }
+
+    /// Returns the type names of the keys that have no state, without duplicates.
+    pub fn missing(&self, keys: &[StateKey]) -> Vec<&'static str> {
+        let mut missing: Vec<&'static str> = Vec::new();
+        for key in keys {
+            if !self.contains(key) && !missing.contains(&key.type_name) {
+                missing.push(key.type_name);
+            }
+        }
+        missing
+    }
+}
+
+impl fmt::Debug for AppState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.values.values().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Store(u32);
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut state = AppState::new();
+        assert!(state.get::<Store>().is_none());
+        assert!(state.insert(Store(1)).is_none());
+        assert_eq!(state.insert(Store(2)), Some(Store(1)));
+        assert_eq!(state.get::<Store>(), Some(&Store(2)));
+        assert!(state.contains(&StateKey::of::<Store>()));
+        assert!(!state.contains(&StateKey::of::<u32>()));
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut state = AppState::new();
+        state.insert(Store(1));
+        let keys = [
+            StateKey::of::<Store>(),
+            StateKey::of::<String>(),
+            StateKey::of::<String>(),
+        ];
+        assert_eq!(state.missing(&keys), vec!["alloc::string::String"]);
+        assert_eq!(StateKey::of::<Store>().type_name(), type_name::<Store>());
+    }
+}
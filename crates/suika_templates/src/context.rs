@@ -33,6 +33,12 @@ pub struct Context {
     macro_args: Option<HashMap<String, JsonValue>>,
 }
 
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Context {
     /// Creates a new, empty `Context`.
     ///
@@ -143,7 +149,7 @@ impl Context {
         })
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<String, JsonValue> {
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, JsonValue> {
         self.values.iter()
     }
 }
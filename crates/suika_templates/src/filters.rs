@@ -102,6 +102,12 @@ impl IntoJsonValue for JsonValue {
     }
 }
 
+/// A type-erased filter function operating on JSON values.
+pub type FilterFn = dyn Fn(JsonValue, Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync;
+
+/// A type-erased template function called with its arguments as JSON values.
+pub type FunctionFn = dyn Fn(Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync;
+
 /// A registry for storing and managing template filters.
 ///
 /// # Examples
@@ -113,10 +119,14 @@ impl IntoJsonValue for JsonValue {
 /// registry.register("upper", |value: String| Ok(value.to_uppercase()));
 /// ```
 pub struct FilterRegistry {
-    filters: HashMap<
-        String,
-        Arc<dyn Fn(JsonValue, Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync>,
-    >,
+    filters: HashMap<String, Arc<FilterFn>>,
+    functions: HashMap<String, Arc<FunctionFn>>,
+}
+
+impl Default for FilterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FilterRegistry {
@@ -124,6 +134,7 @@ impl FilterRegistry {
     pub fn new() -> Self {
         let mut registry = Self {
             filters: HashMap::new(),
+            functions: HashMap::new(),
         };
         registry.register_default_filters();
         registry
@@ -178,14 +189,42 @@ impl FilterRegistry {
     /// # Returns
     ///
     /// An Option containing a reference to the filter function if found
-    pub fn get(
-        &self,
-        name: &str,
-    ) -> Option<&(dyn Fn(JsonValue, Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync)>
-    {
+    pub fn get(&self, name: &str) -> Option<&FilterFn> {
         self.filters.get(name).map(|f| f.as_ref())
     }
 
+    /// Registers a function that can be called in variable expressions, such as
+    /// `<%= asset("style.css") %>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function to register
+    /// * `function` - The function, called with its evaluated arguments
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    /// use suika_templates::filters::FilterRegistry;
+    ///
+    /// let mut registry = FilterRegistry::new();
+    /// registry.register_function("count", |args| Ok(JsonValue::from(args.len())));
+    ///
+    /// let function = registry.get_function("count").unwrap();
+    /// assert_eq!(function(vec![JsonValue::Null]).unwrap(), JsonValue::Number(1.0));
+    /// ```
+    pub fn register_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(name.to_string(), Arc::new(function));
+    }
+
+    /// Gets a function by name.
+    pub fn get_function(&self, name: &str) -> Option<&FunctionFn> {
+        self.functions.get(name).map(|f| f.as_ref())
+    }
+
     /// Registers the default set of filters (upper, lower, length).
     fn register_default_filters(&mut self) {
         // String transformations
@@ -262,6 +301,7 @@ impl fmt::Debug for FilterRegistry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FilterRegistry")
             .field("filters", &self.filters.keys())
+            .field("functions", &self.functions.keys())
             .finish()
     }
 }
@@ -270,6 +310,7 @@ impl Clone for FilterRegistry {
     fn clone(&self) -> Self {
         let mut new_registry = Self {
             filters: HashMap::new(),
+            functions: self.functions.clone(),
         };
         for (name, filter) in &self.filters {
             let filter = filter.clone();
@@ -11,14 +11,23 @@ use std::sync::{Arc, Mutex};
 use suika_json::{json, JsonValue};
 use suika_utils::minify_html;
 
+/// Macro definitions keyed by name, holding the defining template, parameters and body.
+type MacroMap = HashMap<String, (String, Vec<String>, Vec<TemplateToken>)>;
+
 #[derive(Debug, Clone)]
 pub struct TemplateEngine {
     templates: HashMap<String, String>,
     filters: FilterRegistry,
-    macros: Arc<Mutex<HashMap<String, (String, Vec<String>, Vec<TemplateToken>)>>>,
+    macros: Arc<Mutex<MacroMap>>,
     included_templates: Arc<Mutex<HashSet<String>>>,
 }
 
+impl Default for TemplateEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TemplateEngine {
     /// Creates a new `TemplateEngine`.
     ///
@@ -308,12 +317,27 @@ impl TemplateEngine {
         context: &Context,
         output: &mut String,
     ) -> Result<(), String> {
-        if let Some(value_str) = self.resolve_variable(name, context) {
-            let mut value = match context.get(name) {
-                Some(v) => v.clone(),
-                None => JsonValue::String(value_str),
-            };
+        let value = match parse_function_call(name) {
+            Some((function_name, args)) => {
+                let function = self
+                    .filters
+                    .get_function(function_name)
+                    .ok_or_else(|| format!("Function '{}' not found", function_name))?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate_argument(arg, context))
+                    .collect();
+                Some(function(args)?)
+            }
+            None => self
+                .resolve_variable(name, context)
+                .map(|value_str| match context.get(name) {
+                    Some(v) => v.clone(),
+                    None => JsonValue::String(value_str),
+                }),
+        };
 
+        if let Some(mut value) = value {
             // Apply filters in order
             for filter_name in filters {
                 if let Some(filter) = self.filters.get(filter_name) {
@@ -336,6 +360,37 @@ impl TemplateEngine {
         }
     }
 
+    /// Evaluates a function argument: a quoted string, a number, a boolean or a
+    /// variable, which is `null` if it is not defined.
+    fn evaluate_argument(&self, arg: &str, context: &Context) -> JsonValue {
+        let quoted = ['"', '\'']
+            .iter()
+            .find(|q| arg.len() >= 2 && arg.starts_with(**q) && arg.ends_with(**q));
+        if quoted.is_some() {
+            return JsonValue::String(arg[1..arg.len() - 1].to_string());
+        }
+        if let Ok(number) = arg.parse::<f64>() {
+            return JsonValue::Number(number);
+        }
+        match arg {
+            "true" => JsonValue::Boolean(true),
+            "false" => JsonValue::Boolean(false),
+            _ => {
+                let mut parts = arg.split('.');
+                let mut value = parts.next().and_then(|part| context.get(part));
+                for part in parts {
+                    value = match value {
+                        Some(JsonValue::Object(map)) => {
+                            map.iter().find(|(k, _)| k == part).map(|(_, v)| v)
+                        }
+                        _ => None,
+                    };
+                }
+                value.cloned().unwrap_or(JsonValue::Null)
+            }
+        }
+    }
+
     fn resolve_variable(&self, name: &str, context: &Context) -> Option<String> {
         let parts: Vec<&str> = name.split('.').collect();
         let mut current_value = context.get(parts[0])?;
@@ -462,7 +517,9 @@ impl TemplateEngine {
             match &tokens[j] {
                 TemplateToken::Break => return Ok((output, true, false, j)),
                 TemplateToken::Continue => return Ok((output, false, true, j)),
-                token => output.push_str(&self.process_tokens(&[token.clone()], context)?),
+                token => {
+                    output.push_str(&self.process_tokens(std::slice::from_ref(token), context)?)
+                }
             }
             j += 1;
         }
@@ -506,7 +563,7 @@ impl TemplateEngine {
                     }
                 }
                 token => {
-                    output.push_str(&self.process_tokens(&[token.clone()], context)?);
+                    output.push_str(&self.process_tokens(std::slice::from_ref(token), context)?);
                 }
             }
             j += 1;
@@ -603,6 +660,35 @@ impl TemplateEngine {
         self.filters.register(name, filter);
     }
 
+    /// Registers a function that can be called in variable expressions.
+    ///
+    /// Arguments can be quoted strings, numbers, booleans or variables, and the result
+    /// can be passed through filters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use suika_json::JsonValue;
+    /// use suika_templates::context::Context;
+    /// use suika_templates::template_engine::TemplateEngine;
+    ///
+    /// let mut engine = TemplateEngine::new();
+    /// engine.register_function("greet", |args| {
+    ///     let name = args.first().and_then(|arg| arg.as_str()).unwrap_or("stranger");
+    ///     Ok(JsonValue::String(format!("Hello, {}!", name)))
+    /// });
+    /// engine.add_template("hello", "<%= greet(\"World\")|upper %>");
+    ///
+    /// let result = engine.render("hello", &Context::new()).unwrap();
+    /// assert_eq!(result, "HELLO, WORLD!");
+    /// ```
+    pub fn register_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(Vec<JsonValue>) -> Result<JsonValue, String> + Send + Sync + 'static,
+    {
+        self.filters.register_function(name, function);
+    }
+
     fn process_macro_call(
         &self,
         name: &str,
@@ -657,7 +743,7 @@ impl TemplateEngine {
             }
 
             let result = self.process_tokens(&tokens, &macro_context)?;
-            output.push_str(&result.trim());
+            output.push_str(result.trim());
             Ok(())
         } else {
             Err(format!("Macro '{}' not found", name))
@@ -704,6 +790,37 @@ impl TemplateEngine {
     }
 }
 
+/// Splits a variable expression of the form `name(arg, ...)` into the function name and
+/// its unevaluated arguments. Commas inside quoted strings do not separate arguments.
+fn parse_function_call(expression: &str) -> Option<(&str, Vec<&str>)> {
+    let (name, rest) = expression.split_once('(')?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    let args = rest.trim_end().strip_suffix(')')?;
+
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() || !parts.is_empty() {
+        parts.push(last);
+    }
+    Some((name, parts))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -737,6 +854,39 @@ mod tests {
         assert_eq!(result, "Hello, World!");
     }
 
+    #[test]
+    fn test_render_function_call() {
+        let mut engine = TemplateEngine::new();
+        engine.register_function("join_args", |args| {
+            Ok(JsonValue::String(
+                args.iter()
+                    .map(|arg| match arg {
+                        JsonValue::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("+"),
+            ))
+        });
+        engine.add_template(
+            "call",
+            "<%= join_args(\"a, b\", 2, user.name, missing, 'c')|upper %>",
+        );
+        engine.add_template("unknown", "<%= nope(1) %>");
+
+        let mut context = Context::new();
+        context.insert("user", json!({ "name" => "ann" }));
+
+        assert_eq!(
+            engine.render("call", &context).unwrap(),
+            "A, B+2+ANN+NULL+C"
+        );
+        assert_eq!(
+            engine.render("unknown", &context),
+            Err("Function 'nope' not found".to_string())
+        );
+    }
+
     #[test]
     fn test_render_struct() {
         let mut engine = TemplateEngine::new();
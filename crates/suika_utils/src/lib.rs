@@ -306,6 +306,191 @@ pub fn noop_waker() -> Waker {
     unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
 }
 
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes bytes as base64 using the given alphabet, optionally padding with `=`.
+fn encode_base64_with(input: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(alphabet[((n >> (18 - i * 6)) & 0x3f) as usize] as char);
+            } else if pad {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+/// Decodes base64 using the given alphabet. Trailing `=` padding is optional.
+fn decode_base64_with(input: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>, String> {
+    let input = input.trim_end_matches('=');
+    if input.len() % 4 == 1 {
+        return Err("Invalid base64 length".to_string());
+    }
+
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let value = alphabet
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| format!("Invalid base64 character: {}", c as char))?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Encodes bytes as standard, padded base64.
+///
+/// # Arguments
+///
+/// * `input` - The bytes to encode.
+///
+/// # Returns
+///
+/// A String containing the base64 representation of the input.
+///
+/// # Examples
+///
+/// ```
+/// use suika_utils::base64_encode;
+/// assert_eq!(base64_encode(b"user:pass"), "dXNlcjpwYXNz");
+/// assert_eq!(base64_encode(b"hi"), "aGk=");
+/// ```
+pub fn base64_encode(input: &[u8]) -> String {
+    encode_base64_with(input, BASE64_ALPHABET, true)
+}
+
+/// Decodes standard base64, with or without padding.
+///
+/// # Arguments
+///
+/// * `input` - A string slice that holds the base64 data.
+///
+/// # Returns
+///
+/// A Result containing the decoded bytes, or an Err with a descriptive message if the input
+/// is not valid base64.
+///
+/// # Examples
+///
+/// ```
+/// use suika_utils::base64_decode;
+/// assert_eq!(base64_decode("dXNlcjpwYXNz").unwrap(), b"user:pass");
+/// assert_eq!(base64_decode("aGk=").unwrap(), b"hi");
+/// assert!(base64_decode("not base64!").is_err());
+/// ```
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
+    decode_base64_with(input, BASE64_ALPHABET)
+}
+
+/// Encodes bytes as unpadded, URL-safe base64 (RFC 4648 §5), as used by JWTs.
+///
+/// # Arguments
+///
+/// * `input` - The bytes to encode.
+///
+/// # Returns
+///
+/// A String containing the base64url representation of the input.
+///
+/// # Examples
+///
+/// ```
+/// use suika_utils::base64url_encode;
+/// assert_eq!(base64url_encode(&[0xfb, 0xff]), "-_8");
+/// ```
+pub fn base64url_encode(input: &[u8]) -> String {
+    encode_base64_with(input, BASE64URL_ALPHABET, false)
+}
+
+/// Decodes URL-safe base64, with or without padding.
+///
+/// # Arguments
+///
+/// * `input` - A string slice that holds the base64url data.
+///
+/// # Returns
+///
+/// A Result containing the decoded bytes, or an Err with a descriptive message if the input
+/// is not valid base64url.
+///
+/// # Examples
+///
+/// ```
+/// use suika_utils::base64url_decode;
+/// assert_eq!(base64url_decode("-_8").unwrap(), vec![0xfb, 0xff]);
+/// assert!(base64url_decode("+/8").is_err());
+/// ```
+pub fn base64url_decode(input: &str) -> Result<Vec<u8>, String> {
+    decode_base64_with(input, BASE64URL_ALPHABET)
+}
+
+/// Decodes percent-encoded bytes in a URL path segment.
+///
+/// Unlike query string decoding, `+` is left as is.
+///
+/// # Arguments
+///
+/// * `input` - A string slice that holds the percent-encoded data.
+///
+/// # Returns
+///
+/// A Result containing the decoded string, or an Err with a descriptive message if an
+/// escape is malformed or the decoded bytes are not valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use suika_utils::percent_decode;
+/// assert_eq!(percent_decode("/my%20file+1.txt").unwrap(), "/my file+1.txt");
+/// assert_eq!(percent_decode("%2e%2E").unwrap(), "..");
+/// assert!(percent_decode("%zz").is_err());
+/// ```
+pub fn percent_decode(input: &str) -> Result<String, String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("Invalid percent escape at position {}", i))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "Decoded data is not valid UTF-8".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -446,6 +631,42 @@ mod tests {
         assert_eq!(minified, expected);
     }
 
+    #[test]
+    fn test_base64_round_trip() {
+        let cases: [&[u8]; 5] = [b"", b"f", b"fo", b"foo", b"foob\xff\x00"];
+        let encoded = ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYv8A"];
+
+        for (input, expected) in cases.iter().zip(encoded.iter()) {
+            assert_eq!(base64_encode(input), *expected);
+            assert_eq!(base64_decode(expected).unwrap(), *input);
+        }
+    }
+
+    #[test]
+    fn test_base64_decode_invalid() {
+        assert!(base64_decode("Zm9v*").is_err());
+        assert!(base64_decode("Z").is_err());
+        assert_eq!(base64_decode("Zg").unwrap(), b"f");
+    }
+
+    #[test]
+    fn test_base64url_round_trip() {
+        let input = b"{\"alg\":\"HS256\"}\xfb\xff";
+        let encoded = base64url_encode(input);
+        assert!(!encoded.contains('=') && !encoded.contains('+') && !encoded.contains('/'));
+        assert_eq!(base64url_decode(&encoded).unwrap(), input);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("plain").unwrap(), "plain");
+        assert_eq!(percent_decode("caf%C3%A9").unwrap(), "café");
+        assert_eq!(percent_decode("%2F..%2f").unwrap(), "/../");
+        assert!(percent_decode("%4").is_err());
+        assert!(percent_decode("%+1").is_err());
+        assert!(percent_decode("%ff").is_err());
+    }
+
     #[test]
     fn test_noop_waker() {
         let waker = noop_waker();